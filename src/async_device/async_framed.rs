@@ -11,7 +11,7 @@ use futures_core::Stream;
 use crate::platform::offload::VirtioNetHdr;
 use crate::AsyncDevice;
 #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
-use crate::{GROTable, IDEAL_BATCH_SIZE, VIRTIO_NET_HDR_LEN};
+use crate::{GROTable, IDEAL_BATCH_SIZE};
 
 pub trait Decoder {
     /// The type of decoded frames.
@@ -560,7 +560,7 @@ impl WriteState {
     pub(crate) fn new(send_buffer_size: usize, _device: &AsyncDevice) -> WriteState {
         #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
         let packet_arena = if _device.tcp_gso() {
-            Some(PacketArena::new())
+            Some(PacketArena::new(_device.vnet_hdr_len))
         } else {
             None
         };
@@ -654,19 +654,20 @@ impl PacketSplitter {
         }
     }
     fn handle(&mut self, dev: &AsyncDevice, input: &mut [u8]) -> io::Result<()> {
-        if input.len() <= VIRTIO_NET_HDR_LEN {
+        let vnet_hdr_len = dev.vnet_hdr_len;
+        if input.len() <= vnet_hdr_len {
             Err(io::Error::other(format!(
-                "length of packet ({}) <= VIRTIO_NET_HDR_LEN ({VIRTIO_NET_HDR_LEN})",
+                "length of packet ({}) <= vnet_hdr_len ({vnet_hdr_len})",
                 input.len(),
             )))?
         }
         for buf in &mut self.bufs {
             buf.resize(self.recv_buffer_size, 0);
         }
-        let hdr = VirtioNetHdr::decode(&input[..VIRTIO_NET_HDR_LEN])?;
+        let hdr = VirtioNetHdr::decode(&input[..vnet_hdr_len])?;
         let num = dev.handle_virtio_read(
             hdr,
-            &mut input[VIRTIO_NET_HDR_LEN..],
+            &mut input[vnet_hdr_len..],
             &mut self.bufs,
             &mut self.sizes,
             0,
@@ -695,15 +696,17 @@ impl PacketSplitter {
 #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
 struct PacketArena {
     gro_table: GROTable,
+    vnet_hdr_len: usize,
     offset: usize,
     bufs: Vec<BytesMut>,
     send_index: usize,
 }
 #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
 impl PacketArena {
-    fn new() -> PacketArena {
+    fn new(vnet_hdr_len: usize) -> PacketArena {
         Self {
             gro_table: Default::default(),
+            vnet_hdr_len,
             offset: 0,
             bufs: Vec::with_capacity(IDEAL_BATCH_SIZE),
             send_index: 0,
@@ -714,12 +717,12 @@ impl PacketArena {
             let buf = &mut self.bufs[self.offset];
             self.offset += 1;
             buf.clear();
-            buf.reserve(VIRTIO_NET_HDR_LEN + 65536);
+            buf.reserve(self.vnet_hdr_len + 65536);
             return buf;
         }
         assert_eq!(self.offset, self.bufs.len());
         self.bufs
-            .push(BytesMut::with_capacity(VIRTIO_NET_HDR_LEN + 65536));
+            .push(BytesMut::with_capacity(self.vnet_hdr_len + 65536));
         let idx = self.offset;
         self.offset += 1;
         &mut self.bufs[idx]
@@ -733,10 +736,13 @@ impl PacketArena {
         }
         crate::platform::offload::handle_gro(
             &mut self.bufs[..self.offset],
-            VIRTIO_NET_HDR_LEN,
+            self.vnet_hdr_len,
+            self.vnet_hdr_len,
             &mut self.gro_table.tcp_gro_table,
             &mut self.gro_table.udp_gro_table,
             dev.udp_gso,
+            false,
+            dev.is_tap(),
             &mut self.gro_table.to_write,
         )
     }
@@ -810,7 +816,9 @@ where
         self.state.rd.clear();
         #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
         if self.state.packet_splitter.is_some() {
-            self.state.rd.reserve(VIRTIO_NET_HDR_LEN + 65536);
+            self.state
+                .rd
+                .reserve(self.dev.borrow().vnet_hdr_len + 65536);
         }
         self.state.rd.reserve(self.state.recv_buffer_size);
         let buf = unsafe { &mut *(self.state.rd.chunk_mut() as *mut _ as *mut [u8]) };
@@ -871,8 +879,9 @@ where
     {
         #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
         if let Some(packet_arena) = &mut self.state.packet_arena {
+            let vnet_hdr_len = packet_arena.vnet_hdr_len;
             let buf = packet_arena.get();
-            buf.resize(VIRTIO_NET_HDR_LEN, 0);
+            buf.resize(vnet_hdr_len, 0);
             self.codec.encode(item, buf)?;
             return Ok(());
         }
@@ -0,0 +1,76 @@
+use crate::platform::linux::events::LinkWatcher;
+use crate::platform::linux::netlink::NetlinkSocket;
+use crate::{AsyncDevice, LinkEvent};
+use futures_core::Stream;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+#[cfg(feature = "async_tokio")]
+type AsyncSocket = ::tokio::io::unix::AsyncFd<NetlinkSocket>;
+#[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+type AsyncSocket = ::async_io::Async<NetlinkSocket>;
+
+/// A stream of [`LinkEvent`]s of one device, created by [`AsyncDevice::watch_events`].
+///
+/// The stream ends after [`LinkEvent::Deleted`].
+/// If notifications are lost because the stream was not polled for a long time,
+/// the link state and addresses are re-read and the differences are reported.
+pub struct LinkEvents {
+    socket: AsyncSocket,
+    watcher: LinkWatcher,
+}
+
+impl AsyncDevice {
+    /// Watches the device for changes made outside this handle, such as
+    /// `ip link set down`, renames, MTU and address changes.
+    ///
+    /// Only changes after this call are reported.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use tun_rs::{DeviceBuilder, LinkEvent};
+    /// # async fn run() -> std::io::Result<()> {
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .build_async()?;
+    /// let mut events = dev.watch_events()?;
+    /// while let Some(event) = events.next().await {
+    ///     if event? == LinkEvent::Down {
+    ///         dev.enabled(true)?;
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch_events(&self) -> io::Result<LinkEvents> {
+        let (socket, watcher) = LinkWatcher::subscribe(self.if_index()?)?;
+        // SAFETY: the socket owns its fd, which stays open until the socket is dropped.
+        #[cfg(feature = "async_tokio")]
+        let socket = unsafe {
+            AsyncSocket::register_with_interest(socket, ::tokio::io::Interest::READABLE)?
+        };
+        #[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+        let socket = AsyncSocket::new(socket)?;
+        Ok(LinkEvents { socket, watcher })
+    }
+}
+
+impl Stream for LinkEvents {
+    type Item = io::Result<LinkEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match this.watcher.next_event(this.socket.get_ref()) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                rs => return Poll::Ready(rs.transpose()),
+            }
+            #[cfg(feature = "async_tokio")]
+            ready!(this.socket.poll_read_ready(cx))?.clear_ready();
+            #[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+            ready!(this.socket.poll_readable(cx))?;
+        }
+    }
+}
@@ -24,6 +24,22 @@ pub use windows::AsyncDevice;
 )]
 pub mod async_framed;
 
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+mod link_events;
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+pub use link_events::LinkEvents;
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+mod multi_queue;
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+pub use multi_queue::MultiQueueDevice;
+#[cfg(all(
+    target_os = "linux",
+    not(target_env = "ohos"),
+    feature = "async_framed"
+))]
+#[cfg_attr(docsrs, doc(cfg(feature = "async_framed")))]
+pub use multi_queue::MultiQueueStream;
+
 #[cfg(all(feature = "async_tokio", feature = "async_io", not(doc)))]
 compile_error! {"More than one asynchronous runtime is simultaneously specified in features"}
 
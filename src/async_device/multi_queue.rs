@@ -0,0 +1,245 @@
+use crate::platform::{ExpandBuffer, GROTable};
+use crate::AsyncDevice;
+use libc::{c_short, IFF_TAP, IPPROTO_TCP, IPPROTO_UDP};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::sync::Arc;
+
+/// A multi-queue device with one [`AsyncDevice`] per queue.
+///
+/// Received packets can be consumed per queue, typically with one task per queue,
+/// or through a single merged stream (feature `async_framed`).
+/// Outgoing packets are dispatched by a hash of their flow, so all packets of
+/// a TCP/UDP connection leave through the same queue and keep their order.
+///
+/// # Example
+/// ```no_run
+/// use tun_rs::DeviceBuilder;
+/// # async fn run() -> std::io::Result<()> {
+/// let dev = DeviceBuilder::new()
+///     .ipv4("10.0.0.1", 24, None)
+///     .build_multi_queue(4)?;
+/// for queue in dev.queues() {
+///     let queue = queue.clone();
+///     tokio::spawn(async move {
+///         let mut buf = vec![0; 65536];
+///         while let Ok(len) = queue.recv(&mut buf).await {
+///             println!("{:?}", &buf[..len]);
+///         }
+///     });
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct MultiQueueDevice {
+    queues: Vec<Arc<AsyncDevice>>,
+}
+
+impl MultiQueueDevice {
+    /// Creates `queues` queues in total by cloning `device` with [`AsyncDevice::try_clone`].
+    ///
+    /// # Prerequisites
+    /// - The `IFF_MULTI_QUEUE` flag must be enabled.
+    pub fn new(device: AsyncDevice, queues: usize) -> io::Result<Self> {
+        if queues == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "at least one queue is required",
+            ));
+        }
+        let mut list = Vec::with_capacity(queues);
+        for _ in 1..queues {
+            list.push(Arc::new(device.try_clone()?));
+        }
+        list.insert(0, Arc::new(device));
+        Ok(Self { queues: list })
+    }
+    /// Returns the number of queues.
+    pub fn len(&self) -> usize {
+        self.queues.len()
+    }
+    /// Returns true if there are no queues, which cannot happen for a built device.
+    pub fn is_empty(&self) -> bool {
+        self.queues.is_empty()
+    }
+    /// Returns an iterator over the queues.
+    pub fn queues(&self) -> std::slice::Iter<'_, Arc<AsyncDevice>> {
+        self.queues.iter()
+    }
+    /// Returns the queue that packets of the same flow as `packet` are sent on.
+    ///
+    /// `packet` is an IP packet (an Ethernet frame for TAP devices),
+    /// preceded by the virtio-net header if offload is enabled.
+    pub fn queue_for(&self, packet: &[u8]) -> &Arc<AsyncDevice> {
+        &self.queues[self.queue_index(packet, self.queues[0].vnet_hdr_len())]
+    }
+    /// Sends a packet on the queue chosen by [`MultiQueueDevice::queue_for`].
+    ///
+    /// With offload enabled, `buf` must start with a virtio-net header;
+    /// [`MultiQueueDevice::send_multiple`] fills it in instead.
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.queue_for(buf).send(buf).await
+    }
+    /// Sends multiple packets, dispatching each flow to its queue.
+    ///
+    /// Works like [`AsyncDevice::send_multiple`]; the packets of each queue are sent
+    /// in their order in `bufs`, and the order of `bufs` is left unchanged.
+    pub async fn send_multiple<B: ExpandBuffer>(
+        &self,
+        gro_table: &mut GROTable,
+        bufs: &mut [B],
+        offset: usize,
+    ) -> io::Result<usize> {
+        let mut queued: Vec<Vec<&mut B>> = self.queues.iter().map(|_| Vec::new()).collect();
+        for buf in bufs.iter_mut() {
+            queued[self.queue_index(buf.as_ref(), offset)].push(buf);
+        }
+        let mut total = 0;
+        for (queue, mut bufs) in self.queues.iter().zip(queued) {
+            if !bufs.is_empty() {
+                total += queue.send_multiple(gro_table, &mut bufs, offset).await?;
+            }
+        }
+        Ok(total)
+    }
+    fn queue_index(&self, buf: &[u8], offset: usize) -> usize {
+        let tap = self.queues[0].flags & IFF_TAP as c_short != 0;
+        let hash = flow_hash(buf.get(offset..).unwrap_or_default(), tap);
+        (hash % self.queues.len() as u64) as usize
+    }
+}
+
+impl IntoIterator for MultiQueueDevice {
+    type Item = Arc<AsyncDevice>;
+    type IntoIter = std::vec::IntoIter<Arc<AsyncDevice>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.queues.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a MultiQueueDevice {
+    type Item = &'a Arc<AsyncDevice>;
+    type IntoIter = std::slice::Iter<'a, Arc<AsyncDevice>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.queues.iter()
+    }
+}
+
+/// Hashes the addresses, protocol and ports of an IP packet.
+///
+/// Fragments are hashed without ports so that they stay with their first fragment.
+fn flow_hash(packet: &[u8], tap: bool) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let packet = if tap {
+        match packet.get(12..14) {
+            Some([0x08, 0x00]) | Some([0x86, 0xdd]) => &packet[14..],
+            _ => {
+                packet.get(..12).hash(&mut hasher);
+                return hasher.finish();
+            }
+        }
+    } else {
+        packet
+    };
+    let (addrs, protocol, l4) = match packet.first().map(|v| v >> 4) {
+        Some(4) if packet.len() >= 20 => {
+            let ihl = (packet[0] & 0x0f) as usize * 4;
+            let fragmented = u16::from_be_bytes([packet[6], packet[7]]) & 0x3fff != 0;
+            let l4 = if fragmented { None } else { packet.get(ihl..) };
+            (&packet[12..20], packet[9], l4)
+        }
+        Some(6) if packet.len() >= 40 => (&packet[8..40], packet[6], packet.get(40..)),
+        _ => return 0,
+    };
+    addrs.hash(&mut hasher);
+    protocol.hash(&mut hasher);
+    if protocol as i32 == IPPROTO_TCP || protocol as i32 == IPPROTO_UDP {
+        if let Some(ports) = l4.and_then(|l4| l4.get(..4)) {
+            ports.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+#[cfg(feature = "async_framed")]
+mod stream {
+    use super::MultiQueueDevice;
+    use crate::async_framed::{BytesCodec, DeviceFramedRead};
+    use crate::AsyncDevice;
+    use bytes::BytesMut;
+    use futures_core::Stream;
+    use std::io;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+
+    /// A stream of packets received on any queue of a [`MultiQueueDevice`].
+    ///
+    /// Queues are polled round-robin. With offload enabled,
+    /// GSO packets are split into individual IP packets.
+    pub struct MultiQueueStream {
+        readers: Vec<DeviceFramedRead<BytesCodec, Arc<AsyncDevice>>>,
+        next: usize,
+    }
+
+    impl MultiQueueDevice {
+        /// Returns a merged stream of the packets received on all queues.
+        ///
+        /// # Example
+        /// ```no_run
+        /// use futures::StreamExt;
+        /// use tun_rs::DeviceBuilder;
+        /// # async fn run() -> std::io::Result<()> {
+        /// let dev = DeviceBuilder::new()
+        ///     .ipv4("10.0.0.1", 24, None)
+        ///     .build_multi_queue(4)?;
+        /// let mut stream = dev.recv_stream();
+        /// while let Some(packet) = stream.next().await {
+        ///     let packet = packet?;
+        ///     dev.send(&packet).await?;
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn recv_stream(&self) -> MultiQueueStream {
+            MultiQueueStream {
+                readers: self
+                    .queues
+                    .iter()
+                    .map(|queue| DeviceFramedRead::new(queue.clone(), BytesCodec::new()))
+                    .collect(),
+                next: 0,
+            }
+        }
+    }
+
+    impl Stream for MultiQueueStream {
+        type Item = io::Result<BytesMut>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            let len = this.readers.len();
+            let mut closed = 0;
+            for i in 0..len {
+                let index = (this.next + i) % len;
+                match Pin::new(&mut this.readers[index]).poll_next(cx) {
+                    Poll::Ready(Some(item)) => {
+                        this.next = index + 1;
+                        return Poll::Ready(Some(item));
+                    }
+                    Poll::Ready(None) => closed += 1,
+                    Poll::Pending => {}
+                }
+            }
+            if closed == len {
+                Poll::Ready(None)
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+}
+#[cfg(feature = "async_framed")]
+pub use stream::MultiQueueStream;
@@ -1,5 +1,7 @@
 #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
-use crate::platform::offload::{handle_gro, VirtioNetHdr, VIRTIO_NET_HDR_LEN};
+use crate::platform::gso::GsoWrite;
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+use crate::platform::offload::{complete_partial_checksum, handle_gro, VirtioNetHdr};
 use crate::platform::DeviceImpl;
 #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
 use crate::platform::GROTable;
@@ -178,7 +180,7 @@ impl AsyncDevice {
     /// Recv a packet from the device.
     /// If offload is enabled. This method can be used to obtain processed data.
     ///
-    /// original_buffer is used to store raw data, including the VirtioNetHdr and the unsplit IP packet. The recommended size is 10 + 65535.
+    /// original_buffer is used to store raw data, including the VirtioNetHdr and the unsplit IP packet. The recommended size is [`DeviceImpl::vnet_hdr_len`] + 65535.
     /// bufs and sizes are used to store the segmented IP packets. bufs.len == sizes.len > 65535/MTU
     /// offset: Starting position
     #[cfg(target_os = "linux")]
@@ -195,15 +197,16 @@ impl AsyncDevice {
         let tun = self.get_ref();
         if tun.vnet_hdr {
             let len = self.recv(original_buffer).await?;
-            if len <= VIRTIO_NET_HDR_LEN {
+            let vnet_hdr_len = tun.vnet_hdr_len;
+            if len <= vnet_hdr_len {
                 Err(io::Error::other(format!(
-                    "length of packet ({len}) <= VIRTIO_NET_HDR_LEN ({VIRTIO_NET_HDR_LEN})",
+                    "length of packet ({len}) <= vnet_hdr_len ({vnet_hdr_len})",
                 )))?
             }
-            let hdr = VirtioNetHdr::decode(&original_buffer[..VIRTIO_NET_HDR_LEN])?;
+            let hdr = VirtioNetHdr::decode(&original_buffer[..vnet_hdr_len])?;
             tun.handle_virtio_read(
                 hdr,
-                &mut original_buffer[VIRTIO_NET_HDR_LEN..len],
+                &mut original_buffer[vnet_hdr_len..len],
                 bufs,
                 sizes,
                 offset,
@@ -214,9 +217,38 @@ impl AsyncDevice {
             Ok(1)
         }
     }
+    /// Receives a packet without splitting GSO super-packets,
+    /// see [`DeviceImpl::recv_gso`].
+    #[cfg(target_os = "linux")]
+    pub async fn recv_gso<'a>(
+        &self,
+        buf: &'a mut [u8],
+    ) -> io::Result<(crate::GsoInfo, &'a mut [u8])> {
+        let len = self.recv(buf).await?;
+        self.get_ref().gso_packet(buf, len)
+    }
+    /// Sends a TCP or UDP GSO super-packet,
+    /// see [`DeviceImpl::send_gso`].
+    #[cfg(target_os = "linux")]
+    pub async fn send_gso(&self, packet: &mut [u8], info: crate::GsoInfo) -> io::Result<usize> {
+        match self.get_ref().prepare_gso(packet, info)? {
+            GsoWrite::Offload(hdr) => {
+                self.send_vectored(&[IoSlice::new(&hdr), IoSlice::new(packet)])
+                    .await?;
+            }
+            GsoWrite::Split(hdr, info) => {
+                for segment in info.iter_segments(packet)? {
+                    let [header, payload] = segment.io_slices();
+                    self.send_vectored(&[IoSlice::new(&hdr), header, payload])
+                        .await?;
+                }
+            }
+        }
+        Ok(packet.len())
+    }
     /// send multiple fragmented data packets.
     /// GROTable can be reused, as it is used to assist in data merging.
-    /// Offset is the starting position of the data. Need to meet offset>10.
+    /// Offset is the starting position of the data. Need to meet offset>=[`DeviceImpl::vnet_hdr_len`].
     #[cfg(target_os = "linux")]
     pub async fn send_multiple<B: crate::platform::ExpandBuffer>(
         &self,
@@ -230,14 +262,20 @@ impl AsyncDevice {
             handle_gro(
                 bufs,
                 offset,
+                tun.vnet_hdr_len,
                 &mut gro_table.tcp_gro_table,
                 &mut gro_table.udp_gro_table,
                 tun.udp_gso,
+                gro_table.checksum_partial,
+                tun.is_tap(),
                 &mut gro_table.to_write,
             )?;
-            offset -= VIRTIO_NET_HDR_LEN;
+            offset -= tun.vnet_hdr_len;
         } else {
-            for i in 0..bufs.len() {
+            for (i, buf) in bufs.iter_mut().enumerate() {
+                if gro_table.checksum_partial {
+                    complete_partial_checksum(&mut buf.as_mut()[offset..], tun.is_tap());
+                }
                 gro_table.to_write.push(i);
             }
         }
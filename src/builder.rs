@@ -26,6 +26,10 @@ pub enum Layer {
     L3,
 }
 
+/// An IPv6 address and prefix length to add with [`AddressOptions`](crate::AddressOptions).
+#[cfg(target_os = "linux")]
+type Ipv6WithOptions = (io::Result<(Ipv6Addr, u8)>, crate::AddressOptions);
+
 /// Configuration for a TUN/TAP interface.
 ///
 /// This structure stores settings such as the device name, operating layer,
@@ -94,6 +98,18 @@ pub(crate) struct DeviceConfig {
     /// Enable multi queue support
     #[cfg(target_os = "linux")]
     pub(crate) multi_queue: Option<bool>,
+    /// Fail instead of degrading when a requested feature is unavailable
+    #[cfg(target_os = "linux")]
+    pub(crate) strict: Option<bool>,
+    /// Count the packets read and written in userspace
+    #[cfg(target_os = "linux")]
+    pub(crate) io_counters: Option<bool>,
+    /// Only attach to an existing device instead of creating one
+    #[cfg(target_os = "linux")]
+    pub(crate) attach_existing: Option<bool>,
+    /// Length of the virtio-net header when offload is enabled
+    #[cfg(target_os = "linux")]
+    pub(crate) vnet_hdr_len: Option<usize>,
 }
 type IPV4 = (
     io::Result<Ipv4Addr>,
@@ -265,12 +281,106 @@ impl DeviceBuilderGuard<'_> {
         self.0.offload = Some(offload);
         self
     }
+    /// Sets the length of the virtio-net header on Linux, see [`DeviceBuilder::vnet_hdr_len`].
+    #[cfg(target_os = "linux")]
+    pub fn vnet_hdr_len(&mut self, len: usize) -> &mut Self {
+        self.0.vnet_hdr_len = Some(len);
+        self
+    }
     /// Enables multi-queue support on Linux.
     #[cfg(target_os = "linux")]
     pub fn multi_queue(&mut self, multi_queue: bool) -> &mut Self {
         self.0.multi_queue = Some(multi_queue);
         self
     }
+    /// Sets the send buffer limit of the device on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn sndbuf(&mut self, sndbuf: i32) -> &mut Self {
+        self.0.sndbuf = Some(sndbuf);
+        self
+    }
+    /// Sets how IPv6 addresses are generated for the device on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn ipv6_addr_gen_mode(&mut self, mode: crate::Ipv6AddrGenMode) -> &mut Self {
+        self.0.ipv6_addr_gen_mode = Some(mode);
+        self
+    }
+    /// Disables or enables IPv6 on the device on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn disable_ipv6(&mut self, disabled: bool) -> &mut Self {
+        self.0.disable_ipv6 = Some(disabled);
+        self
+    }
+    /// Sets whether router advertisements are accepted on the device on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn accept_ra(&mut self, accept: bool) -> &mut Self {
+        self.0.accept_ra = Some(accept);
+        self
+    }
+    /// Adds a route through the device on Linux.
+    /// Routes are installed via rtnetlink after the device has been enabled.
+    #[cfg(target_os = "linux")]
+    pub fn route<R: Into<crate::Route>>(&mut self, route: R) -> &mut Self {
+        self.0
+            .routes
+            .get_or_insert_with(Vec::new)
+            .push(route.into());
+        self
+    }
+    /// Adds a policy routing rule owned by the device on Linux, see [`DeviceBuilder::rule`].
+    #[cfg(target_os = "linux")]
+    pub fn rule(&mut self, rule: crate::RoutingRule) -> &mut Self {
+        self.0.rules.get_or_insert_with(Vec::new).push(rule);
+        self
+    }
+    /// Enables strict mode on Linux: fail instead of degrading when a requested offload is unavailable.
+    #[cfg(target_os = "linux")]
+    pub fn strict(&mut self, strict: bool) -> &mut Self {
+        self.0.strict = Some(strict);
+        self
+    }
+    /// Enables userspace packet counters on Linux, see [`DeviceImpl::io_counters`].
+    #[cfg(target_os = "linux")]
+    pub fn io_counters(&mut self, enable: bool) -> &mut Self {
+        self.0.io_counters = Some(enable);
+        self
+    }
+    /// Creates and configures the device in the given network namespace on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn netns<N: Into<crate::NetNs>>(&mut self, netns: N) -> &mut Self {
+        self.0.netns = Some(netns.into());
+        self
+    }
+    /// Enslaves the device to the bridge or bond `master` on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn master(&mut self, master: &str) -> &mut Self {
+        self.0.master = Some(master.to_string());
+        self
+    }
+    /// Creates the master as a bridge if it does not exist on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn create_bridge(&mut self, create: bool) -> &mut Self {
+        self.0.create_bridge = Some(create);
+        self
+    }
+    /// Makes the device persistent (or not) on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn persist(&mut self, persist: bool) -> &mut Self {
+        self.0.persist = Some(persist);
+        self
+    }
+    /// Sets the user allowed to open the device on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn owner(&mut self, uid: i32) -> &mut Self {
+        self.0.owner = Some(uid);
+        self
+    }
+    /// Sets the group allowed to open the device on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn group(&mut self, gid: i32) -> &mut Self {
+        self.0.group = Some(gid);
+        self
+    }
     /// Enables or disables packet information for the network driver(TUN)
     /// on macOS, Linux, freebsd, openbsd, netbsd.
     ///
@@ -354,6 +464,8 @@ pub struct DeviceBuilder {
     mtu_v6: Option<u16>,
     ipv4: Option<IPV4>,
     ipv6: Option<Vec<(io::Result<Ipv6Addr>, io::Result<u8>)>>,
+    #[cfg(target_os = "linux")]
+    ipv6_with: Option<Vec<Ipv6WithOptions>>,
     layer: Option<Layer>,
     #[cfg(any(
         target_os = "windows",
@@ -394,6 +506,38 @@ pub struct DeviceBuilder {
     /// Enable multi queue support
     #[cfg(target_os = "linux")]
     multi_queue: Option<bool>,
+    #[cfg(target_os = "linux")]
+    routes: Option<Vec<crate::Route>>,
+    #[cfg(target_os = "linux")]
+    rules: Option<Vec<crate::RoutingRule>>,
+    #[cfg(target_os = "linux")]
+    netns: Option<crate::NetNs>,
+    #[cfg(target_os = "linux")]
+    strict: Option<bool>,
+    #[cfg(target_os = "linux")]
+    io_counters: Option<bool>,
+    #[cfg(target_os = "linux")]
+    master: Option<String>,
+    #[cfg(target_os = "linux")]
+    create_bridge: Option<bool>,
+    #[cfg(target_os = "linux")]
+    persist: Option<bool>,
+    #[cfg(target_os = "linux")]
+    owner: Option<i32>,
+    #[cfg(target_os = "linux")]
+    group: Option<i32>,
+    #[cfg(target_os = "linux")]
+    attach_existing: Option<bool>,
+    #[cfg(target_os = "linux")]
+    vnet_hdr_len: Option<usize>,
+    #[cfg(target_os = "linux")]
+    sndbuf: Option<i32>,
+    #[cfg(target_os = "linux")]
+    ipv6_addr_gen_mode: Option<crate::Ipv6AddrGenMode>,
+    #[cfg(target_os = "linux")]
+    disable_ipv6: Option<bool>,
+    #[cfg(target_os = "linux")]
+    accept_ra: Option<bool>,
 }
 
 impl DeviceBuilder {
@@ -489,6 +633,33 @@ impl DeviceBuilder {
 
         self
     }
+    /// Configures a single IPv6 address with flags and lifetimes on Linux,
+    /// see [`AddressOptions`](crate::AddressOptions).
+    ///
+    /// Use `AddressOptions::new().nodad(true)` so the address is usable as soon as
+    /// the device is up, instead of staying tentative during duplicate address detection.
+    /// # Example
+    /// ```
+    /// use tun_rs::{AddressOptions, DeviceBuilder};
+    /// DeviceBuilder::new().ipv6_with(
+    ///     "CDCD:910A:2222:5498:8475:1111:3900:2021",
+    ///     64,
+    ///     AddressOptions::new().nodad(true),
+    /// );
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn ipv6_with<IPv6: ToIpv6Address, Netmask: ToIpv6Netmask>(
+        mut self,
+        address: IPv6,
+        mask: Netmask,
+        options: crate::AddressOptions,
+    ) -> Self {
+        self.ipv6_with.get_or_insert_with(Vec::new).push((
+            address.ipv6().and_then(|v| Ok((v, mask.prefix()?))),
+            options,
+        ));
+        self
+    }
     /// Configures multiple IPv6 addresses in batch.
     ///
     /// Accepts a slice of (IPv6 address, netmask) tuples.
@@ -583,12 +754,234 @@ impl DeviceBuilder {
         self.offload = Some(offload);
         self
     }
+    /// Sets the length of the virtio-net header that precedes packets when offload
+    /// is enabled on Linux (`TUNSETVNETHDRSZ`).
+    ///
+    /// Defaults to [`VIRTIO_NET_HDR_LEN`](crate::VIRTIO_NET_HDR_LEN);
+    /// use [`VIRTIO_NET_HDR_MRG_RXBUF_LEN`](crate::VIRTIO_NET_HDR_MRG_RXBUF_LEN)
+    /// for the `virtio_net_hdr_mrg_rxbuf` layout. The offset passed to `send_multiple`
+    /// must be at least [`DeviceImpl::vnet_hdr_len`].
+    #[cfg(target_os = "linux")]
+    pub fn vnet_hdr_len(mut self, len: usize) -> Self {
+        self.vnet_hdr_len = Some(len);
+        self
+    }
     /// Enables multi-queue support on Linux.
     #[cfg(target_os = "linux")]
     pub fn multi_queue(mut self, multi_queue: bool) -> Self {
         self.multi_queue = Some(multi_queue);
         self
     }
+    /// Sets the send buffer limit of the device on Linux, see [`DeviceImpl::set_sndbuf`].
+    #[cfg(target_os = "linux")]
+    pub fn sndbuf(mut self, sndbuf: i32) -> Self {
+        self.sndbuf = Some(sndbuf);
+        self
+    }
+    /// Sets how IPv6 addresses are generated for the device on Linux,
+    /// see [`DeviceImpl::set_ipv6_addr_gen_mode`].
+    ///
+    /// Applied before the device is enabled, so [`Ipv6AddrGenMode::None`](crate::Ipv6AddrGenMode::None)
+    /// keeps the kernel from assigning a `fe80::` address and sending router solicitations.
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, Ipv6AddrGenMode};
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .ipv6_addr_gen_mode(Ipv6AddrGenMode::None)
+    ///     .accept_ra(false)
+    ///     .build_sync()
+    ///     .unwrap();
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn ipv6_addr_gen_mode(mut self, mode: crate::Ipv6AddrGenMode) -> Self {
+        self.ipv6_addr_gen_mode = Some(mode);
+        self
+    }
+    /// Disables or enables IPv6 on the device on Linux, see [`DeviceImpl::set_ipv6_disabled`].
+    ///
+    /// IPv6 addresses cannot be added while IPv6 is disabled.
+    #[cfg(target_os = "linux")]
+    pub fn disable_ipv6(mut self, disabled: bool) -> Self {
+        self.disable_ipv6 = Some(disabled);
+        self
+    }
+    /// Sets whether router advertisements are accepted on the device on Linux,
+    /// see [`DeviceImpl::set_accept_ra`].
+    #[cfg(target_os = "linux")]
+    pub fn accept_ra(mut self, accept: bool) -> Self {
+        self.accept_ra = Some(accept);
+        self
+    }
+    /// Adds a route through the device on Linux.
+    ///
+    /// Accepts an `IpNet` or a [`Route`](crate::Route) carrying a metric, table ID or source address.
+    /// Routes are installed via rtnetlink after the device has been enabled,
+    /// so they require the device to be enabled (the default).
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, Route};
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .route("10.1.0.0/16".parse::<ipnet::IpNet>().unwrap())
+    ///     .route(Route::new("10.2.0.0/16".parse().unwrap()).metric(100))
+    ///     .build_sync()
+    ///     .unwrap();
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn route<R: Into<crate::Route>>(mut self, route: R) -> Self {
+        self.routes.get_or_insert_with(Vec::new).push(route.into());
+        self
+    }
+    /// Adds a policy routing rule on Linux.
+    ///
+    /// Rules are installed after the routes and are owned by the device:
+    /// they are removed when its last queue is dropped, see [`DeviceImpl::add_rule`].
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, Route, RoutingRule};
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .route(Route::new("0.0.0.0/0".parse().unwrap()).table(51820))
+    ///     .rule(RoutingRule::ipv4(51820).fwmark(0xca6c).invert(true))
+    ///     .build_sync()
+    ///     .unwrap();
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn rule(mut self, rule: crate::RoutingRule) -> Self {
+        self.rules.get_or_insert_with(Vec::new).push(rule);
+        self
+    }
+    /// Enables strict mode on Linux.
+    ///
+    /// By default, when `offload(true)` is requested but the kernel rejects
+    /// TCP or UDP segmentation offload, the device is created with fewer offloads
+    /// and a warning is logged. In strict mode, building fails with
+    /// [`io::ErrorKind::Unsupported`] instead.
+    /// Use [`DeviceBuilder::probe`] to check what is available beforehand.
+    #[cfg(target_os = "linux")]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = Some(strict);
+        self
+    }
+    /// Enables userspace packet counters on Linux.
+    ///
+    /// Every successful read and write on the device and its queues is counted,
+    /// and the totals are available from [`DeviceImpl::io_counters`].
+    /// Compare them with [`DeviceImpl::statistics`] to see how much the kernel dropped.
+    #[cfg(target_os = "linux")]
+    pub fn io_counters(mut self, enable: bool) -> Self {
+        self.io_counters = Some(enable);
+        self
+    }
+    /// Enslaves the device to the bridge or bond `master` on Linux,
+    /// like `ip link set <dev> master <master>`.
+    ///
+    /// Bridges only accept Ethernet devices, so use it together with `Layer::L2`.
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, Layer};
+    /// let dev = DeviceBuilder::new()
+    ///     .layer(Layer::L2)
+    ///     .master("br0")
+    ///     .create_bridge(true)
+    ///     .build_sync()
+    ///     .unwrap();
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn master(mut self, master: &str) -> Self {
+        self.master = Some(master.to_string());
+        self
+    }
+    /// Creates the master set with [`DeviceBuilder::master`] as a bridge,
+    /// and brings it up, if no interface with that name exists on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn create_bridge(mut self, create: bool) -> Self {
+        self.create_bridge = Some(create);
+        self
+    }
+    /// Makes the device persistent on Linux, so it outlives the process (`TUNSETPERSIST`).
+    ///
+    /// `persist(false)` makes an existing persistent device non-persistent again.
+    /// It is applied last, so a device is not left behind if the configuration fails.
+    /// Use [`DeviceImpl::destroy`] to remove a persistent device.
+    #[cfg(target_os = "linux")]
+    pub fn persist(mut self, persist: bool) -> Self {
+        self.persist = Some(persist);
+        self
+    }
+    /// Sets the user allowed to open the device without `CAP_NET_ADMIN` on Linux (`TUNSETOWNER`).
+    ///
+    /// Mostly useful together with [`DeviceBuilder::persist`].
+    #[cfg(target_os = "linux")]
+    pub fn owner(mut self, uid: i32) -> Self {
+        self.owner = Some(uid);
+        self
+    }
+    /// Sets the group allowed to open the device without `CAP_NET_ADMIN` on Linux (`TUNSETGROUP`).
+    #[cfg(target_os = "linux")]
+    pub fn group(mut self, gid: i32) -> Self {
+        self.group = Some(gid);
+        self
+    }
+    /// Attaches to the existing device `name` on Linux instead of creating a new one.
+    ///
+    /// Building fails with [`io::ErrorKind::NotFound`] if the device does not exist, and with
+    /// [`io::ErrorKind::InvalidInput`] if it is not a TUN/TAP device or if its layer or
+    /// `multi_queue` flag differs from this builder's settings.
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    /// let dev = DeviceBuilder::new()
+    ///     .attach_existing("tun7")
+    ///     .build_sync()
+    ///     .unwrap();
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn attach_existing<S: Into<String>>(mut self, name: S) -> Self {
+        self.dev_name = Some(name.into());
+        self.attach_existing = Some(true);
+        self
+    }
+    /// Probes the TUN/TAP features and offloads supported by the kernel on Linux.
+    ///
+    /// This briefly creates a temporary TUN device, so it needs the same privileges as building one.
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    /// let capabilities = DeviceBuilder::probe().unwrap();
+    /// let dev = DeviceBuilder::new()
+    ///     .offload(capabilities.tcp_offload)
+    ///     .multi_queue(capabilities.multi_queue)
+    ///     .strict(true)
+    ///     .build_sync()
+    ///     .unwrap();
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn probe() -> io::Result<crate::TunCapabilities> {
+        DeviceImpl::probe()
+    }
+    /// Creates the device in the given network namespace on Linux.
+    ///
+    /// Accepts a namespace path such as `/run/netns/tenant1` or an open namespace file descriptor.
+    /// The device is created and all builder configuration is applied from a helper thread
+    /// that has joined the namespace; the calling thread's namespace is not changed.
+    /// Later configuration calls must run in that namespace as well, see [`NetNs::run`](crate::NetNs::run).
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    /// let dev = DeviceBuilder::new()
+    ///     .netns("/run/netns/tenant1")
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .mtu(1400)
+    ///     .build_sync()
+    ///     .unwrap();
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn netns<N: Into<crate::NetNs>>(mut self, netns: N) -> Self {
+        self.netns = Some(netns.into());
+        self
+    }
     /// Enables or disables packet information for the network driver(TUN)
     /// on macOS, Linux, freebsd, openbsd, netbsd.
     ///
@@ -701,9 +1094,18 @@ impl DeviceBuilder {
             offload: self.offload.take(),
             #[cfg(target_os = "linux")]
             multi_queue: self.multi_queue.take(),
+            #[cfg(target_os = "linux")]
+            strict: self.strict.take(),
+            #[cfg(target_os = "linux")]
+            io_counters: self.io_counters.take(),
+            #[cfg(target_os = "linux")]
+            attach_existing: self.attach_existing.take(),
+            #[cfg(target_os = "linux")]
+            vnet_hdr_len: self.vnet_hdr_len.take(),
         }
     }
     pub(crate) fn config(self, device: &DeviceImpl) -> io::Result<()> {
+        #[cfg(not(target_os = "linux"))]
         if let Some(mtu) = self.mtu {
             device.set_mtu(mtu)?;
         }
@@ -719,8 +1121,23 @@ impl DeviceBuilder {
         if let Some(tx_queue_len) = self.tx_queue_len {
             device.set_tx_queue_len(tx_queue_len)?;
         }
+        #[cfg(target_os = "linux")]
+        if let Some(sndbuf) = self.sndbuf {
+            device.set_sndbuf(sndbuf)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(mode) = self.ipv6_addr_gen_mode {
+            device.set_ipv6_addr_gen_mode(mode)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(accept) = self.accept_ra {
+            device.set_accept_ra(accept)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(disabled) = self.disable_ipv6 {
+            device.set_ipv6_disabled(disabled)?;
+        }
         #[cfg(any(
-            target_os = "linux",
             target_os = "freebsd",
             target_os = "macos",
             target_os = "openbsd",
@@ -730,12 +1147,14 @@ impl DeviceBuilder {
             device.set_mac_address(mac_addr)?;
         }
 
+        #[cfg(not(target_os = "linux"))]
         if let Some((address, prefix, destination)) = self.ipv4 {
             let prefix = prefix?;
             let address = address?;
             let destination = destination.transpose()?;
             device.set_network_address(address, prefix, destination)?;
         }
+        #[cfg(not(target_os = "linux"))]
         if let Some(ipv6) = self.ipv6 {
             for (address, prefix) in ipv6 {
                 let prefix = prefix?;
@@ -743,11 +1162,68 @@ impl DeviceBuilder {
                 device.add_address_v6(address, prefix)?;
             }
         }
+        #[cfg(target_os = "linux")]
+        if let Some(master) = self.master {
+            if self.create_bridge.unwrap_or(false) {
+                DeviceImpl::ensure_bridge(&master)?;
+            }
+            device.set_master(&master)?;
+        }
+        #[cfg(not(target_os = "linux"))]
         device.enabled(self.enabled.unwrap_or(true))?;
+        // The MTU, MAC address, addresses, up state and routes are applied
+        // the same way as by `DeviceConfigTransaction` on an existing device.
+        #[cfg(target_os = "linux")]
+        {
+            let mut transaction =
+                crate::DeviceConfigTransaction::new().enabled(self.enabled.unwrap_or(true));
+            if let Some(mtu) = self.mtu {
+                transaction = transaction.mtu(mtu);
+            }
+            if let Some(mac_addr) = self.mac_addr {
+                transaction = transaction.mac_addr(mac_addr);
+            }
+            if let Some((address, prefix, destination)) = self.ipv4 {
+                transaction = transaction.ipv4(address?, prefix?, destination.transpose()?);
+            }
+            for (address, prefix) in self.ipv6.into_iter().flatten() {
+                transaction = transaction.ipv6(address?, prefix?);
+            }
+            for (address, options) in self.ipv6_with.into_iter().flatten() {
+                let (address, prefix) = address?;
+                transaction = transaction.ipv6_with(address, prefix, options);
+            }
+            for route in self.routes.into_iter().flatten() {
+                transaction = transaction.route(route);
+            }
+            transaction.commit(device)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(rules) = self.rules {
+            device.add_rules(rules)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(uid) = self.owner {
+            device.user(uid)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(gid) = self.group {
+            device.group(gid)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(persist) = self.persist {
+            device.set_persist_impl(persist)?;
+        }
         Ok(())
     }
     /// Builds a synchronous device instance and applies all configuration parameters.
     pub fn build_sync(mut self) -> io::Result<SyncDevice> {
+        #[cfg(target_os = "linux")]
+        if let Some(netns) = self.netns.take() {
+            let device = netns.run(move || self.build_sync())?;
+            *device.0.netns.lock().unwrap() = Some(netns);
+            return Ok(device);
+        }
         let device = DeviceImpl::new(self.build_config())?;
         self.config(&device)?;
         Ok(SyncDevice(device))
@@ -764,6 +1240,18 @@ impl DeviceBuilder {
         let device = crate::AsyncDevice::new_dev(sync_device.0)?;
         Ok(device)
     }
+    /// Builds a multi-queue device with `queues` queues on Linux.
+    ///
+    /// Enables `multi_queue`, builds the device with [`DeviceBuilder::build_async`]
+    /// and adds the remaining queues with `try_clone`.
+    #[cfg(all(
+        target_os = "linux",
+        any(feature = "async_io", feature = "async_tokio")
+    ))]
+    pub fn build_multi_queue(self, queues: usize) -> io::Result<crate::MultiQueueDevice> {
+        let device = self.multi_queue(true).build_async()?;
+        crate::MultiQueueDevice::new(device, queues)
+    }
     /// To conveniently set the platform-specific parameters without breaking the calling chain.
     /// # Ergonomic
     ///
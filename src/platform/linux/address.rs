@@ -0,0 +1,325 @@
+use crate::platform::linux::netlink::{attr_ip, attr_u32, NetlinkMessage, NetlinkSocket};
+use crate::platform::DeviceImpl;
+use crate::{ToIpv6Address, ToIpv6Netmask};
+use ipnet::IpNet;
+use libc::{
+    ifaddrmsg, AF_INET, AF_INET6, AF_UNSPEC, IFA_ADDRESS, IFA_BROADCAST, IFA_LOCAL,
+    IFF_POINTOPOINT, NLM_F_CREATE, NLM_F_EXCL, RTM_DELADDR, RTM_GETADDR, RTM_NEWADDR,
+    RT_SCOPE_HOST, RT_SCOPE_LINK, RT_SCOPE_SITE, RT_SCOPE_UNIVERSE,
+};
+use std::io;
+use std::net::IpAddr;
+use std::ops::{BitOr, BitOrAssign};
+use std::time::Duration;
+
+/// https://github.com/torvalds/linux/blob/master/include/uapi/linux/if_addr.h
+const IFA_CACHEINFO: u16 = 6;
+const IFA_FLAGS: u16 = 8;
+/// `ifa_prefered`/`ifa_valid` of an address that does not expire.
+const INFINITY_LIFE_TIME: u32 = u32::MAX;
+
+/// The `IFA_F_*` flags of an interface address.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AddressFlags(pub u32);
+
+impl AddressFlags {
+    /// `IFA_F_SECONDARY`: a secondary (IPv4) or temporary (IPv6) address.
+    pub const SECONDARY: AddressFlags = AddressFlags(0x01);
+    /// `IFA_F_NODAD`: duplicate address detection is skipped.
+    pub const NODAD: AddressFlags = AddressFlags(0x02);
+    /// `IFA_F_OPTIMISTIC`: usable while duplicate address detection is running.
+    pub const OPTIMISTIC: AddressFlags = AddressFlags(0x04);
+    /// `IFA_F_DADFAILED`: duplicate address detection failed.
+    pub const DADFAILED: AddressFlags = AddressFlags(0x08);
+    /// `IFA_F_DEPRECATED`: the preferred lifetime expired.
+    pub const DEPRECATED: AddressFlags = AddressFlags(0x20);
+    /// `IFA_F_TENTATIVE`: duplicate address detection has not completed yet.
+    pub const TENTATIVE: AddressFlags = AddressFlags(0x40);
+    /// `IFA_F_PERMANENT`: configured statically rather than by autoconfiguration.
+    pub const PERMANENT: AddressFlags = AddressFlags(0x80);
+    /// `IFA_F_NOPREFIXROUTE`: no prefix route is added for the address.
+    pub const NOPREFIXROUTE: AddressFlags = AddressFlags(0x200);
+
+    /// Returns true if all flags of `other` are set.
+    pub fn contains(&self, other: AddressFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for AddressFlags {
+    type Output = AddressFlags;
+
+    fn bitor(self, rhs: AddressFlags) -> AddressFlags {
+        AddressFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for AddressFlags {
+    fn bitor_assign(&mut self, rhs: AddressFlags) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// The scope of an interface address (`RT_SCOPE_*`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressScope {
+    /// Globally valid (`RT_SCOPE_UNIVERSE`).
+    Global,
+    /// Valid within the site (`RT_SCOPE_SITE`), e.g. deprecated IPv6 site-local addresses.
+    Site,
+    /// Valid on the link only (`RT_SCOPE_LINK`), e.g. `fe80::/10`.
+    Link,
+    /// Valid on this host only (`RT_SCOPE_HOST`), e.g. loopback addresses.
+    Host,
+    /// Any other scope value.
+    Other(u8),
+}
+
+impl From<u8> for AddressScope {
+    fn from(scope: u8) -> Self {
+        match scope {
+            RT_SCOPE_UNIVERSE => AddressScope::Global,
+            RT_SCOPE_SITE => AddressScope::Site,
+            RT_SCOPE_LINK => AddressScope::Link,
+            RT_SCOPE_HOST => AddressScope::Host,
+            scope => AddressScope::Other(scope),
+        }
+    }
+}
+
+/// Options for adding an address with [`DeviceImpl::add_address_v6_with`]
+/// or [`DeviceBuilder::ipv6_with`](crate::DeviceBuilder::ipv6_with).
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use tun_rs::{AddressOptions, DeviceBuilder};
+/// let dev = DeviceBuilder::new()
+///     .ipv6_with("fd00::1", 64, AddressOptions::new().nodad(true))
+///     .build_sync()
+///     .unwrap();
+/// dev.add_address_v6_with(
+///     "fd00::2",
+///     64,
+///     &AddressOptions::new()
+///         .noprefixroute(true)
+///         .lifetimes(Duration::from_secs(600), Duration::from_secs(3600)),
+/// )
+/// .unwrap();
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AddressOptions {
+    /// Skip duplicate address detection (`IFA_F_NODAD`), so the address is usable at once.
+    pub nodad: bool,
+    /// Do not add a route for the prefix (`IFA_F_NOPREFIXROUTE`).
+    pub noprefixroute: bool,
+    /// How long the address is preferred for new connections. `None` means forever.
+    pub preferred_lifetime: Option<Duration>,
+    /// How long the address stays assigned. `None` means forever.
+    pub valid_lifetime: Option<Duration>,
+}
+
+impl AddressOptions {
+    /// Creates options for a permanent address with duplicate address detection.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets whether duplicate address detection is skipped.
+    pub fn nodad(mut self, nodad: bool) -> Self {
+        self.nodad = nodad;
+        self
+    }
+    /// Sets whether adding the prefix route is skipped.
+    pub fn noprefixroute(mut self, noprefixroute: bool) -> Self {
+        self.noprefixroute = noprefixroute;
+        self
+    }
+    /// Sets the preferred and valid lifetimes; `preferred` must not exceed `valid`.
+    pub fn lifetimes(mut self, preferred: Duration, valid: Duration) -> Self {
+        self.preferred_lifetime = Some(preferred);
+        self.valid_lifetime = Some(valid);
+        self
+    }
+    fn flags(&self) -> AddressFlags {
+        let mut flags = AddressFlags::default();
+        if self.nodad {
+            flags |= AddressFlags::NODAD;
+        }
+        if self.noprefixroute {
+            flags |= AddressFlags::NOPREFIXROUTE;
+        }
+        flags
+    }
+}
+
+/// An address assigned to the device, as listed by [`DeviceImpl::interface_addresses`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InterfaceAddress {
+    /// The address and its prefix length.
+    pub net: IpNet,
+    /// The peer of a point-to-point IPv4 address, like the `destination` of
+    /// [`DeviceImpl::set_network_address`].
+    pub peer: Option<IpAddr>,
+    /// The scope of the address.
+    pub scope: AddressScope,
+    /// The `IFA_F_*` flags, e.g. [`AddressFlags::TENTATIVE`] while DAD is running.
+    pub flags: AddressFlags,
+    /// The remaining preferred lifetime. `None` means forever.
+    pub preferred_lifetime: Option<Duration>,
+    /// The remaining valid lifetime. `None` means forever.
+    pub valid_lifetime: Option<Duration>,
+}
+
+fn lifetime_secs(lifetime: Option<Duration>) -> u32 {
+    lifetime.map_or(INFINITY_LIFE_TIME, |v| {
+        v.as_secs().min(INFINITY_LIFE_TIME as u64 - 1) as u32
+    })
+}
+
+fn lifetime(secs: u32) -> Option<Duration> {
+    (secs != INFINITY_LIFE_TIME).then(|| Duration::from_secs(secs as u64))
+}
+
+impl DeviceImpl {
+    fn address_header(&self, net: IpNet) -> io::Result<ifaddrmsg> {
+        Ok(ifaddrmsg {
+            ifa_family: match net {
+                IpNet::V4(_) => AF_INET,
+                IpNet::V6(_) => AF_INET6,
+            } as u8,
+            ifa_prefixlen: net.prefix_len(),
+            ifa_flags: 0,
+            ifa_scope: RT_SCOPE_UNIVERSE,
+            ifa_index: self.if_index_impl()?,
+        })
+    }
+    pub(crate) fn add_address_impl(
+        &self,
+        net: IpNet,
+        peer: Option<IpAddr>,
+        options: &AddressOptions,
+    ) -> io::Result<()> {
+        let header = self.address_header(net)?;
+        let mut msg = NetlinkMessage::new(RTM_NEWADDR, (NLM_F_CREATE | NLM_F_EXCL) as u16, &header);
+        msg.push_attr_ip(IFA_LOCAL, net.addr())
+            .push_attr_ip(IFA_ADDRESS, peer.unwrap_or(net.addr()))
+            .push_attr_u32(IFA_FLAGS, options.flags().0);
+        // Like SIOCSIFNETMASK, which derives the broadcast address unless the link is point-to-point.
+        if let IpNet::V4(net) = net {
+            if peer.is_none()
+                && net.prefix_len() < 31
+                && self.ifru_flags()? & IFF_POINTOPOINT as i16 == 0
+            {
+                msg.push_attr_ip(IFA_BROADCAST, net.broadcast().into());
+            }
+        }
+        if options.preferred_lifetime.is_some() || options.valid_lifetime.is_some() {
+            let valid = lifetime_secs(options.valid_lifetime);
+            // A missing preferred lifetime follows the valid one.
+            let preferred = lifetime_secs(options.preferred_lifetime.or(options.valid_lifetime));
+            if preferred > valid {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "preferred lifetime exceeds valid lifetime",
+                ));
+            }
+            // struct ifa_cacheinfo: ifa_prefered, ifa_valid, cstamp, tstamp
+            let mut cache_info = Vec::with_capacity(16);
+            for v in [preferred, valid, 0, 0] {
+                cache_info.extend_from_slice(&v.to_ne_bytes());
+            }
+            msg.push_attr(IFA_CACHEINFO, &cache_info);
+        }
+        NetlinkSocket::new()?.request(msg)
+    }
+    pub(crate) fn remove_address_impl(&self, net: IpNet) -> io::Result<()> {
+        let header = self.address_header(net)?;
+        let mut msg = NetlinkMessage::new(RTM_DELADDR, 0, &header);
+        msg.push_attr_ip(IFA_LOCAL, net.addr());
+        NetlinkSocket::new()?.request(msg)
+    }
+    pub(crate) fn interface_addresses_impl(&self) -> io::Result<Vec<InterfaceAddress>> {
+        let if_index = self.if_index_impl()?;
+        let header = ifaddrmsg {
+            ifa_family: AF_UNSPEC as u8,
+            ifa_prefixlen: 0,
+            ifa_flags: 0,
+            ifa_scope: 0,
+            ifa_index: 0,
+        };
+        let replies = NetlinkSocket::new()?.dump(NetlinkMessage::new(RTM_GETADDR, 0, &header))?;
+        let mut addresses = Vec::new();
+        for reply in replies {
+            if reply.ty != RTM_NEWADDR {
+                continue;
+            }
+            let Some(hdr) = reply.header::<ifaddrmsg>() else {
+                continue;
+            };
+            if hdr.ifa_index != if_index {
+                continue;
+            }
+            let mut address = None;
+            let mut local = None;
+            let mut flags = hdr.ifa_flags as u32;
+            let mut preferred_lifetime = None;
+            let mut valid_lifetime = None;
+            for (ty, data) in reply.attrs::<ifaddrmsg>() {
+                match ty {
+                    IFA_ADDRESS => address = attr_ip(data),
+                    IFA_LOCAL => local = attr_ip(data),
+                    IFA_FLAGS => flags = attr_u32(data).unwrap_or(flags),
+                    IFA_CACHEINFO => {
+                        preferred_lifetime = attr_u32(data).and_then(lifetime);
+                        valid_lifetime = data.get(4..).and_then(attr_u32).and_then(lifetime);
+                    }
+                    _ => {}
+                }
+            }
+            // For IPv4, IFA_ADDRESS is the peer address of point-to-point links.
+            let (address, peer) = match local {
+                Some(local) if hdr.ifa_family as i32 == AF_INET => {
+                    (Some(local), address.filter(|v| *v != local))
+                }
+                _ => (address, None),
+            };
+            let Some(net) = address.and_then(|ip| IpNet::new(ip, hdr.ifa_prefixlen).ok()) else {
+                continue;
+            };
+            addresses.push(InterfaceAddress {
+                net,
+                peer,
+                scope: hdr.ifa_scope.into(),
+                flags: AddressFlags(flags),
+                preferred_lifetime,
+                valid_lifetime,
+            });
+        }
+        Ok(addresses)
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Adds an IPv6 address using rtnetlink, with the given flags and lifetimes.
+    ///
+    /// Unlike [`DeviceImpl::add_address_v6`], this can skip duplicate address detection,
+    /// which otherwise keeps the address tentative (unusable) for about a second.
+    pub fn add_address_v6_with<IPv6: ToIpv6Address, Netmask: ToIpv6Netmask>(
+        &self,
+        addr: IPv6,
+        netmask: Netmask,
+        options: &AddressOptions,
+    ) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        let net = IpNet::new(addr.ipv6()?.into(), netmask.prefix()?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.add_address_impl(net, None, options)
+    }
+    /// Lists the addresses of the device with their prefix length, scope, flags
+    /// and remaining lifetimes, in the order the kernel reports them.
+    pub fn interface_addresses(&self) -> io::Result<Vec<InterfaceAddress>> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.interface_addresses_impl()
+    }
+}
@@ -0,0 +1,90 @@
+use crate::builder::DeviceConfig;
+use crate::platform::linux::sys::{tungetfeatures, tunsetfilterebpf};
+use crate::platform::DeviceImpl;
+use libc::{c_int, c_short, EBADF, IFF_MULTI_QUEUE, IFF_NAPI, IFF_NAPI_FRAGS, IFF_VNET_HDR};
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+/// TUN/TAP driver features and offloads.
+///
+/// From [`DeviceBuilder::probe`](crate::DeviceBuilder::probe), the flags tell what the kernel
+/// supports. From [`DeviceImpl::capabilities`], they tell what is enabled on the device.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TunCapabilities {
+    /// The raw `IFF_*` flags reported by `TUNGETFEATURES`.
+    pub features: u32,
+    /// Multiple queues (`IFF_MULTI_QUEUE`).
+    pub multi_queue: bool,
+    /// The virtio-net header (`IFF_VNET_HDR`), required for offloads.
+    pub vnet_hdr: bool,
+    /// NAPI receive processing (`IFF_NAPI`).
+    pub napi: bool,
+    /// NAPI fragment frames (`IFF_NAPI_FRAGS`).
+    pub napi_frags: bool,
+    /// Checksum and TCP segmentation offload (`TUN_F_CSUM | TUN_F_TSO4 | TUN_F_TSO6`).
+    pub tcp_offload: bool,
+    /// UDP segmentation offload (`TUN_F_USO4 | TUN_F_USO6`), Linux 6.2+.
+    pub udp_offload: bool,
+    /// eBPF filter and steering programs (`TUNSETFILTEREBPF`/`TUNSETSTEERINGEBPF`).
+    pub ebpf: bool,
+}
+
+impl TunCapabilities {
+    fn has_feature(&self, flag: c_int) -> bool {
+        self.features & flag as u32 == flag as u32
+    }
+}
+
+impl DeviceImpl {
+    fn features_impl(&self) -> io::Result<u32> {
+        let mut features = 0;
+        unsafe {
+            if let Err(err) = tungetfeatures(self.as_raw_fd(), &mut features) {
+                return Err(io::Error::from(err));
+            }
+        }
+        Ok(features)
+    }
+    /// Probes eBPF program support without touching an attached program:
+    /// an invalid fd yields `EBADF` if the ioctl exists, `EINVAL` otherwise.
+    fn ebpf_supported(&self) -> bool {
+        let mut fd = -2;
+        let rs = unsafe { tunsetfilterebpf(self.as_raw_fd(), &mut fd) };
+        matches!(rs, Err(err) if err as i32 == EBADF)
+    }
+    /// Creates a throw-away device with offloads requested and reports what the kernel accepted.
+    pub(crate) fn probe() -> io::Result<TunCapabilities> {
+        let device = DeviceImpl::new(DeviceConfig {
+            offload: Some(true),
+            ..Default::default()
+        })?;
+        let mut capabilities = device.capabilities()?;
+        capabilities.multi_queue = capabilities.has_feature(IFF_MULTI_QUEUE);
+        capabilities.napi = capabilities.has_feature(IFF_NAPI);
+        capabilities.napi_frags = capabilities.has_feature(IFF_NAPI_FRAGS);
+        capabilities.vnet_hdr = capabilities.has_feature(IFF_VNET_HDR);
+        Ok(capabilities)
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Returns the features and offloads enabled on this device,
+    /// together with the raw feature flags supported by the kernel.
+    ///
+    /// Use this to find out whether a requested offload was actually enabled.
+    pub fn capabilities(&self) -> io::Result<TunCapabilities> {
+        let _guard = self.op_lock.lock().unwrap();
+        let flag = |flag: c_int| self.flags & flag as c_short == flag as c_short;
+        Ok(TunCapabilities {
+            features: self.features_impl()?,
+            multi_queue: flag(IFF_MULTI_QUEUE),
+            vnet_hdr: self.vnet_hdr,
+            napi: flag(IFF_NAPI),
+            napi_frags: flag(IFF_NAPI_FRAGS),
+            tcp_offload: self.vnet_hdr,
+            udp_offload: self.udp_gso,
+            ebpf: self.ebpf_supported(),
+        })
+    }
+}
@@ -1,8 +1,15 @@
+use crate::platform::linux::gso::{split_decoded, GsoInfo};
+use crate::platform::linux::netlink::{
+    attr_u32, Attributes, IfInfoMsg, NetlinkMessage, NetlinkSocket,
+};
 use crate::platform::linux::offload::{
-    gso_none_checksum, gso_split, handle_gro, VirtioNetHdr, VIRTIO_NET_HDR_F_NEEDS_CSUM,
-    VIRTIO_NET_HDR_GSO_NONE, VIRTIO_NET_HDR_GSO_TCPV4, VIRTIO_NET_HDR_GSO_TCPV6,
-    VIRTIO_NET_HDR_GSO_UDP_L4, VIRTIO_NET_HDR_LEN,
+    complete_partial_checksum, handle_gro, VirtioNetHdr, VIRTIO_NET_HDR_LEN,
 };
+use crate::platform::linux::rule::DeviceRules;
+use crate::platform::linux::stats::AtomicIoCounters;
+use crate::platform::linux::DeviceConfigTransaction;
+use crate::platform::linux::FdState;
+use crate::platform::linux::NetNs;
 use crate::platform::unix::device::{ctl, ctl_v6};
 use crate::platform::{ExpandBuffer, GROTable};
 use crate::{
@@ -16,8 +23,9 @@ use crate::{
 };
 use ipnet::IpNet;
 use libc::{
-    self, c_char, c_short, ifreq, in6_ifreq, ARPHRD_ETHER, IFF_MULTI_QUEUE, IFF_NO_PI, IFF_RUNNING,
-    IFF_TAP, IFF_TUN, IFF_UP, IFNAMSIZ, O_RDWR,
+    self, c_char, c_int, c_short, ifreq, in6_ifreq, sock_filter, ARPHRD_ETHER, IFF_ATTACH_QUEUE,
+    IFF_DETACH_QUEUE, IFF_MULTI_QUEUE, IFF_NO_PI, IFF_RUNNING, IFF_TAP, IFF_TUN, IFF_UP,
+    IFLA_IFNAME, IFLA_INFO_DATA, IFLA_INFO_KIND, IFLA_LINKINFO, IFNAMSIZ, O_RDWR, RTM_GETLINK,
 };
 use mac_address::mac_address_by_name;
 use std::net::Ipv6Addr;
@@ -25,8 +33,8 @@ use std::sync::{Arc, Mutex};
 use std::{
     ffi::CString,
     io, mem,
-    net::{IpAddr, Ipv4Addr},
-    os::unix::io::{AsRawFd, RawFd},
+    net::IpAddr,
+    os::unix::io::{AsFd, AsRawFd, RawFd},
     ptr,
 };
 
@@ -36,9 +44,19 @@ const OVERWRITE_SIZE: usize = mem::size_of::<libc::__c_anonymous_ifr_ifru>();
 pub struct DeviceImpl {
     pub(crate) tun: Tun,
     pub(crate) vnet_hdr: bool,
+    /// Length of the virtio-net header preceding each packet, 0 without `vnet_hdr`.
+    pub(crate) vnet_hdr_len: usize,
     pub(crate) udp_gso: bool,
-    flags: c_short,
+    pub(crate) flags: c_short,
     pub(crate) op_lock: Arc<Mutex<()>>,
+    /// The classic BPF filter shared by all queues, re-attached to new queues.
+    pub(crate) filter: Arc<Mutex<Option<Vec<sock_filter>>>>,
+    /// Userspace packet counters shared by all queues, if enabled.
+    pub(crate) io_counters: Option<Arc<AtomicIoCounters>>,
+    /// The network namespace the device lives in, if not the caller's; new queues are opened there.
+    pub(crate) netns: Arc<Mutex<Option<NetNs>>>,
+    /// Policy routing rules shared by all queues, removed when the last queue is dropped.
+    pub(crate) rules: Arc<Mutex<DeviceRules>>,
 }
 
 impl DeviceImpl {
@@ -77,10 +95,24 @@ impl DeviceImpl {
             let iff_multi_queue = IFF_MULTI_QUEUE as c_short;
             let packet_information = config.packet_information.unwrap_or(false);
             let offload = config.offload.unwrap_or(false);
+            let strict = config.strict.unwrap_or(false);
+            let vnet_hdr_len = config.vnet_hdr_len.unwrap_or(VIRTIO_NET_HDR_LEN);
+            if vnet_hdr_len < VIRTIO_NET_HDR_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("virtio-net header length {vnet_hdr_len} < {VIRTIO_NET_HDR_LEN}"),
+                ));
+            }
             req.ifr_ifru.ifru_flags = device_type
                 | if packet_information { 0 } else { iff_no_pi }
                 | if multi_queue { iff_multi_queue } else { 0 }
                 | if offload { iff_vnet_hdr } else { 0 };
+            if config.attach_existing.unwrap_or(false) {
+                let name = config.dev_name.as_deref().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "device name is required")
+                })?;
+                check_existing(name, req.ifr_ifru.ifru_flags)?;
+            }
 
             let fd = libc::open(
                 c"/dev/net/tun".as_ptr() as *const _,
@@ -96,25 +128,53 @@ impl DeviceImpl {
                 let tun_tcp_offloads = libc::TUN_F_CSUM | libc::TUN_F_TSO4 | libc::TUN_F_TSO6;
                 let tun_udp_offloads = libc::TUN_F_USO4 | libc::TUN_F_USO6;
                 if let Err(err) = tunsetoffload(tun_fd.inner, tun_tcp_offloads as _) {
+                    if strict {
+                        return Err(io::Error::new(
+                            io::ErrorKind::Unsupported,
+                            format!("unsupported offload: {err:?}"),
+                        ));
+                    }
                     log::warn!("unsupported offload: {err:?}");
                     (false, false)
                 } else {
                     // tunUDPOffloads were added in Linux v6.2. We do not return an
-                    // error if they are unsupported at runtime.
+                    // error if they are unsupported at runtime, unless in strict mode.
                     let rs =
                         tunsetoffload(tun_fd.inner, (tun_tcp_offloads | tun_udp_offloads) as _);
+                    if let Err(err) = rs {
+                        if strict {
+                            return Err(io::Error::new(
+                                io::ErrorKind::Unsupported,
+                                format!("unsupported udp offload: {err:?}"),
+                            ));
+                        }
+                    }
                     (true, rs.is_ok())
                 }
             } else {
                 (false, false)
             };
+            // Always set, a persistent device keeps the size of its previous owner.
+            if vnet_hdr {
+                if let Err(err) = tunsetvnethdrsz(tun_fd.inner, &(vnet_hdr_len as c_int)) {
+                    return Err(io::Error::from(err));
+                }
+            }
 
             let device = DeviceImpl {
                 tun: Tun::new(tun_fd),
                 vnet_hdr,
+                vnet_hdr_len: if vnet_hdr { vnet_hdr_len } else { 0 },
                 udp_gso,
                 flags: req.ifr_ifru.ifru_flags,
                 op_lock: Arc::new(Mutex::new(())),
+                filter: Arc::new(Mutex::new(None)),
+                io_counters: config
+                    .io_counters
+                    .unwrap_or(false)
+                    .then(|| Arc::new(AtomicIoCounters::default())),
+                netns: Arc::new(Mutex::new(None)),
+                rules: Arc::default(),
             };
             Ok(device)
         }
@@ -132,13 +192,23 @@ impl DeviceImpl {
             .map(|_| ())
             .map_err(|e| e.into())
     }
+    /// Wraps an fd opened elsewhere, detecting its state with [`FdState::detect`].
     pub(crate) fn from_tun(tun: Tun) -> io::Result<Self> {
+        match FdState::detect(tun.fd.as_fd()) {
+            Ok(state) => return Ok(DeviceImpl::from_state(tun, state)),
+            Err(e) => log::warn!("failed to detect the device state: {e:?}"),
+        }
         Ok(Self {
             tun,
             vnet_hdr: false,
+            vnet_hdr_len: 0,
             udp_gso: false,
             flags: 0,
             op_lock: Arc::new(Mutex::new(())),
+            filter: Arc::new(Mutex::new(None)),
+            io_counters: None,
+            netns: Arc::new(Mutex::new(None)),
+            rules: Arc::default(),
         })
     }
 
@@ -156,7 +226,8 @@ impl DeviceImpl {
                 "iff_multi_queue not enabled",
             ));
         }
-        unsafe {
+        // TUNSETIFF looks the device up by name in the namespace of the calling thread.
+        let open = || unsafe {
             let mut req = self.request()?;
             req.ifr_ifru.ifru_flags = flags;
             let fd = libc::open(
@@ -167,12 +238,25 @@ impl DeviceImpl {
             if let Err(err) = tunsetiff(tun_fd.inner, &mut req as *mut _ as *mut _) {
                 return Err(io::Error::from(err));
             }
+            Ok(tun_fd)
+        };
+        let netns = self.netns.lock().unwrap().clone();
+        let tun_fd = match netns {
+            Some(netns) => netns.run(open)?,
+            None => open()?,
+        };
+        unsafe {
             let dev = DeviceImpl {
                 tun: Tun::new(tun_fd),
                 vnet_hdr: self.vnet_hdr,
+                vnet_hdr_len: self.vnet_hdr_len,
                 udp_gso: self.udp_gso,
                 flags,
                 op_lock: self.op_lock.clone(),
+                filter: self.filter.clone(),
+                io_counters: self.io_counters.clone(),
+                netns: self.netns.clone(),
+                rules: self.rules.clone(),
             };
             if dev.vnet_hdr {
                 if dev.udp_gso {
@@ -181,10 +265,71 @@ impl DeviceImpl {
                     dev.set_tcp_offloads()?;
                 }
             }
+            // Queues attached with IFF_NO_PI (aka IFF_NOFILTER) skip the device filter.
+            if let Some(filter) = self.filter.lock().unwrap().as_ref() {
+                dev.attach_filter_impl(filter)?;
+            }
 
             Ok(dev)
         }
     }
+    fn set_queue(&self, flags: c_int) -> io::Result<()> {
+        if self.flags & (IFF_MULTI_QUEUE as c_short) != IFF_MULTI_QUEUE as c_short {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "iff_multi_queue not enabled",
+            ));
+        }
+        unsafe {
+            let mut req: ifreq = mem::zeroed();
+            req.ifr_ifru.ifru_flags = flags as c_short;
+            if let Err(err) = tunsetqueue(self.as_raw_fd(), &mut req as *mut _ as *mut _) {
+                return Err(io::Error::from(err));
+            }
+        }
+        Ok(())
+    }
+    /// Detaches this queue from the device (`TUNSETQUEUE` with `IFF_DETACH_QUEUE`).
+    ///
+    /// A detached queue stays open but no longer receives packets until it is
+    /// re-attached with [`DeviceImpl::attach_queue`].
+    /// The kernel spreads traffic over the remaining queues.
+    ///
+    /// # Prerequisites
+    /// - The `IFF_MULTI_QUEUE` flag must be enabled.
+    pub fn detach_queue(&self) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.set_queue(IFF_DETACH_QUEUE)
+    }
+    /// Re-attaches a queue detached with [`DeviceImpl::detach_queue`]
+    /// (`TUNSETQUEUE` with `IFF_ATTACH_QUEUE`).
+    ///
+    /// # Prerequisites
+    /// - The `IFF_MULTI_QUEUE` flag must be enabled.
+    pub fn attach_queue(&self) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.set_queue(IFF_ATTACH_QUEUE)
+    }
+    /// Returns the number of queues currently attached to the device.
+    ///
+    /// Detached queues are not counted. The value is the driver's `IFLA_TUN_NUM_QUEUES`,
+    /// read over rtnetlink, and needs Linux 4.15 or later.
+    pub fn queue_count(&self) -> io::Result<usize> {
+        let _guard = self.op_lock.lock().unwrap();
+        let info = tun_link_info(&self.name_impl()?)?.unwrap_or_default();
+        // The number of queues is only reported for multi-queue devices,
+        // a single-queue device has just this one.
+        match info.multi_queue {
+            Some(false) => Some(1),
+            _ => info.num_queues.map(|num| num as usize),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the kernel does not report the number of queues",
+            )
+        })
+    }
     /// Returns whether UDP Generic Segmentation Offload (GSO) is enabled.
     ///
     /// This is determined by the `udp_gso` flag in the device.
@@ -192,6 +337,19 @@ impl DeviceImpl {
         let _guard = self.op_lock.lock().unwrap();
         self.udp_gso
     }
+    /// Returns the length of the virtio-net header that precedes each packet
+    /// read or written, or 0 if offload is disabled.
+    ///
+    /// It is [`VIRTIO_NET_HDR_LEN`](crate::VIRTIO_NET_HDR_LEN) unless set with
+    /// [`DeviceBuilder::vnet_hdr_len`](crate::DeviceBuilder::vnet_hdr_len)
+    /// or detected on an fd opened elsewhere.
+    pub fn vnet_hdr_len(&self) -> usize {
+        self.vnet_hdr_len
+    }
+    /// Whether packets are Ethernet frames of a TAP device.
+    pub(crate) fn is_tap(&self) -> bool {
+        self.flags & IFF_TAP as c_short != 0
+    }
     /// Returns whether TCP Generic Segmentation Offload (GSO) is enabled.
     ///
     /// In this implementation, this is represented by the `vnet_hdr` flag.
@@ -230,23 +388,71 @@ impl DeviceImpl {
             Ok(ifreq.ifr_ifru.ifru_metric as _)
         }
     }
+    /// Sets the send buffer limit of the device in bytes (`TUNSETSNDBUF`).
+    ///
+    /// It bounds how much data written to the device may still be queued in the kernel.
+    /// Once exceeded, writes block, or fail with `WouldBlock` in non-blocking mode.
+    /// The default is `i32::MAX`, i.e. unlimited. The limit applies to all queues.
+    pub fn set_sndbuf(&self, sndbuf: i32) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        unsafe {
+            if let Err(err) = tunsetsndbuf(self.as_raw_fd(), &sndbuf) {
+                return Err(io::Error::from(err));
+            }
+        }
+        Ok(())
+    }
+    /// Retrieves the send buffer limit of the device in bytes (`TUNGETSNDBUF`).
+    pub fn sndbuf(&self) -> io::Result<i32> {
+        let _guard = self.op_lock.lock().unwrap();
+        let mut sndbuf = 0;
+        unsafe {
+            if let Err(err) = tungetsndbuf(self.as_raw_fd(), &mut sndbuf) {
+                return Err(io::Error::from(err));
+            }
+        }
+        Ok(sndbuf)
+    }
     /// Make the device persistent.
     pub fn persist(&self) -> io::Result<()> {
         let _guard = self.op_lock.lock().unwrap();
+        self.set_persist_impl(true)
+    }
+    pub(crate) fn set_persist_impl(&self, persist: bool) -> io::Result<()> {
         unsafe {
-            if let Err(err) = tunsetpersist(self.as_raw_fd(), &1) {
+            if let Err(err) = tunsetpersist(self.as_raw_fd(), persist as _) {
                 Err(io::Error::from(err))
             } else {
                 Ok(())
             }
         }
     }
+    /// Deletes the device, which is typically persistent.
+    ///
+    /// This clears `TUNSETPERSIST` and removes the interface via rtnetlink
+    /// without waiting for every open queue to be closed.
+    /// Reads and writes on remaining handles fail afterwards.
+    pub fn destroy(&self) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        let index = self.if_index_impl()?;
+        self.set_persist_impl(false)?;
+        let header = IfInfoMsg {
+            ifi_family: libc::AF_UNSPEC as u8,
+            ifi_index: index as i32,
+            ..Default::default()
+        };
+        match NetlinkSocket::new()?.request(NetlinkMessage::new(libc::RTM_DELLINK, 0, &header)) {
+            // Already gone, e.g. this was the last fd of a non-persistent device.
+            Err(e) if e.raw_os_error() == Some(libc::ENODEV) => Ok(()),
+            rs => rs,
+        }
+    }
 
     /// Set the owner of the device.
     pub fn user(&self, value: i32) -> io::Result<()> {
         let _guard = self.op_lock.lock().unwrap();
         unsafe {
-            if let Err(err) = tunsetowner(self.as_raw_fd(), &value) {
+            if let Err(err) = tunsetowner(self.as_raw_fd(), value as _) {
                 Err(io::Error::from(err))
             } else {
                 Ok(())
@@ -258,7 +464,7 @@ impl DeviceImpl {
     pub fn group(&self, value: i32) -> io::Result<()> {
         let _guard = self.op_lock.lock().unwrap();
         unsafe {
-            if let Err(err) = tunsetgroup(self.as_raw_fd(), &value) {
+            if let Err(err) = tunsetgroup(self.as_raw_fd(), value as _) {
                 Err(io::Error::from(err))
             } else {
                 Ok(())
@@ -267,7 +473,7 @@ impl DeviceImpl {
     }
     /// send multiple fragmented data packets.
     /// GROTable can be reused, as it is used to assist in data merging.
-    /// Offset is the starting position of the data. Need to meet offset>=10.
+    /// Offset is the starting position of the data. Need to meet offset>=[`DeviceImpl::vnet_hdr_len`].
     pub fn send_multiple<B: ExpandBuffer>(
         &self,
         gro_table: &mut GROTable,
@@ -288,14 +494,20 @@ impl DeviceImpl {
             handle_gro(
                 bufs,
                 offset,
+                self.vnet_hdr_len,
                 &mut gro_table.tcp_gro_table,
                 &mut gro_table.udp_gro_table,
                 self.udp_gso,
+                gro_table.checksum_partial,
+                self.is_tap(),
                 &mut gro_table.to_write,
             )?;
-            offset -= VIRTIO_NET_HDR_LEN;
+            offset -= self.vnet_hdr_len;
         } else {
-            for i in 0..bufs.len() {
+            for (i, buf) in bufs.iter_mut().enumerate() {
+                if gro_table.checksum_partial {
+                    complete_partial_checksum(&mut buf.as_mut()[offset..], self.is_tap());
+                }
                 gro_table.to_write.push(i);
             }
         }
@@ -305,6 +517,7 @@ impl DeviceImpl {
         for buf_idx in &gro_table.to_write {
             match write_f(&self.tun, &bufs[*buf_idx].as_ref()[offset..]) {
                 Ok(n) => {
+                    self.count_tx(n);
                     total += n;
                 }
                 Err(e) => {
@@ -323,7 +536,7 @@ impl DeviceImpl {
     /// Recv a packet from tun device.
     /// If offload is enabled. This method can be used to obtain processed data.
     ///
-    /// original_buffer is used to store raw data, including the VirtioNetHdr and the unsplit IP packet. The recommended size is 10 + 65535.
+    /// original_buffer is used to store raw data, including the VirtioNetHdr and the unsplit IP packet. The recommended size is [`DeviceImpl::vnet_hdr_len`] + 65535.
     /// bufs and sizes are used to store the segmented IP packets. bufs.len == sizes.len > 65535/MTU
     /// offset: Starting position
     pub fn recv_multiple<B: AsRef<[u8]> + AsMut<[u8]>>(
@@ -353,21 +566,24 @@ impl DeviceImpl {
         }
         if self.vnet_hdr {
             let len = read_f(&self.tun, original_buffer)?;
-            if len <= VIRTIO_NET_HDR_LEN {
+            self.count_rx(len);
+            let vnet_hdr_len = self.vnet_hdr_len;
+            if len <= vnet_hdr_len {
                 Err(io::Error::other(format!(
-                    "length of packet ({len}) <= VIRTIO_NET_HDR_LEN ({VIRTIO_NET_HDR_LEN})",
+                    "length of packet ({len}) <= vnet_hdr_len ({vnet_hdr_len})",
                 )))?
             }
-            let hdr = VirtioNetHdr::decode(&original_buffer[..VIRTIO_NET_HDR_LEN])?;
+            let hdr = VirtioNetHdr::decode(&original_buffer[..vnet_hdr_len])?;
             self.handle_virtio_read(
                 hdr,
-                &mut original_buffer[VIRTIO_NET_HDR_LEN..len],
+                &mut original_buffer[vnet_hdr_len..len],
                 bufs,
                 sizes,
                 offset,
             )
         } else {
             let len = read_f(&self.tun, &mut bufs[0].as_mut()[offset..])?;
+            self.count_rx(len);
             sizes[0] = len;
             Ok(1)
         }
@@ -378,105 +594,14 @@ impl DeviceImpl {
     /// and returns the number of packets read.
     pub(crate) fn handle_virtio_read<B: AsRef<[u8]> + AsMut<[u8]>>(
         &self,
-        mut hdr: VirtioNetHdr,
+        hdr: VirtioNetHdr,
         input: &mut [u8],
         bufs: &mut [B],
         sizes: &mut [usize],
         offset: usize,
     ) -> io::Result<usize> {
-        let len = input.len();
-        if hdr.gso_type == VIRTIO_NET_HDR_GSO_NONE {
-            if hdr.flags & VIRTIO_NET_HDR_F_NEEDS_CSUM != 0 {
-                // This means CHECKSUM_PARTIAL in skb context. We are responsible
-                // for computing the checksum starting at hdr.csumStart and placing
-                // at hdr.csumOffset.
-                gso_none_checksum(input, hdr.csum_start, hdr.csum_offset);
-            }
-            if bufs[0].as_ref()[offset..].len() < len {
-                Err(io::Error::other(format!(
-                    "read len {len} overflows bufs element len {}",
-                    bufs[0].as_ref().len()
-                )))?
-            }
-            sizes[0] = len;
-            bufs[0].as_mut()[offset..offset + len].copy_from_slice(input);
-            return Ok(1);
-        }
-        if hdr.gso_type != VIRTIO_NET_HDR_GSO_TCPV4
-            && hdr.gso_type != VIRTIO_NET_HDR_GSO_TCPV6
-            && hdr.gso_type != VIRTIO_NET_HDR_GSO_UDP_L4
-        {
-            Err(io::Error::other(format!(
-                "unsupported virtio GSO type: {}",
-                hdr.gso_type
-            )))?
-        }
-        let ip_version = input[0] >> 4;
-        match ip_version {
-            4 => {
-                if hdr.gso_type != VIRTIO_NET_HDR_GSO_TCPV4
-                    && hdr.gso_type != VIRTIO_NET_HDR_GSO_UDP_L4
-                {
-                    Err(io::Error::other(format!(
-                        "ip header version: 4, GSO type: {}",
-                        hdr.gso_type
-                    )))?
-                }
-            }
-            6 => {
-                if hdr.gso_type != VIRTIO_NET_HDR_GSO_TCPV6
-                    && hdr.gso_type != VIRTIO_NET_HDR_GSO_UDP_L4
-                {
-                    Err(io::Error::other(format!(
-                        "ip header version: 6, GSO type: {}",
-                        hdr.gso_type
-                    )))?
-                }
-            }
-            ip_version => Err(io::Error::other(format!(
-                "invalid ip header version: {ip_version}"
-            )))?,
-        }
-        // Don't trust hdr.hdrLen from the kernel as it can be equal to the length
-        // of the entire first packet when the kernel is handling it as part of a
-        // FORWARD path. Instead, parse the transport header length and add it onto
-        // csumStart, which is synonymous for IP header length.
-        if hdr.gso_type == VIRTIO_NET_HDR_GSO_UDP_L4 {
-            hdr.hdr_len = hdr.csum_start + 8
-        } else {
-            if len <= hdr.csum_start as usize + 12 {
-                Err(io::Error::other("packet is too short"))?
-            }
-
-            let tcp_h_len = ((input[hdr.csum_start as usize + 12] as u16) >> 4) * 4;
-            if !(20..=60).contains(&tcp_h_len) {
-                // A TCP header must be between 20 and 60 bytes in length.
-                Err(io::Error::other(format!(
-                    "tcp header len is invalid: {tcp_h_len}"
-                )))?
-            }
-            hdr.hdr_len = hdr.csum_start + tcp_h_len
-        }
-        if len < hdr.hdr_len as usize {
-            Err(io::Error::other(format!(
-                "length of packet ({len}) < virtioNetHdr.hdr_len ({})",
-                hdr.hdr_len
-            )))?
-        }
-        if hdr.hdr_len < hdr.csum_start {
-            Err(io::Error::other(format!(
-                "virtioNetHdr.hdrLen ({}) < virtioNetHdr.csumStart ({})",
-                hdr.hdr_len, hdr.csum_start
-            )))?
-        }
-        let c_sum_at = (hdr.csum_start + hdr.csum_offset) as usize;
-        if c_sum_at + 1 >= len {
-            Err(io::Error::other(format!(
-                "end of checksum offset ({}) exceeds packet length ({len})",
-                c_sum_at + 1,
-            )))?
-        }
-        gso_split(input, hdr, bufs, sizes, offset, ip_version == 6)
+        let (info, hdr) = GsoInfo::decode(hdr, input, self.is_tap())?;
+        split_decoded(info, hdr, input, bufs, sizes, offset)
     }
     pub fn remove_address_v6_impl(&self, addr: Ipv6Addr, prefix: u8) -> io::Result<()> {
         unsafe {
@@ -501,65 +626,90 @@ impl DeviceImpl {
     unsafe fn request(&self) -> io::Result<ifreq> {
         request(&self.name_impl()?)
     }
-    fn set_address_v4(&self, addr: Ipv4Addr) -> io::Result<()> {
+    /// Retrieves the name of the network interface.
+    pub(crate) fn name_impl(&self) -> io::Result<String> {
+        unsafe { name(self.as_raw_fd()) }
+    }
+
+    pub(crate) fn ifru_flags(&self) -> io::Result<i16> {
         unsafe {
+            let ctl = ctl()?;
             let mut req = self.request()?;
-            ipaddr_to_sockaddr(addr, 0, &mut req.ifr_ifru.ifru_addr, OVERWRITE_SIZE);
-            if let Err(err) = siocsifaddr(ctl()?.as_raw_fd(), &req) {
+
+            if let Err(err) = siocgifflags(ctl.as_raw_fd(), &mut req) {
                 return Err(io::Error::from(err));
             }
+            Ok(req.ifr_ifru.ifru_flags)
         }
-        Ok(())
     }
-    fn set_netmask(&self, value: Ipv4Addr) -> io::Result<()> {
+
+    pub(crate) fn enabled_impl(&self, value: bool) -> io::Result<()> {
         unsafe {
+            let ctl = ctl()?;
             let mut req = self.request()?;
-            ipaddr_to_sockaddr(value, 0, &mut req.ifr_ifru.ifru_netmask, OVERWRITE_SIZE);
-            if let Err(err) = siocsifnetmask(ctl()?.as_raw_fd(), &req) {
+
+            if let Err(err) = siocgifflags(ctl.as_raw_fd(), &mut req) {
                 return Err(io::Error::from(err));
             }
+
+            if value {
+                req.ifr_ifru.ifru_flags |= (IFF_UP | IFF_RUNNING) as c_short;
+            } else {
+                req.ifr_ifru.ifru_flags &= !(IFF_UP as c_short);
+            }
+
+            if let Err(err) = siocsifflags(ctl.as_raw_fd(), &req) {
+                return Err(io::Error::from(err));
+            }
+
             Ok(())
         }
     }
-
-    fn set_destination(&self, value: Ipv4Addr) -> io::Result<()> {
+    pub(crate) fn mtu_impl(&self) -> io::Result<u16> {
         unsafe {
             let mut req = self.request()?;
-            ipaddr_to_sockaddr(value, 0, &mut req.ifr_ifru.ifru_dstaddr, OVERWRITE_SIZE);
-            if let Err(err) = siocsifdstaddr(ctl()?.as_raw_fd(), &req) {
+
+            if let Err(err) = siocgifmtu(ctl()?.as_raw_fd(), &mut req) {
                 return Err(io::Error::from(err));
             }
-            Ok(())
-        }
-    }
 
-    /// Retrieves the name of the network interface.
-    pub(crate) fn name_impl(&self) -> io::Result<String> {
-        unsafe { name(self.as_raw_fd()) }
+            req.ifr_ifru
+                .ifru_mtu
+                .try_into()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{e:?}")))
+        }
     }
-
-    fn ifru_flags(&self) -> io::Result<i16> {
+    pub(crate) fn set_mtu_impl(&self, value: u16) -> io::Result<()> {
         unsafe {
-            let ctl = ctl()?;
             let mut req = self.request()?;
+            req.ifr_ifru.ifru_mtu = value as i32;
 
-            if let Err(err) = siocgifflags(ctl.as_raw_fd(), &mut req) {
+            if let Err(err) = siocsifmtu(ctl()?.as_raw_fd(), &req) {
                 return Err(io::Error::from(err));
             }
-            Ok(req.ifr_ifru.ifru_flags)
+            Ok(())
         }
     }
-
-    fn remove_all_address_v4(&self) -> io::Result<()> {
-        let interface = netconfig_rs::Interface::try_from_index(self.if_index_impl()?)
-            .map_err(io::Error::from)?;
-        let list = interface.addresses().map_err(io::Error::from)?;
-        for x in list {
-            if x.addr().is_ipv4() {
-                interface.remove_address(x).map_err(io::Error::from)?;
+    pub(crate) fn set_mac_address_impl(
+        &self,
+        eth_addr: [u8; ETHER_ADDR_LEN as usize],
+    ) -> io::Result<()> {
+        unsafe {
+            let mut req = self.request()?;
+            req.ifr_ifru.ifru_hwaddr.sa_family = ARPHRD_ETHER;
+            req.ifr_ifru.ifru_hwaddr.sa_data[0..ETHER_ADDR_LEN as usize]
+                .copy_from_slice(eth_addr.map(|c| c as _).as_slice());
+            if let Err(err) = siocsifhwaddr(ctl()?.as_raw_fd(), &req) {
+                return Err(io::Error::from(err));
             }
+            Ok(())
         }
-        Ok(())
+    }
+    pub(crate) fn mac_address_impl(&self) -> io::Result<[u8; ETHER_ADDR_LEN as usize]> {
+        let mac = mac_address_by_name(&self.name_impl()?)
+            .map_err(|e| io::Error::other(e.to_string()))?
+            .ok_or(io::Error::from(io::ErrorKind::NotFound))?;
+        Ok(mac.bytes())
     }
 }
 
@@ -617,26 +767,7 @@ impl DeviceImpl {
     /// If false, the IFF_UP flag is cleared. The change is applied using a system call.
     pub fn enabled(&self, value: bool) -> io::Result<()> {
         let _guard = self.op_lock.lock().unwrap();
-        unsafe {
-            let ctl = ctl()?;
-            let mut req = self.request()?;
-
-            if let Err(err) = siocgifflags(ctl.as_raw_fd(), &mut req) {
-                return Err(io::Error::from(err));
-            }
-
-            if value {
-                req.ifr_ifru.ifru_flags |= (IFF_UP | IFF_RUNNING) as c_short;
-            } else {
-                req.ifr_ifru.ifru_flags &= !(IFF_UP as c_short);
-            }
-
-            if let Err(err) = siocsifflags(ctl.as_raw_fd(), &req) {
-                return Err(io::Error::from(err));
-            }
-
-            Ok(())
-        }
+        self.enabled_impl(value)
     }
     /// Retrieves the broadcast address of the network interface.
     ///
@@ -670,6 +801,9 @@ impl DeviceImpl {
     }
     /// Sets the IPv4 network address, netmask, and an optional destination address.
     /// Remove all previous set IPv4 addresses and set the specified address.
+    ///
+    /// The new address is added before the previous ones are removed, and they are
+    /// restored if that fails, see [`DeviceConfigTransaction`].
     pub fn set_network_address<IPv4: ToIpv4Address, Netmask: ToIpv4Netmask>(
         &self,
         address: IPv4,
@@ -677,13 +811,9 @@ impl DeviceImpl {
         destination: Option<IPv4>,
     ) -> io::Result<()> {
         let _guard = self.op_lock.lock().unwrap();
-        self.remove_all_address_v4()?;
-        self.set_address_v4(address.ipv4()?)?;
-        self.set_netmask(netmask.netmask()?)?;
-        if let Some(destination) = destination {
-            self.set_destination(destination.ipv4()?)?;
-        }
-        Ok(())
+        DeviceConfigTransaction::new()
+            .ipv4(address, netmask, destination)
+            .commit_impl(self)
     }
     /// Add IPv4 network address, netmask
     pub fn add_address_v4<IPv4: ToIpv4Address, Netmask: ToIpv4Netmask>(
@@ -764,18 +894,7 @@ impl DeviceImpl {
     /// to obtain the MTU. The result is then converted to a u16.
     pub fn mtu(&self) -> io::Result<u16> {
         let _guard = self.op_lock.lock().unwrap();
-        unsafe {
-            let mut req = self.request()?;
-
-            if let Err(err) = siocgifmtu(ctl()?.as_raw_fd(), &mut req) {
-                return Err(io::Error::from(err));
-            }
-
-            req.ifr_ifru
-                .ifru_mtu
-                .try_into()
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{e:?}")))
-        }
+        self.mtu_impl()
     }
     /// Sets the MTU (Maximum Transmission Unit) for the interface.
     ///
@@ -783,15 +902,7 @@ impl DeviceImpl {
     /// and then applies it via a system call.
     pub fn set_mtu(&self, value: u16) -> io::Result<()> {
         let _guard = self.op_lock.lock().unwrap();
-        unsafe {
-            let mut req = self.request()?;
-            req.ifr_ifru.ifru_mtu = value as i32;
-
-            if let Err(err) = siocsifmtu(ctl()?.as_raw_fd(), &req) {
-                return Err(io::Error::from(err));
-            }
-            Ok(())
-        }
+        self.set_mtu_impl(value)
     }
     /// Sets the MAC (hardware) address for the interface.
     ///
@@ -800,16 +911,7 @@ impl DeviceImpl {
     /// This operation is typically supported only for TAP devices.
     pub fn set_mac_address(&self, eth_addr: [u8; ETHER_ADDR_LEN as usize]) -> io::Result<()> {
         let _guard = self.op_lock.lock().unwrap();
-        unsafe {
-            let mut req = self.request()?;
-            req.ifr_ifru.ifru_hwaddr.sa_family = ARPHRD_ETHER;
-            req.ifr_ifru.ifru_hwaddr.sa_data[0..ETHER_ADDR_LEN as usize]
-                .copy_from_slice(eth_addr.map(|c| c as _).as_slice());
-            if let Err(err) = siocsifhwaddr(ctl()?.as_raw_fd(), &req) {
-                return Err(io::Error::from(err));
-            }
-            Ok(())
-        }
+        self.set_mac_address_impl(eth_addr)
     }
     /// Retrieves the MAC (hardware) address of the interface.
     ///
@@ -817,11 +919,104 @@ impl DeviceImpl {
     /// An error is returned if the MAC address cannot be found.
     pub fn mac_address(&self) -> io::Result<[u8; ETHER_ADDR_LEN as usize]> {
         let _guard = self.op_lock.lock().unwrap();
-        let mac = mac_address_by_name(&self.name_impl()?)
-            .map_err(|e| io::Error::other(e.to_string()))?
-            .ok_or(io::Error::from(io::ErrorKind::NotFound))?;
-        Ok(mac.bytes())
+        self.mac_address_impl()
+    }
+}
+
+/// `IFLA_TUN_*` attributes of `IFLA_INFO_DATA`, see `include/uapi/linux/if_link.h`.
+const IFLA_TUN_TYPE: u16 = 3;
+const IFLA_TUN_MULTI_QUEUE: u16 = 7;
+const IFLA_TUN_NUM_QUEUES: u16 = 8;
+
+/// The TUN/TAP driver attributes of a link.
+#[derive(Default)]
+struct TunLinkInfo {
+    /// `IFF_TUN` or `IFF_TAP`.
+    tun_type: Option<c_short>,
+    multi_queue: Option<bool>,
+    /// The number of attached queues.
+    num_queues: Option<u32>,
+}
+
+/// Reads the TUN/TAP attributes of the link `name` over rtnetlink, which, unlike sysfs,
+/// follows the network namespace of the calling thread.
+///
+/// Returns `None` if the link is not a TUN/TAP device.
+fn tun_link_info(name: &str) -> io::Result<Option<TunLinkInfo>> {
+    let mut msg = NetlinkMessage::new(RTM_GETLINK, 0, &IfInfoMsg::default());
+    let mut ifname = name.as_bytes().to_vec();
+    ifname.push(0);
+    msg.push_attr(IFLA_IFNAME, &ifname);
+    let reply = match NetlinkSocket::new()?.get(msg) {
+        Ok(reply) => reply,
+        Err(e) if e.raw_os_error() == Some(libc::ENODEV) => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("device {name} does not exist"),
+            ));
+        }
+        Err(e) => return Err(e),
+    };
+    let Some((_, link_info)) = reply
+        .attrs::<IfInfoMsg>()
+        .find(|(ty, _)| *ty == IFLA_LINKINFO)
+    else {
+        return Ok(None);
+    };
+    let mut is_tun = false;
+    let mut info = TunLinkInfo::default();
+    for (ty, data) in Attributes::new(link_info) {
+        match ty {
+            IFLA_INFO_KIND => is_tun = data.split(|v| *v == 0).next() == Some(b"tun"),
+            IFLA_INFO_DATA => {
+                for (ty, data) in Attributes::new(data) {
+                    match (ty, data.first()) {
+                        (IFLA_TUN_TYPE, Some(v)) => info.tun_type = Some(*v as c_short),
+                        (IFLA_TUN_MULTI_QUEUE, Some(v)) => info.multi_queue = Some(*v != 0),
+                        (IFLA_TUN_NUM_QUEUES, _) => info.num_queues = attr_u32(data),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(is_tun.then_some(info))
+}
+
+/// Checks that `name` is an existing TUN/TAP device that can be attached with `flags`.
+///
+/// Kernels before 4.15 do not report the TUN/TAP flags, which are then left to `TUNSETIFF`.
+fn check_existing(name: &str, flags: c_short) -> io::Result<()> {
+    let Some(info) = tun_link_info(name)? else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{name} is not a TUN/TAP device"),
+        ));
+    };
+    let type_mask = (IFF_TUN | IFF_TAP) as c_short;
+    if let Some(tun_type) = info.tun_type {
+        if tun_type & type_mask != flags & type_mask {
+            let layer = if tun_type & IFF_TAP as c_short != 0 {
+                "TAP"
+            } else {
+                "TUN"
+            };
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{name} is a {layer} device"),
+            ));
+        }
+    }
+    if let Some(multi_queue) = info.multi_queue {
+        if multi_queue != (flags & IFF_MULTI_QUEUE as c_short != 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("multi_queue does not match the flags of {name}"),
+            ));
+        }
     }
+    Ok(())
 }
 
 unsafe fn name(fd: RawFd) -> io::Result<String> {
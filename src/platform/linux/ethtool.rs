@@ -0,0 +1,312 @@
+//! Minimal `SIOCETHTOOL` client for the netdev feature flags.
+//!
+//! https://github.com/torvalds/linux/blob/master/include/uapi/linux/ethtool.h
+use crate::platform::linux::sys::siocethtool;
+use crate::platform::unix::device::ctl;
+use crate::platform::DeviceImpl;
+use libc::{c_char, ifreq, IFNAMSIZ};
+use std::io;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+
+const ETHTOOL_GSTRINGS: u32 = 0x1b;
+const ETHTOOL_GSSET_INFO: u32 = 0x37;
+const ETHTOOL_GFEATURES: u32 = 0x3a;
+const ETHTOOL_SFEATURES: u32 = 0x3b;
+const ETH_SS_FEATURES: u32 = 4;
+const ETH_GSTRING_LEN: usize = 32;
+
+/// An interface feature that can be queried and toggled like with `ethtool -k`/`ethtool -K`.
+///
+/// Most of them map to several kernel features, e.g. [`EthtoolFeature::TxChecksumming`]
+/// covers `tx-checksum-ipv4`, `tx-checksum-ipv6`, `tx-checksum-ip-generic` and so on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EthtoolFeature {
+    /// `rx-checksumming`
+    RxChecksumming,
+    /// `tx-checksumming`
+    TxChecksumming,
+    /// `scatter-gather`
+    ScatterGather,
+    /// `tcp-segmentation-offload`
+    TcpSegmentation,
+    /// `tx-udp-segmentation`
+    UdpSegmentation,
+    /// `generic-segmentation-offload`
+    GenericSegmentation,
+    /// `generic-receive-offload`
+    GenericReceive,
+    /// `large-receive-offload`
+    LargeReceive,
+    /// `rx-gro-list`
+    GroList,
+    /// `rx-udp-gro-forwarding`
+    UdpGroForwarding,
+}
+
+impl EthtoolFeature {
+    /// All features, in `ethtool -k` order.
+    pub const ALL: [EthtoolFeature; 10] = [
+        EthtoolFeature::RxChecksumming,
+        EthtoolFeature::TxChecksumming,
+        EthtoolFeature::ScatterGather,
+        EthtoolFeature::TcpSegmentation,
+        EthtoolFeature::UdpSegmentation,
+        EthtoolFeature::GenericSegmentation,
+        EthtoolFeature::GenericReceive,
+        EthtoolFeature::LargeReceive,
+        EthtoolFeature::GroList,
+        EthtoolFeature::UdpGroForwarding,
+    ];
+    /// The name used by `ethtool -k`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EthtoolFeature::RxChecksumming => "rx-checksumming",
+            EthtoolFeature::TxChecksumming => "tx-checksumming",
+            EthtoolFeature::ScatterGather => "scatter-gather",
+            EthtoolFeature::TcpSegmentation => "tcp-segmentation-offload",
+            EthtoolFeature::UdpSegmentation => "tx-udp-segmentation",
+            EthtoolFeature::GenericSegmentation => "generic-segmentation-offload",
+            EthtoolFeature::GenericReceive => "generic-receive-offload",
+            EthtoolFeature::LargeReceive => "large-receive-offload",
+            EthtoolFeature::GroList => "rx-gro-list",
+            EthtoolFeature::UdpGroForwarding => "rx-udp-gro-forwarding",
+        }
+    }
+    /// The kernel features (`ETH_SS_FEATURES` strings) this feature consists of.
+    pub fn kernel_names(&self) -> &'static [&'static str] {
+        match self {
+            EthtoolFeature::RxChecksumming => &["rx-checksum"],
+            EthtoolFeature::TxChecksumming => &[
+                "tx-checksum-ipv4",
+                "tx-checksum-ip-generic",
+                "tx-checksum-ipv6",
+                "tx-checksum-fcoe-crc",
+                "tx-checksum-sctp",
+            ],
+            EthtoolFeature::ScatterGather => &["tx-scatter-gather", "tx-scatter-gather-fraglist"],
+            EthtoolFeature::TcpSegmentation => &[
+                "tx-tcp-segmentation",
+                "tx-tcp-ecn-segmentation",
+                "tx-tcp-mangleid-segmentation",
+                "tx-tcp6-segmentation",
+            ],
+            EthtoolFeature::UdpSegmentation => &["tx-udp-segmentation"],
+            EthtoolFeature::GenericSegmentation => &["tx-generic-segmentation"],
+            EthtoolFeature::GenericReceive => &["rx-gro"],
+            EthtoolFeature::LargeReceive => &["rx-lro"],
+            EthtoolFeature::GroList => &["rx-gro-list"],
+            EthtoolFeature::UdpGroForwarding => &["rx-udp-gro-forwarding"],
+        }
+    }
+}
+
+impl std::fmt::Display for EthtoolFeature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The netdev features of an interface, as read by [`DeviceImpl::features`].
+#[derive(Clone, Debug)]
+pub struct DeviceFeatures {
+    names: Vec<String>,
+    available: Vec<u32>,
+    active: Vec<u32>,
+    never_changed: Vec<u32>,
+}
+
+fn bit(bits: &[u32], i: usize) -> bool {
+    bits.get(i / 32).is_some_and(|v| v & (1 << (i % 32)) != 0)
+}
+
+impl DeviceFeatures {
+    fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|v| v == name)
+    }
+    fn indexes(&self, feature: EthtoolFeature) -> impl Iterator<Item = usize> + '_ {
+        feature
+            .kernel_names()
+            .iter()
+            .filter_map(|name| self.index(name))
+    }
+    fn is_changeable_index(&self, i: usize) -> bool {
+        bit(&self.available, i) && !bit(&self.never_changed, i)
+    }
+    /// Returns true if the kernel feature `name` (e.g. `tx-udp-segmentation`) is active.
+    pub(crate) fn is_active_name(&self, name: &str) -> bool {
+        self.index(name).is_some_and(|i| bit(&self.active, i))
+    }
+    /// Returns true if any part of the feature is active,
+    /// e.g. `tx-checksumming` is on if any kind of checksum is offloaded.
+    pub fn is_active(&self, feature: EthtoolFeature) -> bool {
+        self.indexes(feature).any(|i| bit(&self.active, i))
+    }
+    /// Returns true if the feature can be changed with [`DeviceImpl::set_features`].
+    pub fn is_changeable(&self, feature: EthtoolFeature) -> bool {
+        self.indexes(feature).any(|i| self.is_changeable_index(i))
+    }
+    /// All kernel features of the interface and whether they are active.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, bool)> + '_ {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), bit(&self.active, i)))
+    }
+}
+
+/// Runs an ethtool command whose buffer starts with the `u32` command.
+fn ethtool<T>(name: &str, data: &mut [T]) -> io::Result<()> {
+    if name.len() >= IFNAMSIZ {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "device name too long",
+        ));
+    }
+    unsafe {
+        let ctl = ctl()?;
+        let mut req: ifreq = mem::zeroed();
+        std::ptr::copy_nonoverlapping(
+            name.as_ptr() as *const c_char,
+            req.ifr_name.as_mut_ptr(),
+            name.len(),
+        );
+        req.ifr_ifru.ifru_data = data.as_mut_ptr() as *mut c_char;
+        if let Err(err) = siocethtool(ctl.as_raw_fd(), &mut req) {
+            return Err(io::Error::from(err));
+        }
+    }
+    Ok(())
+}
+
+fn feature_names(name: &str) -> io::Result<Vec<String>> {
+    // struct ethtool_sset_info with room for one set
+    let mut info: [u64; 3] = [0; 3];
+    info[0] = if cfg!(target_endian = "big") {
+        (ETHTOOL_GSSET_INFO as u64) << 32
+    } else {
+        ETHTOOL_GSSET_INFO as u64
+    };
+    info[1] = 1 << ETH_SS_FEATURES;
+    ethtool(name, &mut info)?;
+    if info[1] & (1 << ETH_SS_FEATURES) == 0 {
+        return Ok(Vec::new());
+    }
+    let count = u32::from_ne_bytes(info[2].to_ne_bytes()[..4].try_into().unwrap()) as usize;
+    // struct ethtool_gstrings
+    let mut strings = vec![0u32; 3 + count * ETH_GSTRING_LEN / 4];
+    strings[0] = ETHTOOL_GSTRINGS;
+    strings[1] = ETH_SS_FEATURES;
+    strings[2] = count as u32;
+    ethtool(name, &mut strings)?;
+    let bytes: Vec<u8> = strings[3..].iter().flat_map(|v| v.to_ne_bytes()).collect();
+    Ok(bytes
+        .chunks_exact(ETH_GSTRING_LEN)
+        .map(|raw| {
+            let len = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+            String::from_utf8_lossy(&raw[..len]).into_owned()
+        })
+        .collect())
+}
+
+/// Reads the names and state of all netdev features (`ETHTOOL_GFEATURES`).
+pub(crate) fn features(name: &str) -> io::Result<DeviceFeatures> {
+    let names = feature_names(name)?;
+    let size = names.len().div_ceil(32);
+    // struct ethtool_gfeatures
+    let mut buf = vec![0u32; 2 + size * 4];
+    buf[0] = ETHTOOL_GFEATURES;
+    buf[1] = size as u32;
+    ethtool(name, &mut buf)?;
+    // struct ethtool_get_features_block: available, requested, active, never_changed
+    let blocks = || buf[2..].chunks_exact(4);
+    Ok(DeviceFeatures {
+        names,
+        available: blocks().map(|v| v[0]).collect(),
+        active: blocks().map(|v| v[2]).collect(),
+        never_changed: blocks().map(|v| v[3]).collect(),
+    })
+}
+
+/// Requests the changeable kernel features of each feature to be turned on or off
+/// (`ETHTOOL_SFEATURES`).
+fn set_features(name: &str, changes: &[(EthtoolFeature, bool)]) -> io::Result<()> {
+    let current = features(name)?;
+    let size = current.names.len().div_ceil(32);
+    // struct ethtool_sfeatures
+    let mut buf = vec![0u32; 2 + size * 2];
+    buf[0] = ETHTOOL_SFEATURES;
+    buf[1] = size as u32;
+    for &(feature, on) in changes {
+        let mut changeable = current
+            .indexes(feature)
+            .filter(|&i| current.is_changeable_index(i))
+            .peekable();
+        if changeable.peek().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{feature} cannot be changed on {name}"),
+            ));
+        }
+        for i in changeable {
+            // struct ethtool_set_features_block: valid, requested
+            let block = &mut buf[2 + i / 32 * 2..][..2];
+            block[0] |= 1 << (i % 32);
+            if on {
+                block[1] |= 1 << (i % 32);
+            } else {
+                block[1] &= !(1 << (i % 32));
+            }
+        }
+    }
+    ethtool(name, &mut buf)?;
+    // Some features depend on others, or are limited by the driver (e.g. TUNSETOFFLOAD).
+    // The ETHTOOL_F_WISH result is not reliably passed through, so read the features back.
+    let current = features(name)?;
+    let failed: Vec<&str> = changes
+        .iter()
+        .filter(|&&(feature, on)| current.is_active(feature) != on)
+        .map(|(feature, _)| feature.name())
+        .collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    Err(io::Error::other(format!(
+        "failed to change {} on {name}",
+        failed.join(", ")
+    )))
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Reads the netdev features of the interface, like `ethtool -k`.
+    pub fn features(&self) -> io::Result<DeviceFeatures> {
+        let _guard = self.op_lock.lock().unwrap();
+        features(&self.name_impl()?)
+    }
+    /// Turns netdev features of the interface on or off, like `ethtool -K`.
+    ///
+    /// For features that consist of several kernel features, all changeable ones are set.
+    /// The checksum and segmentation offloads can only be turned on as far as
+    /// the offload negotiated when creating the device allows (see `DeviceBuilder::offload`).
+    /// If some changes could not be applied, the others still are, and an error naming
+    /// the failed ones is returned.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, EthtoolFeature};
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .offload(true)
+    ///     .build_sync()
+    ///     .unwrap();
+    /// dev.set_features(&[(EthtoolFeature::GenericReceive, false)])
+    ///     .unwrap();
+    /// assert!(!dev.features().unwrap().is_active(EthtoolFeature::GenericReceive));
+    /// ```
+    pub fn set_features(&self, changes: &[(EthtoolFeature, bool)]) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        set_features(&self.name_impl()?, changes)
+    }
+}
@@ -0,0 +1,220 @@
+use crate::platform::linux::netlink::{
+    attr_ip, attr_str, attr_u32, IfInfoMsg, NetlinkMessage, NetlinkReply, NetlinkSocket,
+};
+use ipnet::IpNet;
+use libc::{
+    ifaddrmsg, AF_INET, AF_UNSPEC, ENOBUFS, ENODEV, IFA_ADDRESS, IFA_LOCAL, IFF_UP, IFLA_IFNAME,
+    IFLA_MTU, RTM_DELADDR, RTM_DELLINK, RTM_GETADDR, RTM_GETLINK, RTM_NEWADDR, RTM_NEWLINK,
+    RTNLGRP_IPV4_IFADDR, RTNLGRP_IPV6_IFADDR, RTNLGRP_LINK,
+};
+use std::collections::{HashSet, VecDeque};
+use std::io;
+
+/// A change of the device made by the kernel or by another process.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LinkEvent {
+    /// The device was brought up (`IFF_UP` set).
+    Up,
+    /// The device was brought down (`IFF_UP` cleared).
+    Down,
+    /// The MTU changed to the given value.
+    MtuChanged(u32),
+    /// An address was assigned to the device.
+    ///
+    /// Updates of an existing address, such as the end of IPv6 duplicate address
+    /// detection or a lifetime refresh, are not reported.
+    AddressAdded(IpNet),
+    /// An address was removed from the device.
+    AddressRemoved(IpNet),
+    /// The device was renamed to the given name.
+    Renamed(String),
+    /// The device was deleted. No further events follow.
+    Deleted,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LinkState {
+    up: bool,
+    mtu: Option<u32>,
+    name: Option<String>,
+}
+
+/// Turns rtnetlink notifications about one interface into [`LinkEvent`]s.
+///
+/// The link state and addresses are snapshotted on creation, so only later changes are reported.
+pub(crate) struct LinkWatcher {
+    index: u32,
+    state: Option<LinkState>,
+    addresses: Option<HashSet<IpNet>>,
+    pending: VecDeque<LinkEvent>,
+    deleted: bool,
+}
+
+impl LinkWatcher {
+    /// Subscribes to link and address notifications and snapshots the current state of `index`.
+    pub(crate) fn subscribe(index: u32) -> io::Result<(NetlinkSocket, Self)> {
+        let socket =
+            NetlinkSocket::subscribe(&[RTNLGRP_LINK, RTNLGRP_IPV4_IFADDR, RTNLGRP_IPV6_IFADDR])?;
+        let mut watcher = Self {
+            index,
+            state: None,
+            addresses: None,
+            pending: VecDeque::new(),
+            deleted: false,
+        };
+        watcher.resync()?;
+        Ok((socket, watcher))
+    }
+    /// Returns the next event, `Ok(None)` once the device is deleted,
+    /// or a `WouldBlock` error if no notification is queued on `socket`.
+    pub(crate) fn next_event(&mut self, socket: &NetlinkSocket) -> io::Result<Option<LinkEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            if self.deleted {
+                return Ok(None);
+            }
+            match socket.recv() {
+                Ok(replies) => replies.iter().for_each(|reply| self.handle(reply)),
+                // The receive queue overflowed and notifications were lost.
+                Err(e) if e.raw_os_error() == Some(ENOBUFS) => self.resync()?,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    fn resync(&mut self) -> io::Result<()> {
+        let hdr = IfInfoMsg {
+            ifi_index: self.index as i32,
+            ..Default::default()
+        };
+        let socket = NetlinkSocket::new()?;
+        match socket.get(NetlinkMessage::new(RTM_GETLINK, 0, &hdr)) {
+            Ok(reply) => self.handle(&reply),
+            Err(e) if e.raw_os_error() == Some(ENODEV) => {
+                self.pending.push_back(LinkEvent::Deleted);
+                self.deleted = true;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+        let hdr = ifaddrmsg {
+            ifa_family: AF_UNSPEC as u8,
+            ifa_prefixlen: 0,
+            ifa_flags: 0,
+            ifa_scope: 0,
+            ifa_index: 0,
+        };
+        let addresses: HashSet<IpNet> = socket
+            .dump(NetlinkMessage::new(RTM_GETADDR, 0, &hdr))?
+            .iter()
+            .filter(|reply| reply.ty == RTM_NEWADDR)
+            .filter_map(|reply| self.address(reply))
+            .collect();
+        if let Some(old) = &self.addresses {
+            for net in addresses.difference(old) {
+                self.pending.push_back(LinkEvent::AddressAdded(*net));
+            }
+            for net in old.difference(&addresses) {
+                self.pending.push_back(LinkEvent::AddressRemoved(*net));
+            }
+        }
+        self.addresses = Some(addresses);
+        Ok(())
+    }
+    fn handle(&mut self, reply: &NetlinkReply) {
+        if self.deleted {
+            return;
+        }
+        match reply.ty {
+            RTM_NEWLINK | RTM_DELLINK => {
+                let Some(hdr) = reply.header::<IfInfoMsg>() else {
+                    return;
+                };
+                if hdr.ifi_index as u32 != self.index {
+                    return;
+                }
+                if reply.ty == RTM_DELLINK {
+                    self.pending.push_back(LinkEvent::Deleted);
+                    self.deleted = true;
+                    return;
+                }
+                let mut state = LinkState {
+                    up: hdr.ifi_flags & IFF_UP as u32 != 0,
+                    mtu: None,
+                    name: None,
+                };
+                for (ty, data) in reply.attrs::<IfInfoMsg>() {
+                    match ty {
+                        IFLA_MTU => state.mtu = attr_u32(data),
+                        IFLA_IFNAME => state.name = attr_str(data),
+                        _ => {}
+                    }
+                }
+                self.update(state);
+            }
+            RTM_NEWADDR | RTM_DELADDR => {
+                let Some(net) = self.address(reply) else {
+                    return;
+                };
+                let addresses = self.addresses.get_or_insert_with(HashSet::new);
+                // RTM_NEWADDR is also sent when an existing address changes.
+                if reply.ty == RTM_NEWADDR {
+                    if addresses.insert(net) {
+                        self.pending.push_back(LinkEvent::AddressAdded(net));
+                    }
+                } else if addresses.remove(&net) {
+                    self.pending.push_back(LinkEvent::AddressRemoved(net));
+                }
+            }
+            _ => {}
+        }
+    }
+    /// The address of an address message about this interface.
+    fn address(&self, reply: &NetlinkReply) -> Option<IpNet> {
+        let hdr = reply.header::<ifaddrmsg>()?;
+        if hdr.ifa_index != self.index {
+            return None;
+        }
+        let mut address = None;
+        let mut local = None;
+        for (ty, data) in reply.attrs::<ifaddrmsg>() {
+            match ty {
+                IFA_ADDRESS => address = attr_ip(data),
+                IFA_LOCAL => local = attr_ip(data),
+                _ => {}
+            }
+        }
+        // For IPv4, IFA_ADDRESS is the peer address of point-to-point links.
+        if hdr.ifa_family as i32 == AF_INET {
+            address = local.or(address);
+        }
+        address.and_then(|ip| IpNet::new(ip, hdr.ifa_prefixlen).ok())
+    }
+    fn update(&mut self, mut state: LinkState) {
+        let Some(old) = self.state.take() else {
+            self.state = Some(state);
+            return;
+        };
+        if state.up != old.up {
+            self.pending.push_back(if state.up {
+                LinkEvent::Up
+            } else {
+                LinkEvent::Down
+            });
+        }
+        match state.mtu {
+            Some(mtu) if old.mtu != Some(mtu) => self.pending.push_back(LinkEvent::MtuChanged(mtu)),
+            None => state.mtu = old.mtu,
+            _ => {}
+        }
+        match &state.name {
+            Some(name) if old.name.as_ref() != Some(name) => {
+                self.pending.push_back(LinkEvent::Renamed(name.clone()))
+            }
+            None => state.name = old.name,
+            _ => {}
+        }
+        self.state = Some(state);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::builder::Layer;
+use crate::platform::linux::ethtool;
+use crate::platform::linux::offload::VIRTIO_NET_HDR_LEN;
+use crate::platform::linux::sys::{tungetiff, tungetvnethdrsz};
+use crate::platform::unix::{Fd, Tun};
+use crate::platform::DeviceImpl;
+use libc::{
+    c_int, c_short, ifreq, IFF_MULTI_QUEUE, IFF_NO_PI, IFF_TAP, IFF_VNET_HDR, MSG_CMSG_CLOEXEC,
+    MSG_CTRUNC, MSG_NOSIGNAL, SCM_RIGHTS, SOL_SOCKET,
+};
+use std::io;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::sync::{Arc, Mutex};
+
+/// Set in the payload byte if UDP segmentation offload is enabled,
+/// which cannot be queried from the fd.
+const STATE_UDP_GSO: u8 = 1;
+
+/// Room for one `SCM_RIGHTS` message carrying a single fd, suitably aligned.
+type CmsgBuffer = [u64; 4];
+
+/// How a TUN/TAP fd opened elsewhere was attached, see [`SyncDevice::from_fd_with_state`].
+///
+/// [`SyncDevice::from_fd`] detects it automatically; build it by hand
+/// when detection is not possible, e.g. for a device in another network namespace.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FdState {
+    /// TUN (`Layer::L3`) or TAP (`Layer::L2`).
+    pub layer: Layer,
+    /// Packets carry the packet information header (no `IFF_NO_PI`).
+    pub packet_information: bool,
+    /// The device was created with `IFF_MULTI_QUEUE`.
+    pub multi_queue: bool,
+    /// Packets carry a virtio-net header (`IFF_VNET_HDR`), i.e. offload is enabled.
+    pub vnet_hdr: bool,
+    /// The length of the virtio-net header (`TUNGETVNETHDRSZ`).
+    /// Values below [`VIRTIO_NET_HDR_LEN`], such as the default 0, mean [`VIRTIO_NET_HDR_LEN`].
+    pub vnet_hdr_len: usize,
+    /// UDP segmentation offload is enabled.
+    pub udp_gso: bool,
+}
+
+/// Sends `payload` with a duplicate of `fd` attached (`SCM_RIGHTS`).
+pub(crate) fn send_with_fd(socket: BorrowedFd<'_>, payload: &[u8], fd: RawFd) -> io::Result<()> {
+    let mut iov = libc::iovec {
+        iov_base: payload.as_ptr() as *mut _,
+        iov_len: payload.len(),
+    };
+    let mut cmsg_buf: CmsgBuffer = [0; 4];
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+    msg.msg_controllen = unsafe { libc::CMSG_SPACE(mem::size_of::<c_int>() as _) } as _;
+    let n = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = SOL_SOCKET;
+        (*cmsg).cmsg_type = SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<c_int>() as _) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut c_int, fd);
+        libc::sendmsg(socket.as_raw_fd(), &msg, MSG_NOSIGNAL)
+    };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl FdState {
+    /// Detects the state of an attached TUN/TAP fd.
+    ///
+    /// The flags are read with `TUNGETIFF` and the virtio-net header size with
+    /// `TUNGETVNETHDRSZ`. UDP segmentation offload is read from the ethtool features
+    /// of the interface, which is only possible in its network namespace;
+    /// otherwise it is assumed to be off.
+    pub fn detect(fd: BorrowedFd<'_>) -> io::Result<FdState> {
+        let mut req: ifreq = unsafe { mem::zeroed() };
+        unsafe {
+            if let Err(err) = tungetiff(fd.as_raw_fd(), &mut req as *mut _ as *mut _) {
+                return Err(io::Error::from(err));
+            }
+        }
+        let flags = unsafe { req.ifr_ifru.ifru_flags } as c_int;
+        let vnet_hdr = flags & IFF_VNET_HDR != 0;
+        let mut udp_gso = false;
+        let mut vnet_hdr_len = 0;
+        if vnet_hdr {
+            let mut size = 0;
+            unsafe {
+                if let Err(err) = tungetvnethdrsz(fd.as_raw_fd(), &mut size) {
+                    return Err(io::Error::from(err));
+                }
+            }
+            vnet_hdr_len = size as usize;
+            let name = unsafe { std::ffi::CStr::from_ptr(req.ifr_name.as_ptr()) };
+            match ethtool::features(&name.to_string_lossy()) {
+                Ok(features) => udp_gso = features.is_active_name("tx-udp-segmentation"),
+                Err(e) => log::warn!("failed to read the offload state: {e:?}"),
+            }
+        }
+        Ok(FdState {
+            layer: if flags & IFF_TAP != 0 {
+                Layer::L2
+            } else {
+                Layer::L3
+            },
+            packet_information: flags & IFF_NO_PI == 0,
+            multi_queue: flags & IFF_MULTI_QUEUE != 0,
+            vnet_hdr,
+            vnet_hdr_len,
+            udp_gso,
+        })
+    }
+}
+
+impl DeviceImpl {
+    pub(crate) fn from_state(tun: Tun, state: FdState) -> Self {
+        let mut flags: c_short = state.layer.into();
+        if !state.packet_information {
+            flags |= IFF_NO_PI as c_short;
+        }
+        if state.multi_queue {
+            flags |= IFF_MULTI_QUEUE as c_short;
+        }
+        if state.vnet_hdr {
+            flags |= IFF_VNET_HDR as c_short;
+        }
+        Self {
+            tun,
+            vnet_hdr: state.vnet_hdr,
+            vnet_hdr_len: if state.vnet_hdr {
+                state.vnet_hdr_len.max(VIRTIO_NET_HDR_LEN)
+            } else {
+                0
+            },
+            udp_gso: state.vnet_hdr && state.udp_gso,
+            flags,
+            op_lock: Arc::new(Mutex::new(())),
+            filter: Arc::new(Mutex::new(None)),
+            io_counters: None,
+            netns: Arc::new(Mutex::new(None)),
+            rules: Arc::default(),
+        }
+    }
+    pub(crate) fn recv_fd<S: AsFd>(socket: &S) -> io::Result<Self> {
+        let mut state = 0u8;
+        let mut iov = libc::iovec {
+            iov_base: &mut state as *mut u8 as *mut _,
+            iov_len: 1,
+        };
+        let mut cmsg_buf: CmsgBuffer = [0; 4];
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+        msg.msg_controllen = mem::size_of::<CmsgBuffer>() as _;
+        let n = unsafe { libc::recvmsg(socket.as_fd().as_raw_fd(), &mut msg, MSG_CMSG_CLOEXEC) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Take ownership of every fd received, so that unexpected ones are closed.
+        let mut fds = Vec::new();
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == SOL_SOCKET && (*cmsg).cmsg_type == SCM_RIGHTS {
+                    let data = libc::CMSG_DATA(cmsg) as *const c_int;
+                    let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                    for i in 0..len / mem::size_of::<c_int>() {
+                        fds.push(OwnedFd::from_raw_fd(std::ptr::read_unaligned(data.add(i))));
+                    }
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+        if fds.len() > 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected one file descriptor, received {}", fds.len()),
+            ));
+        }
+        let fd = match fds.pop() {
+            Some(fd) => Fd::new(fd.into_raw_fd())?,
+            None if n == 0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "no file descriptor received",
+                ))
+            }
+        };
+        if msg.msg_flags & MSG_CTRUNC != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "control message truncated",
+            ));
+        }
+        let mut fd_state = FdState::detect(fd.as_fd())?;
+        // The sender knows better than the ethtool features.
+        fd_state.udp_gso = state & STATE_UDP_GSO != 0;
+        Ok(DeviceImpl::from_state(Tun::new(fd), fd_state))
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Sends the device fd over a Unix socket (`SCM_RIGHTS`), together with its offload state.
+    ///
+    /// This lets a privileged helper create and configure the device, then hand it
+    /// to an unprivileged process, which receives it with
+    /// [`SyncDevice::from_received_fd`](crate::SyncDevice::from_received_fd).
+    /// The helper can drop its own handle afterwards; the receiver gets a duplicate.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::os::unix::net::UnixStream;
+    /// use tun_rs::{DeviceBuilder, SyncDevice};
+    /// let (helper, daemon) = UnixStream::pair().unwrap();
+    /// // In the privileged helper:
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .offload(true)
+    ///     .build_sync()
+    ///     .unwrap();
+    /// dev.send_fd(&helper).unwrap();
+    /// drop(dev);
+    /// // In the unprivileged daemon:
+    /// let dev = SyncDevice::from_received_fd(&daemon).unwrap();
+    /// assert!(dev.tcp_gso());
+    /// ```
+    pub fn send_fd<S: AsFd>(&self, socket: &S) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        let state = if self.udp_gso { STATE_UDP_GSO } else { 0 };
+        send_with_fd(socket.as_fd(), &[state], self.as_raw_fd())
+    }
+}
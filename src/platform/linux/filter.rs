@@ -0,0 +1,271 @@
+use crate::platform::linux::sys::{tunattachfilter, tundetachfilter};
+use crate::platform::DeviceImpl;
+use ipnet::IpNet;
+use libc::{
+    sock_filter, sock_fprog, BPF_ABS, BPF_ALU, BPF_AND, BPF_B, BPF_H, BPF_JEQ, BPF_JMP, BPF_K,
+    BPF_LD, BPF_RET, BPF_W, ETH_P_IP, ETH_P_IPV6,
+};
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+const ETHER_TYPE_OFFSET: u32 = 12;
+const ETHER_HDR_LEN: u32 = 14;
+const IPV4_DST_OFFSET: u32 = ETHER_HDR_LEN + 16;
+const IPV6_DST_OFFSET: u32 = ETHER_HDR_LEN + 24;
+
+#[derive(Copy, Clone)]
+enum Jump {
+    Next,
+    Accept,
+    Drop,
+    Label(usize),
+}
+
+/// A tiny classic BPF assembler with forward-only symbolic jumps.
+struct Assembler {
+    insns: Vec<(u16, Jump, Jump, u32)>,
+    labels: Vec<usize>,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Self {
+            insns: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+    fn stmt(&mut self, code: u32, k: u32) {
+        self.insns.push((code as u16, Jump::Next, Jump::Next, k));
+    }
+    fn jeq(&mut self, k: u32, jt: Jump, jf: Jump) {
+        self.insns
+            .push(((BPF_JMP | BPF_JEQ | BPF_K) as u16, jt, jf, k));
+    }
+    fn new_label(&mut self) -> usize {
+        self.labels.push(usize::MAX);
+        self.labels.len() - 1
+    }
+    fn bind(&mut self, label: usize) {
+        self.labels[label] = self.insns.len();
+    }
+    /// Loads `bits` of the big-endian word at `offset` and jumps to `jf` unless they equal `value`.
+    fn match_word(&mut self, offset: u32, value: u32, bits: u8, jt: Jump, jf: Jump) {
+        self.stmt(BPF_LD | BPF_W | BPF_ABS, offset);
+        if bits < 32 {
+            let mask = u32::MAX.checked_shl(32 - bits as u32).unwrap_or(0);
+            self.stmt(BPF_ALU | BPF_AND | BPF_K, mask);
+            self.jeq(value & mask, jt, jf);
+        } else {
+            self.jeq(value, jt, jf);
+        }
+    }
+    /// Appends the verdicts, falling through to `accept` or to drop at the end of the program.
+    fn finish(self, accept: bool) -> io::Result<Vec<sock_filter>> {
+        let end = self.insns.len();
+        let (accept_pos, drop_pos) = if accept {
+            (end, end + 1)
+        } else {
+            (end + 1, end)
+        };
+        let resolve = |pos: usize, jump: Jump| -> io::Result<u8> {
+            let target = match jump {
+                Jump::Next => return Ok(0),
+                Jump::Accept => accept_pos,
+                Jump::Drop => drop_pos,
+                Jump::Label(label) => self.labels[label],
+            };
+            u8::try_from(target - pos - 1)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "filter too large"))
+        };
+        let mut program = Vec::with_capacity(end + 2);
+        for (pos, &(code, jt, jf, k)) in self.insns.iter().enumerate() {
+            program.push(sock_filter {
+                code,
+                jt: resolve(pos, jt)?,
+                jf: resolve(pos, jf)?,
+                k,
+            });
+        }
+        let ret = |k| sock_filter {
+            code: (BPF_RET | BPF_K) as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        };
+        if accept {
+            program.extend([ret(u32::MAX), ret(0)]);
+        } else {
+            program.extend([ret(0), ret(u32::MAX)]);
+        }
+        Ok(program)
+    }
+}
+
+/// Builds classic BPF programs for [`DeviceImpl::attach_filter`] from common rules.
+///
+/// The kernel only accepts filters on TAP devices, so the programs match Ethernet frames.
+/// Packets rejected by the filter are dropped before they are copied to userspace.
+/// # Example
+/// ```no_run
+/// use tun_rs::{DeviceBuilder, Layer, PacketFilter};
+/// let dev = DeviceBuilder::new()
+///     .layer(Layer::L2)
+///     .ipv4("10.0.0.1", 24, None)
+///     .build_sync()
+///     .unwrap();
+/// let filter = PacketFilter::new()
+///     .drop_multicast()
+///     .destination("10.0.0.0/24".parse().unwrap())
+///     .build()
+///     .unwrap();
+/// dev.attach_filter(&filter).unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PacketFilter {
+    ipv4_only: bool,
+    drop_multicast: bool,
+    destinations: Vec<IpNet>,
+}
+
+impl PacketFilter {
+    /// Creates a filter that accepts every packet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Accepts only IPv4 packets.
+    ///
+    /// Note that this also drops ARP, so the kernel will not get answers to its
+    /// neighbour requests unless they are handled elsewhere.
+    pub fn ipv4_only(mut self) -> Self {
+        self.ipv4_only = true;
+        self
+    }
+    /// Drops IPv4 and IPv6 packets sent to a multicast address.
+    pub fn drop_multicast(mut self) -> Self {
+        self.drop_multicast = true;
+        self
+    }
+    /// Accepts only IP packets whose destination is inside `prefix`.
+    ///
+    /// Can be called multiple times; a packet is accepted if it matches any prefix.
+    pub fn destination(mut self, prefix: IpNet) -> Self {
+        self.destinations.push(prefix.trunc());
+        self
+    }
+    /// Assembles the classic BPF program.
+    ///
+    /// Fails if the program would not fit the 8-bit jump offsets of classic BPF.
+    pub fn build(&self) -> io::Result<Vec<sock_filter>> {
+        let mut asm = Assembler::new();
+        if self.drop_multicast {
+            let not_v4 = asm.new_label();
+            let done = asm.new_label();
+            asm.stmt(BPF_LD | BPF_H | BPF_ABS, ETHER_TYPE_OFFSET);
+            asm.jeq(ETH_P_IP as u32, Jump::Next, Jump::Label(not_v4));
+            asm.match_word(
+                IPV4_DST_OFFSET,
+                0xe000_0000,
+                4,
+                Jump::Drop,
+                Jump::Label(done),
+            );
+            asm.bind(not_v4);
+            asm.jeq(ETH_P_IPV6 as u32, Jump::Next, Jump::Label(done));
+            asm.stmt(BPF_LD | BPF_B | BPF_ABS, IPV6_DST_OFFSET);
+            asm.jeq(0xff, Jump::Drop, Jump::Label(done));
+            asm.bind(done);
+        }
+        if self.ipv4_only {
+            asm.stmt(BPF_LD | BPF_H | BPF_ABS, ETHER_TYPE_OFFSET);
+            asm.jeq(ETH_P_IP as u32, Jump::Next, Jump::Drop);
+        }
+        for prefix in &self.destinations {
+            let next = asm.new_label();
+            asm.stmt(BPF_LD | BPF_H | BPF_ABS, ETHER_TYPE_OFFSET);
+            let (ether_type, offset, addr) = match prefix {
+                IpNet::V4(net) => (
+                    ETH_P_IP,
+                    IPV4_DST_OFFSET,
+                    u32::from(net.addr()).to_be_bytes().to_vec(),
+                ),
+                IpNet::V6(net) => (
+                    ETH_P_IPV6,
+                    IPV6_DST_OFFSET,
+                    u128::from(net.addr()).to_be_bytes().to_vec(),
+                ),
+            };
+            let mut remaining = prefix.prefix_len();
+            let jt = if remaining == 0 {
+                Jump::Accept
+            } else {
+                Jump::Next
+            };
+            asm.jeq(ether_type as u32, jt, Jump::Label(next));
+            for (word, chunk) in addr.chunks(4).enumerate() {
+                if remaining == 0 {
+                    break;
+                }
+                let bits = remaining.min(32);
+                remaining -= bits;
+                let jt = if remaining == 0 {
+                    Jump::Accept
+                } else {
+                    Jump::Next
+                };
+                let value = u32::from_be_bytes(chunk.try_into().unwrap());
+                asm.match_word(offset + 4 * word as u32, value, bits, jt, Jump::Label(next));
+            }
+            asm.bind(next);
+        }
+        asm.finish(self.destinations.is_empty())
+    }
+}
+
+impl DeviceImpl {
+    pub(crate) fn attach_filter_impl(&self, filter: &[sock_filter]) -> io::Result<()> {
+        let len = u16::try_from(filter.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "filter too large"))?;
+        let prog = sock_fprog {
+            len,
+            filter: filter.as_ptr() as *mut _,
+        };
+        unsafe {
+            if let Err(err) = tunattachfilter(self.as_raw_fd(), &prog) {
+                return Err(io::Error::from(err));
+            }
+        }
+        Ok(())
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Attaches a classic BPF program (`TUNATTACHFILTER`) that decides which packets
+    /// the kernel passes to userspace. A return value of 0 drops the packet.
+    ///
+    /// Only TAP devices support filters; the kernel rejects them on TUN devices.
+    /// The filter applies to every queue of the device and replaces any previous filter.
+    /// Queues created later with `try_clone` get the filter as well.
+    /// See [`PacketFilter`] for building common programs.
+    pub fn attach_filter(&self, filter: &[sock_filter]) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.attach_filter_impl(filter)?;
+        *self.filter.lock().unwrap() = Some(filter.to_vec());
+        Ok(())
+    }
+    /// Detaches the filter attached with [`DeviceImpl::attach_filter`] from all queues.
+    pub fn detach_filter(&self) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        let prog = sock_fprog {
+            len: 0,
+            filter: std::ptr::null_mut(),
+        };
+        unsafe {
+            if let Err(err) = tundetachfilter(self.as_raw_fd(), &prog) {
+                return Err(io::Error::from(err));
+            }
+        }
+        *self.filter.lock().unwrap() = None;
+        Ok(())
+    }
+}
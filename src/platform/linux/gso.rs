@@ -0,0 +1,712 @@
+use crate::platform::linux::checksum::{
+    checksum, checksum_no_fold, pseudo_header_checksum_no_fold,
+};
+use crate::platform::linux::offload::{
+    ethernet_header_len, gso_none_checksum, gso_segment_header, gso_split, network_offset,
+    VirtioNetHdr, MAX_L2_HEADER_LEN, VIRTIO_NET_HDR_F_NEEDS_CSUM, VIRTIO_NET_HDR_GSO_NONE,
+    VIRTIO_NET_HDR_GSO_TCPV4, VIRTIO_NET_HDR_GSO_TCPV6, VIRTIO_NET_HDR_GSO_UDP_L4,
+};
+use crate::platform::DeviceImpl;
+use byteorder::{BigEndian, ByteOrder};
+use libc::{IPPROTO_TCP, IPPROTO_UDP};
+use std::io;
+use std::io::IoSlice;
+
+/// `VIRTIO_NET_HDR_F_DATA_VALID`
+const VIRTIO_NET_HDR_F_DATA_VALID: u8 = 2;
+
+/// The segmentation type of a packet (`VIRTIO_NET_HDR_GSO_*`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GsoType {
+    /// A single packet.
+    None,
+    /// A TCP over IPv4 super-packet (TSO).
+    TcpV4,
+    /// A TCP over IPv6 super-packet (TSO).
+    TcpV6,
+    /// A UDP super-packet (USO), over IPv4 or IPv6.
+    UdpL4,
+}
+
+/// The checksum state of a packet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChecksumState {
+    /// The transport checksum is complete, or was not checked.
+    #[default]
+    Complete,
+    /// `VIRTIO_NET_HDR_F_NEEDS_CSUM` (`CHECKSUM_PARTIAL`): the checksum field at
+    /// `start + offset` only holds the pseudo-header checksum, and the checksum over
+    /// the bytes from `start` to the end of the packet still has to be added.
+    Partial {
+        /// The offset of the transport header, the IP header length plus [`GsoInfo::l2_len`].
+        start: u16,
+        /// The offset of the checksum field within the transport header.
+        offset: u16,
+    },
+    /// `VIRTIO_NET_HDR_F_DATA_VALID`: the checksum was already verified.
+    Valid,
+}
+
+/// How a packet is segmented and checksummed, decoded from its virtio-net header.
+///
+/// Returned by [`DeviceImpl::recv_gso`] together with the unsplit packet,
+/// or created with [`GsoInfo::new`] for [`DeviceImpl::send_gso`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct GsoInfo {
+    /// The segmentation type.
+    pub gso_type: GsoType,
+    /// The payload size of each segment, except possibly the last one.
+    pub gso_size: u16,
+    /// The length of the headers repeated in each segment, including the Ethernet header on TAP devices.
+    /// 0 if `gso_type` is [`GsoType::None`].
+    pub hdr_len: u16,
+    /// The checksum state. Super-packets from the kernel carry [`ChecksumState::Partial`];
+    /// one without it is split using the transport header found after its IP header.
+    /// The checksum start counts from the Ethernet header on TAP devices.
+    pub checksum: ChecksumState,
+    /// The length of the Ethernet header, including VLAN tags, in front of the IP header
+    /// of a TAP device frame. 0 on TUN devices.
+    pub l2_len: u16,
+}
+
+impl GsoInfo {
+    /// A [`GsoInfo`] for [`DeviceImpl::send_gso`], which fills in the headers and
+    /// checksum of super-packets. `gso_size` is ignored for [`GsoType::None`].
+    pub fn new(gso_type: GsoType, gso_size: u16) -> GsoInfo {
+        GsoInfo {
+            gso_type,
+            gso_size: if gso_type == GsoType::None {
+                0
+            } else {
+                gso_size
+            },
+            hdr_len: 0,
+            checksum: ChecksumState::Complete,
+            l2_len: 0,
+        }
+    }
+    /// Validates the virtio-net header of `packet` the same way as `recv_multiple`.
+    /// `l2` indicates an Ethernet frame of a TAP device.
+    pub(crate) fn from_virtio(hdr: VirtioNetHdr, packet: &[u8], l2: bool) -> io::Result<GsoInfo> {
+        GsoInfo::decode(hdr, packet, l2).map(|(info, _)| info)
+    }
+    /// Like [`GsoInfo::from_virtio`], but also returns the virtio-net header with
+    /// `hdr_len`, `csum_start` and `csum_offset` taken from the packet, as used to split it.
+    ///
+    /// A super-packet without `VIRTIO_NET_HDR_F_NEEDS_CSUM` is located by its IP header,
+    /// and one with a `gso_size` of 0 is returned unsplit, as a [`GsoType::None`] packet.
+    pub(crate) fn decode(
+        mut hdr: VirtioNetHdr,
+        packet: &[u8],
+        l2: bool,
+    ) -> io::Result<(GsoInfo, VirtioNetHdr)> {
+        let checksum = if hdr.flags & VIRTIO_NET_HDR_F_NEEDS_CSUM != 0 {
+            ChecksumState::Partial {
+                start: hdr.csum_start,
+                offset: hdr.csum_offset,
+            }
+        } else if hdr.flags & VIRTIO_NET_HDR_F_DATA_VALID != 0 {
+            ChecksumState::Valid
+        } else {
+            ChecksumState::Complete
+        };
+        let len = packet.len();
+        if hdr.gso_type == VIRTIO_NET_HDR_GSO_NONE || hdr.gso_size == 0 {
+            if let ChecksumState::Partial { start, offset } = checksum {
+                let c_sum_at = start as usize + offset as usize;
+                if c_sum_at + 1 >= len {
+                    Err(io::Error::other(format!(
+                        "end of checksum offset ({}) exceeds packet length ({len})",
+                        c_sum_at + 1,
+                    )))?
+                }
+            }
+            let info = GsoInfo {
+                gso_type: GsoType::None,
+                gso_size: 0,
+                hdr_len: 0,
+                checksum,
+                l2_len: l2_header_len(packet, l2),
+            };
+            return Ok((info, info.to_virtio()));
+        }
+        let gso_type = match hdr.gso_type {
+            VIRTIO_NET_HDR_GSO_TCPV4 => GsoType::TcpV4,
+            VIRTIO_NET_HDR_GSO_TCPV6 => GsoType::TcpV6,
+            VIRTIO_NET_HDR_GSO_UDP_L4 => GsoType::UdpL4,
+            gso_type => Err(io::Error::other(format!(
+                "unsupported virtio GSO type: {gso_type}"
+            )))?,
+        };
+        let l2_len = network_offset(packet, l2)
+            .ok_or_else(|| io::Error::other("GSO frame does not carry IPv4 or IPv6"))?;
+        let ip = &packet[l2_len..];
+        let ip_version = ip.first().map_or(0, |v| v >> 4);
+        let min_iph_len = match ip_version {
+            4 => {
+                if gso_type == GsoType::TcpV6 {
+                    Err(io::Error::other(format!(
+                        "ip header version: 4, GSO type: {}",
+                        hdr.gso_type
+                    )))?
+                }
+                20
+            }
+            6 => {
+                if gso_type == GsoType::TcpV4 {
+                    Err(io::Error::other(format!(
+                        "ip header version: 6, GSO type: {}",
+                        hdr.gso_type
+                    )))?
+                }
+                40
+            }
+            ip_version => Err(io::Error::other(format!(
+                "invalid ip header version: {ip_version}"
+            )))?,
+        };
+        if ip.len() < min_iph_len {
+            Err(io::Error::other("packet is too short"))?
+        }
+        if hdr.flags & VIRTIO_NET_HDR_F_NEEDS_CSUM == 0 {
+            // The transport header follows the IP header, without IPv6 extension headers.
+            let (iph_len, protocol) = if ip_version == 4 {
+                ((ip[0] & 0x0f) as usize * 4, ip[9])
+            } else {
+                (40, ip[6])
+            };
+            let csum_offset = match (gso_type, protocol as i32) {
+                (GsoType::UdpL4, IPPROTO_UDP) => 6,
+                (GsoType::TcpV4 | GsoType::TcpV6, IPPROTO_TCP) => 16,
+                _ => Err(io::Error::other(format!(
+                    "GSO type {} does not match ip protocol {protocol}",
+                    hdr.gso_type
+                )))?,
+            };
+            hdr.csum_start = (l2_len + iph_len) as u16;
+            hdr.csum_offset = csum_offset;
+        }
+        if (hdr.csum_start as usize) < l2_len + min_iph_len {
+            Err(io::Error::other(format!(
+                "virtioNetHdr.csumStart ({}) is inside the ip header",
+                hdr.csum_start
+            )))?
+        }
+        // Don't trust hdr.hdrLen from the kernel as it can be equal to the length
+        // of the entire first packet when the kernel is handling it as part of a
+        // FORWARD path. Instead, parse the transport header length and add it onto
+        // csumStart, which is synonymous for IP header length.
+        let th_len = if gso_type == GsoType::UdpL4 {
+            8
+        } else {
+            if len <= hdr.csum_start as usize + 12 {
+                Err(io::Error::other("packet is too short"))?
+            }
+
+            let tcp_h_len = ((packet[hdr.csum_start as usize + 12] as usize) >> 4) * 4;
+            if !(20..=60).contains(&tcp_h_len) {
+                // A TCP header must be between 20 and 60 bytes in length.
+                Err(io::Error::other(format!(
+                    "tcp header len is invalid: {tcp_h_len}"
+                )))?
+            }
+            tcp_h_len
+        };
+        let hdr_len = hdr.csum_start as usize + th_len;
+        if len < hdr_len || hdr_len > u16::MAX as usize {
+            Err(io::Error::other(format!(
+                "length of packet ({len}) < virtioNetHdr.hdr_len ({hdr_len})",
+            )))?
+        }
+        hdr.hdr_len = hdr_len as u16;
+        let c_sum_at = hdr.csum_start as usize + hdr.csum_offset as usize;
+        if c_sum_at + 2 > hdr.hdr_len as usize {
+            Err(io::Error::other(format!(
+                "end of checksum offset ({}) exceeds virtioNetHdr.hdrLen ({})",
+                c_sum_at + 1,
+                hdr.hdr_len
+            )))?
+        }
+        let info = GsoInfo {
+            gso_type,
+            gso_size: hdr.gso_size,
+            hdr_len: hdr.hdr_len,
+            checksum,
+            l2_len: l2_len as u16,
+        };
+        Ok((info, hdr))
+    }
+    /// The virtio-net header describing the packet.
+    pub(crate) fn to_virtio(self) -> VirtioNetHdr {
+        let (flags, csum_start, csum_offset) = match self.checksum {
+            ChecksumState::Complete => (0, 0, 0),
+            ChecksumState::Partial { start, offset } => {
+                (VIRTIO_NET_HDR_F_NEEDS_CSUM, start, offset)
+            }
+            ChecksumState::Valid => (VIRTIO_NET_HDR_F_DATA_VALID, 0, 0),
+        };
+        VirtioNetHdr {
+            flags,
+            gso_type: match self.gso_type {
+                GsoType::None => VIRTIO_NET_HDR_GSO_NONE,
+                GsoType::TcpV4 => VIRTIO_NET_HDR_GSO_TCPV4,
+                GsoType::TcpV6 => VIRTIO_NET_HDR_GSO_TCPV6,
+                GsoType::UdpL4 => VIRTIO_NET_HDR_GSO_UDP_L4,
+            },
+            hdr_len: self.hdr_len,
+            gso_size: self.gso_size,
+            csum_start,
+            csum_offset,
+        }
+    }
+    /// Returns the number of packets `packet` is split into,
+    /// or 0 if `packet` does not match `self` or `gso_size` is 0.
+    pub fn segments(&self, packet: &[u8]) -> usize {
+        let Ok((info, _)) = self.decode_packet(packet) else {
+            return 0;
+        };
+        if info.gso_type == GsoType::None {
+            return 1;
+        }
+        packet
+            .len()
+            .saturating_sub(info.hdr_len as usize)
+            .div_ceil(info.gso_size as usize)
+            .max(1)
+    }
+    /// Splits `packet` into `bufs`, leaving `offset` bytes at the front of each buffer,
+    /// and completes partial checksums, as done by `recv_multiple`.
+    ///
+    /// `packet` is validated against `info` and its headers are parsed again,
+    /// so `hdr_len` need not be set. `packet` is modified in place.
+    /// Returns the number of packets, with their lengths written to `sizes`.
+    pub fn split<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        packet: &mut [u8],
+        bufs: &mut [B],
+        sizes: &mut [usize],
+        offset: usize,
+    ) -> io::Result<usize> {
+        let (info, hdr) = self.decode_packet(packet)?;
+        split_decoded(info, hdr, packet, bufs, sizes, offset)
+    }
+    /// Returns a lazy iterator over the packets `packet` is split into,
+    /// with the same fix-ups as [`GsoInfo::split`], but without modifying `packet`.
+    ///
+    /// Unlike `split`, no output buffers have to be allocated up front.
+    pub fn iter_segments<'a>(&self, packet: &'a [u8]) -> io::Result<GsoSegments<'a>> {
+        let (info, hdr) = self.decode_packet(packet)?;
+        // The headers are fixed up in a copy, which must include the checksum field.
+        let header_len = match (info.gso_type, info.checksum) {
+            (GsoType::None, ChecksumState::Partial { start, offset }) => {
+                start as usize + offset as usize + 3
+            }
+            (GsoType::None, _) => 0,
+            _ => hdr.hdr_len as usize,
+        };
+        if header_len > MAX_SEGMENT_HEADER_LEN {
+            Err(invalid_input(format!(
+                "header length {header_len} is not supported"
+            )))?
+        }
+        Ok(GsoSegments {
+            info,
+            hdr,
+            packet,
+            next_at: 0,
+            index: 0,
+        })
+    }
+    /// Validates `self` against `packet` and derives hdr_len from the transport header.
+    fn decode_packet(&self, packet: &[u8]) -> io::Result<(GsoInfo, VirtioNetHdr)> {
+        if self.gso_type != GsoType::None && self.gso_size == 0 {
+            Err(invalid_input("gso_size is 0".into()))?
+        }
+        GsoInfo::decode(self.to_virtio(), packet, self.l2_len != 0)
+    }
+}
+
+/// Splits `packet` described by [`GsoInfo::decode`] into `bufs`.
+pub(crate) fn split_decoded<B: AsRef<[u8]> + AsMut<[u8]>>(
+    info: GsoInfo,
+    hdr: VirtioNetHdr,
+    packet: &mut [u8],
+    bufs: &mut [B],
+    sizes: &mut [usize],
+    offset: usize,
+) -> io::Result<usize> {
+    if bufs.is_empty() || bufs.len() != sizes.len() {
+        return Err(io::Error::other("bufs error"));
+    }
+    if info.gso_type == GsoType::None {
+        let len = packet.len();
+        if let ChecksumState::Partial { start, offset } = info.checksum {
+            // This means CHECKSUM_PARTIAL in skb context. We are responsible
+            // for computing the checksum starting at hdr.csumStart and placing
+            // at hdr.csumOffset.
+            gso_none_checksum(packet, start, offset);
+        }
+        if bufs[0].as_ref().len().saturating_sub(offset) < len {
+            Err(io::Error::other(format!(
+                "read len {len} overflows bufs element len {}",
+                bufs[0].as_ref().len()
+            )))?
+        }
+        sizes[0] = len;
+        bufs[0].as_mut()[offset..offset + len].copy_from_slice(packet);
+        return Ok(1);
+    }
+    gso_split(packet, hdr, bufs, sizes, offset, info.l2_len as usize)
+}
+
+/// The maximum length of the headers of a segment, an Ethernet header with two VLAN tags,
+/// an IPv4 header with options and a TCP header with options.
+const MAX_SEGMENT_HEADER_LEN: usize = MAX_L2_HEADER_LEN + 60 + 60;
+
+/// A packet split from a GSO packet by [`GsoSegments`]: the fixed-up headers
+/// followed by a slice of the original payload.
+#[derive(Clone, Copy)]
+pub struct GsoSegment<'a> {
+    header: [u8; MAX_SEGMENT_HEADER_LEN],
+    header_len: usize,
+    payload: &'a [u8],
+}
+
+impl<'a> GsoSegment<'a> {
+    /// The IP and transport headers of the packet.
+    ///
+    /// Empty for a [`GsoType::None`] packet with a complete checksum.
+    pub fn header(&self) -> &[u8] {
+        &self.header[..self.header_len]
+    }
+    /// The rest of the packet, borrowed from the original packet.
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+    /// The length of the packet.
+    pub fn len(&self) -> usize {
+        self.header_len + self.payload.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// The packet as header and payload slices, e.g. for `send_vectored`.
+    pub fn io_slices(&self) -> [IoSlice<'_>; 2] {
+        [IoSlice::new(self.header()), IoSlice::new(self.payload)]
+    }
+    /// Copies the packet into `buf` and returns its length.
+    pub fn copy_to(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.len();
+        if buf.len() < len {
+            Err(io::Error::other(format!(
+                "packet len {len} overflows buffer len {}",
+                buf.len()
+            )))?
+        }
+        buf[..self.header_len].copy_from_slice(self.header());
+        buf[self.header_len..len].copy_from_slice(self.payload);
+        Ok(len)
+    }
+}
+
+/// Lazy iterator over the packets of a GSO packet,
+/// created by [`GsoInfo::iter_segments`].
+pub struct GsoSegments<'a> {
+    info: GsoInfo,
+    hdr: VirtioNetHdr,
+    packet: &'a [u8],
+    next_at: usize,
+    index: usize,
+}
+
+impl<'a> GsoSegments<'a> {
+    fn next_none(&mut self) -> GsoSegment<'a> {
+        let mut segment = GsoSegment {
+            header: [0; MAX_SEGMENT_HEADER_LEN],
+            header_len: 0,
+            payload: self.packet,
+        };
+        if let ChecksumState::Partial { start, offset } = self.info.checksum {
+            // Complete the checksum as gso_none_checksum does, over a header
+            // reaching past the checksum field.
+            let (start, csum_at) = (start as usize, start as usize + offset as usize);
+            let header_len = (csum_at + 2 + (csum_at - start) % 2).min(self.packet.len());
+            let header = &mut segment.header[..header_len];
+            header.copy_from_slice(&self.packet[..header_len]);
+            let initial = BigEndian::read_u16(&header[csum_at..]);
+            header[csum_at] = 0;
+            header[csum_at + 1] = 0;
+            let sum = checksum_no_fold(&header[start..], initial as u64);
+            let csum = !checksum(&self.packet[header_len..], sum);
+            BigEndian::write_u16(&mut header[csum_at..], csum);
+            segment.header_len = header_len;
+            segment.payload = &self.packet[header_len..];
+        }
+        segment
+    }
+}
+
+impl<'a> Iterator for GsoSegments<'a> {
+    type Item = GsoSegment<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let info = self.info;
+        if info.gso_type == GsoType::None {
+            if self.index > 0 {
+                return None;
+            }
+            self.index = 1;
+            return Some(self.next_none());
+        }
+        let hdr_len = info.hdr_len as usize;
+        let data_at = hdr_len + self.next_at;
+        if data_at >= self.packet.len() {
+            return None;
+        }
+        let data_end = (data_at + info.gso_size as usize).min(self.packet.len());
+        let payload = &self.packet[data_at..data_end];
+        let mut segment = GsoSegment {
+            header: [0; MAX_SEGMENT_HEADER_LEN],
+            header_len: hdr_len,
+            payload,
+        };
+        gso_segment_header(
+            self.packet,
+            &self.hdr,
+            self.index,
+            payload,
+            data_end == self.packet.len(),
+            info.l2_len as usize,
+            &mut segment.header[..hdr_len],
+        );
+        self.next_at += info.gso_size as usize;
+        self.index += 1;
+        Some(segment)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = if self.info.gso_type == GsoType::None {
+            1 - self.index
+        } else {
+            self.packet
+                .len()
+                .saturating_sub(self.info.hdr_len as usize + self.next_at)
+                .div_ceil(self.info.gso_size as usize)
+        };
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for GsoSegments<'_> {}
+
+/// How [`DeviceImpl::send_gso`] writes a packet.
+pub(crate) enum GsoWrite {
+    /// The packet follows this virtio-net header, which is empty without offload.
+    Offload(Vec<u8>),
+    /// The packet is split in software with [`GsoInfo::iter_segments`],
+    /// each segment follows this empty virtio-net header.
+    Split(Vec<u8>, GsoInfo),
+}
+
+/// The length of the Ethernet header of `packet` if it is a TAP device frame.
+fn l2_header_len(packet: &[u8], l2: bool) -> u16 {
+    if !l2 {
+        return 0;
+    }
+    ethernet_header_len(packet).map_or(0, |(len, _)| len as u16)
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+impl DeviceImpl {
+    /// Decodes the virtio-net header in front of the `len` bytes read into `buf`.
+    pub(crate) fn gso_packet<'a>(
+        &self,
+        buf: &'a mut [u8],
+        len: usize,
+    ) -> io::Result<(GsoInfo, &'a mut [u8])> {
+        if !self.vnet_hdr {
+            let info = GsoInfo {
+                gso_type: GsoType::None,
+                gso_size: 0,
+                hdr_len: 0,
+                checksum: ChecksumState::Complete,
+                l2_len: l2_header_len(&buf[..len], self.is_tap()),
+            };
+            return Ok((info, &mut buf[..len]));
+        }
+        let vnet_hdr_len = self.vnet_hdr_len;
+        if len <= vnet_hdr_len {
+            Err(io::Error::other(format!(
+                "length of packet ({len}) <= vnet_hdr_len ({vnet_hdr_len})",
+            )))?
+        }
+        let hdr = VirtioNetHdr::decode(&buf[..vnet_hdr_len])?;
+        let packet = &mut buf[vnet_hdr_len..len];
+        let info = GsoInfo::from_virtio(hdr, packet, self.is_tap())?;
+        Ok((info, packet))
+    }
+
+    /// Completes the headers of the TCP or UDP super-packet `packet` for `info`,
+    /// and decides whether the kernel or [`GsoInfo::split`] segments it.
+    pub(crate) fn prepare_gso(&self, packet: &mut [u8], info: GsoInfo) -> io::Result<GsoWrite> {
+        let vnet_hdr_len = self.vnet_hdr_len;
+        if info.gso_type == GsoType::None {
+            if !self.vnet_hdr {
+                if let ChecksumState::Partial { start, offset } = info.checksum {
+                    if start as usize + offset as usize + 1 >= packet.len() {
+                        Err(invalid_input(format!(
+                            "checksum offset exceeds packet length ({})",
+                            packet.len()
+                        )))?
+                    }
+                    gso_none_checksum(packet, start, offset);
+                }
+                return Ok(GsoWrite::Offload(Vec::new()));
+            }
+            let mut hdr = vec![0; vnet_hdr_len];
+            info.to_virtio().encode_with_len(&mut hdr, vnet_hdr_len)?;
+            return Ok(GsoWrite::Offload(hdr));
+        }
+        // On TAP devices, the IP header follows the Ethernet header.
+        let l2_len = network_offset(packet, self.is_tap())
+            .ok_or_else(|| invalid_input("not an IP packet".into()))?;
+        let ip = &mut packet[l2_len..];
+        let len = ip.len();
+        if len > u16::MAX as usize {
+            Err(invalid_input(format!("ip is too long: {len}")))?
+        }
+        if info.gso_size == 0 {
+            Err(invalid_input("gso_size is 0".into()))?
+        }
+        let (iph_len, protocol, is_v6) = match ip.first().map(|v| v >> 4) {
+            Some(4) if len >= 20 => ((ip[0] & 0x0f) as usize * 4, ip[9], false),
+            Some(6) if len >= 40 => (40, ip[6], true),
+            _ => Err(invalid_input("invalid ip header".into()))?,
+        };
+        let (th_len, csum_offset) = match (info.gso_type, is_v6, protocol as i32) {
+            (GsoType::TcpV4, false, IPPROTO_TCP) | (GsoType::TcpV6, true, IPPROTO_TCP) => {
+                if len < iph_len + 20 {
+                    Err(invalid_input("ip is too short".into()))?
+                }
+                ((ip[iph_len + 12] >> 4) as usize * 4, 16)
+            }
+            (GsoType::UdpL4, _, IPPROTO_UDP) => (8, 6),
+            (gso_type, _, _) => Err(invalid_input(format!(
+                "GSO type {gso_type:?} does not match ip version {} and protocol {protocol}",
+                if is_v6 { 6 } else { 4 }
+            )))?,
+        };
+        let hdr_len = iph_len + th_len;
+        if iph_len < 20 || th_len < 8 || len < hdr_len {
+            Err(invalid_input("invalid transport header".into()))?
+        }
+
+        // Recalculate the total len (IPv4) or payload len (IPv6),
+        // the (IPv4) header checksum and the UDP length.
+        if is_v6 {
+            BigEndian::write_u16(&mut ip[4..6], (len - iph_len) as u16);
+        } else {
+            BigEndian::write_u16(&mut ip[2..4], len as u16);
+            ip[10] = 0;
+            ip[11] = 0;
+            let iph_csum = !checksum(&ip[..iph_len], 0);
+            BigEndian::write_u16(&mut ip[10..12], iph_csum);
+        }
+        if protocol as i32 == IPPROTO_UDP {
+            BigEndian::write_u16(&mut ip[iph_len + 4..iph_len + 6], (len - iph_len) as u16);
+        }
+        // Calculate the pseudo header checksum and place it at the transport
+        // checksum offset, to be completed by the kernel or by the split.
+        let addr_len = if is_v6 { 16 } else { 4 };
+        let src_addr_at = if is_v6 { 8 } else { 12 };
+        let psum = pseudo_header_checksum_no_fold(
+            protocol,
+            &ip[src_addr_at..src_addr_at + addr_len],
+            &ip[src_addr_at + addr_len..src_addr_at + addr_len * 2],
+            (len - iph_len) as u16,
+        );
+        let csum_at = iph_len + csum_offset;
+        BigEndian::write_u16(&mut ip[csum_at..csum_at + 2], checksum(&[], psum));
+
+        let info = GsoInfo {
+            gso_type: info.gso_type,
+            gso_size: info.gso_size,
+            hdr_len: (l2_len + hdr_len) as u16,
+            checksum: ChecksumState::Partial {
+                start: (l2_len + iph_len) as u16,
+                offset: csum_offset as u16,
+            },
+            l2_len: l2_len as u16,
+        };
+        let segments = info.segments(packet);
+        let offload = if protocol as i32 == IPPROTO_TCP {
+            self.vnet_hdr
+        } else {
+            self.udp_gso
+        };
+        if offload {
+            let info = if segments > 1 {
+                info
+            } else {
+                GsoInfo {
+                    gso_type: GsoType::None,
+                    gso_size: 0,
+                    hdr_len: 0,
+                    ..info
+                }
+            };
+            let mut hdr = vec![0; vnet_hdr_len];
+            info.to_virtio().encode_with_len(&mut hdr, vnet_hdr_len)?;
+            return Ok(GsoWrite::Offload(hdr));
+        }
+        Ok(GsoWrite::Split(vec![0; vnet_hdr_len], info))
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Sends a TCP or UDP packet of up to 64 KiB as a single GSO super-packet,
+    /// which is split into segments of `info.gso_size` payload bytes.
+    ///
+    /// Only `gso_type` and `gso_size` of `info` are used for super-packets, see [`GsoInfo::new`]:
+    /// the header length, the IP lengths, the IPv4 header checksum and the
+    /// `VIRTIO_NET_HDR_F_NEEDS_CSUM` pseudo-header checksum are filled in from `packet`.
+    /// The kernel segments the packet if the device has TCP or UDP offload
+    /// ([`DeviceImpl::tcp_gso`], [`DeviceImpl::udp_gso`]), otherwise it is split in software.
+    /// On TAP devices `packet` is an Ethernet frame, optionally VLAN tagged.
+    ///
+    /// A packet with [`GsoType::None`] is sent as is, with the virtio-net header from `info`.
+    /// Returns the length of `packet`.
+    pub fn send_gso(&self, packet: &mut [u8], info: GsoInfo) -> io::Result<usize> {
+        match self.prepare_gso(packet, info)? {
+            GsoWrite::Offload(hdr) => {
+                self.send_vectored(&[IoSlice::new(&hdr), IoSlice::new(packet)])?;
+            }
+            GsoWrite::Split(hdr, info) => {
+                for segment in info.iter_segments(packet)? {
+                    let [header, payload] = segment.io_slices();
+                    self.send_vectored(&[IoSlice::new(&hdr), header, payload])?;
+                }
+            }
+        }
+        Ok(packet.len())
+    }
+    /// Receives a packet without splitting GSO super-packets.
+    ///
+    /// With offload enabled, the kernel may deliver TCP or UDP super-packets of up to 64 KiB.
+    /// Unlike [`DeviceImpl::recv_multiple`], which splits them into MTU-sized packets,
+    /// this returns the packet as received together with its [`GsoInfo`], e.g. to forward it
+    /// to a UDP socket with `UDP_SEGMENT`, or to split it later with [`GsoInfo::split`].
+    ///
+    /// `buf` should be at least [`DeviceImpl::vnet_hdr_len`] + 65535 bytes long.
+    /// The returned packet is the part of `buf` after the virtio-net header.
+    /// A packet with [`ChecksumState::Partial`] carries an incomplete transport checksum.
+    pub fn recv_gso<'a>(&self, buf: &'a mut [u8]) -> io::Result<(GsoInfo, &'a mut [u8])> {
+        let len = self.recv(buf)?;
+        self.gso_packet(buf, len)
+    }
+}
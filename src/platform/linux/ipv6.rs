@@ -0,0 +1,166 @@
+use crate::platform::linux::netlink::{Attributes, IfInfoMsg, NetlinkMessage, NetlinkSocket};
+use crate::platform::DeviceImpl;
+use libc::{AF_INET6, AF_UNSPEC, IFLA_AF_SPEC, RTM_GETLINK, RTM_NEWLINK};
+use std::io;
+
+/// https://github.com/torvalds/linux/blob/master/include/uapi/linux/if_link.h
+const IFLA_INET6_ADDR_GEN_MODE: u16 = 8;
+
+/// How the kernel generates the IPv6 link-local address (and SLAAC addresses) of the device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Ipv6AddrGenMode {
+    /// Derived from the hardware address (`IN6_ADDR_GEN_MODE_EUI64`).
+    Eui64,
+    /// No address is generated (`IN6_ADDR_GEN_MODE_NONE`).
+    None,
+    /// RFC 7217 stable privacy addresses (`IN6_ADDR_GEN_MODE_STABLE_PRIVACY`).
+    StablePrivacy,
+    /// A random address (`IN6_ADDR_GEN_MODE_RANDOM`), the default of devices without
+    /// a hardware address such as TUN.
+    Random,
+}
+
+impl Ipv6AddrGenMode {
+    fn from_raw(mode: u8) -> Option<Self> {
+        match mode {
+            0 => Some(Ipv6AddrGenMode::Eui64),
+            1 => Some(Ipv6AddrGenMode::None),
+            2 => Some(Ipv6AddrGenMode::StablePrivacy),
+            3 => Some(Ipv6AddrGenMode::Random),
+            _ => None,
+        }
+    }
+    fn raw(self) -> u8 {
+        match self {
+            Ipv6AddrGenMode::Eui64 => 0,
+            Ipv6AddrGenMode::None => 1,
+            Ipv6AddrGenMode::StablePrivacy => 2,
+            Ipv6AddrGenMode::Random => 3,
+        }
+    }
+}
+
+/// Rejects names that would leave `/proc/sys/net/ipv6/conf/<if>/`.
+fn check_conf_name(name: &str) -> io::Result<()> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid ipv6 conf name {name:?}"),
+        ));
+    }
+    Ok(())
+}
+
+impl DeviceImpl {
+    fn ipv6_conf_path(&self, name: &str) -> io::Result<String> {
+        check_conf_name(name)?;
+        Ok(format!(
+            "/proc/sys/net/ipv6/conf/{}/{name}",
+            self.name_impl()?
+        ))
+    }
+    fn set_ipv6_conf_impl(&self, name: &str, value: &str) -> io::Result<()> {
+        std::fs::write(self.ipv6_conf_path(name)?, value)
+    }
+    fn ipv6_conf_impl(&self, name: &str) -> io::Result<String> {
+        let value = std::fs::read_to_string(self.ipv6_conf_path(name)?)?;
+        Ok(value.trim_end().to_string())
+    }
+    fn ipv6_conf_bool(&self, name: &str) -> io::Result<bool> {
+        match self.ipv6_conf_impl(name)?.as_str() {
+            "0" => Ok(false),
+            _ => Ok(true),
+        }
+    }
+    fn set_ipv6_addr_gen_mode_impl(&self, mode: Ipv6AddrGenMode) -> io::Result<()> {
+        let header = IfInfoMsg {
+            ifi_family: AF_UNSPEC as u8,
+            ifi_index: self.if_index_impl()? as i32,
+            ..Default::default()
+        };
+        let mut msg = NetlinkMessage::new(RTM_NEWLINK, 0, &header);
+        msg.push_nested(IFLA_AF_SPEC, |msg| {
+            msg.push_nested(AF_INET6 as u16, |msg| {
+                msg.push_attr(IFLA_INET6_ADDR_GEN_MODE, &[mode.raw()]);
+            });
+        });
+        NetlinkSocket::new()?.request(msg)
+    }
+    fn ipv6_addr_gen_mode_impl(&self) -> io::Result<Ipv6AddrGenMode> {
+        let header = IfInfoMsg {
+            ifi_index: self.if_index_impl()? as i32,
+            ..Default::default()
+        };
+        let reply = NetlinkSocket::new()?.get(NetlinkMessage::new(RTM_GETLINK, 0, &header))?;
+        reply
+            .attrs::<IfInfoMsg>()
+            .filter(|(ty, _)| *ty == IFLA_AF_SPEC)
+            .flat_map(|(_, data)| Attributes::new(data))
+            .filter(|(ty, _)| *ty == AF_INET6 as u16)
+            .flat_map(|(_, data)| Attributes::new(data))
+            .find(|(ty, _)| *ty == IFLA_INET6_ADDR_GEN_MODE)
+            .and_then(|(_, data)| Ipv6AddrGenMode::from_raw(*data.first()?))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no IPv6 configuration, IPv6 may be disabled on the device",
+                )
+            })
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Sets how the kernel generates IPv6 addresses for the device
+    /// (`IFLA_INET6_ADDR_GEN_MODE`), like `ip link set <dev> addrgenmode <mode>`.
+    ///
+    /// With [`Ipv6AddrGenMode::None`] set before the device is brought up, no `fe80::`
+    /// address is assigned, so the kernel does not send router solicitations or
+    /// MLD reports into the tunnel.
+    pub fn set_ipv6_addr_gen_mode(&self, mode: Ipv6AddrGenMode) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.set_ipv6_addr_gen_mode_impl(mode)
+    }
+    /// Returns how the kernel generates IPv6 addresses for the device.
+    pub fn ipv6_addr_gen_mode(&self) -> io::Result<Ipv6AddrGenMode> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.ipv6_addr_gen_mode_impl()
+    }
+    /// Disables or enables IPv6 on the device (`net.ipv6.conf.<dev>.disable_ipv6`).
+    ///
+    /// Disabling removes all IPv6 addresses of the device.
+    pub fn set_ipv6_disabled(&self, disabled: bool) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.set_ipv6_conf_impl("disable_ipv6", if disabled { "1" } else { "0" })
+    }
+    /// Returns whether IPv6 is disabled on the device.
+    pub fn ipv6_disabled(&self) -> io::Result<bool> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.ipv6_conf_bool("disable_ipv6")
+    }
+    /// Sets whether router advertisements are accepted on the device
+    /// (`net.ipv6.conf.<dev>.accept_ra`).
+    pub fn set_accept_ra(&self, accept: bool) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.set_ipv6_conf_impl("accept_ra", if accept { "1" } else { "0" })
+    }
+    /// Returns whether router advertisements are accepted on the device.
+    pub fn accept_ra(&self) -> io::Result<bool> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.ipv6_conf_bool("accept_ra")
+    }
+    /// Writes the per-interface sysctl `net.ipv6.conf.<dev>.<name>`,
+    /// e.g. `router_solicitations` or `autoconf`.
+    ///
+    /// The sysctls exist only while IPv6 is available on the device,
+    /// which requires an MTU of at least 1280.
+    pub fn set_ipv6_conf(&self, name: &str, value: &str) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.set_ipv6_conf_impl(name, value)
+    }
+    /// Reads the per-interface sysctl `net.ipv6.conf.<dev>.<name>`.
+    pub fn ipv6_conf(&self, name: &str) -> io::Result<String> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.ipv6_conf_impl(name)
+    }
+}
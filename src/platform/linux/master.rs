@@ -0,0 +1,76 @@
+use crate::platform::linux::netlink::{IfInfoMsg, NetlinkMessage, NetlinkSocket};
+use crate::platform::DeviceImpl;
+use libc::{
+    AF_UNSPEC, EEXIST, IFF_UP, IFLA_IFNAME, IFLA_INFO_KIND, IFLA_LINKINFO, IFLA_MASTER,
+    NLM_F_CREATE, NLM_F_EXCL, RTM_NEWLINK,
+};
+use std::ffi::CString;
+use std::io;
+
+fn if_index_by_name(name: &str) -> io::Result<Option<u32>> {
+    let name = CString::new(name)?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Ok(None),
+        index => Ok(Some(index)),
+    }
+}
+
+impl DeviceImpl {
+    /// Creates the bridge `name` and brings it up, unless an interface with that name exists.
+    pub(crate) fn ensure_bridge(name: &str) -> io::Result<()> {
+        if if_index_by_name(name)?.is_some() {
+            return Ok(());
+        }
+        let header = IfInfoMsg {
+            ifi_family: AF_UNSPEC as u8,
+            ifi_flags: IFF_UP as u32,
+            ifi_change: IFF_UP as u32,
+            ..Default::default()
+        };
+        let mut msg = NetlinkMessage::new(RTM_NEWLINK, (NLM_F_CREATE | NLM_F_EXCL) as u16, &header);
+        let mut ifname = name.as_bytes().to_vec();
+        ifname.push(0);
+        msg.push_attr(IFLA_IFNAME, &ifname)
+            .push_nested(IFLA_LINKINFO, |msg| {
+                msg.push_attr(IFLA_INFO_KIND, b"bridge");
+            });
+        match NetlinkSocket::new()?.request(msg) {
+            // Created concurrently by someone else.
+            Err(e) if e.raw_os_error() == Some(EEXIST) => Ok(()),
+            rs => rs,
+        }
+    }
+    fn set_master_index(&self, master: u32) -> io::Result<()> {
+        let header = IfInfoMsg {
+            ifi_family: AF_UNSPEC as u8,
+            ifi_index: self.if_index_impl()? as i32,
+            ..Default::default()
+        };
+        let mut msg = NetlinkMessage::new(RTM_NEWLINK, 0, &header);
+        msg.push_attr_u32(IFLA_MASTER, master);
+        NetlinkSocket::new()?.request(msg)
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Enslaves the device to the bridge or bond `master` (`IFLA_MASTER`),
+    /// like `ip link set <dev> master <master>`.
+    ///
+    /// Bridges only accept Ethernet devices, so the device should be a TAP device (`Layer::L2`).
+    pub fn set_master(&self, master: &str) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        let index = if_index_by_name(master)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("master {master} not found"),
+            )
+        })?;
+        self.set_master_index(index)
+    }
+    /// Releases the device from its bridge or bond, like `ip link set <dev> nomaster`.
+    pub fn clear_master(&self) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.set_master_index(0)
+    }
+}
@@ -1,10 +1,45 @@
 mod sys;
 
+mod address;
+mod capabilities;
 mod checksum;
 mod device;
+mod ethtool;
+#[cfg(any(feature = "async_tokio", feature = "async_io"))]
+pub(crate) mod events;
+mod fd_passing;
+mod filter;
+pub(crate) mod gso;
+mod ipv6;
+mod master;
+pub(crate) mod netlink;
+mod netns;
 pub(crate) mod offload;
+mod route;
+mod rule;
+mod stats;
+mod steering;
+#[cfg(feature = "systemd")]
+mod systemd;
+mod transaction;
+pub use address::{AddressFlags, AddressOptions, AddressScope, InterfaceAddress};
+pub use capabilities::TunCapabilities;
 pub use device::DeviceImpl;
+pub use ethtool::{DeviceFeatures, EthtoolFeature};
+#[cfg(any(feature = "async_tokio", feature = "async_io"))]
+pub use events::LinkEvent;
+pub use fd_passing::FdState;
+pub use filter::PacketFilter;
+pub use gso::{ChecksumState, GsoInfo, GsoSegment, GsoSegments, GsoType};
+pub use ipv6::Ipv6AddrGenMode;
+pub use libc::sock_filter;
+pub use netns::NetNs;
 pub use offload::ExpandBuffer;
 pub use offload::GROTable;
 pub use offload::IDEAL_BATCH_SIZE;
 pub use offload::VIRTIO_NET_HDR_LEN;
+pub use offload::VIRTIO_NET_HDR_MRG_RXBUF_LEN;
+pub use route::Route;
+pub use rule::RoutingRule;
+pub use stats::{InterfaceStats, IoCounters};
+pub use transaction::DeviceConfigTransaction;
@@ -0,0 +1,420 @@
+//! A minimal rtnetlink client used to manage links, addresses and routes.
+//!
+//! https://man7.org/linux/man-pages/man7/rtnetlink.7.html
+use crate::platform::unix::Fd;
+use libc::{
+    c_int, nlmsgerr, nlmsghdr, sockaddr_nl, AF_NETLINK, NETLINK_ROUTE, NLMSG_DONE, NLMSG_ERROR,
+    NLM_F_ACK, NLM_F_DUMP, NLM_F_MULTI, NLM_F_REQUEST, SOCK_CLOEXEC, SOCK_RAW,
+};
+use std::cell::Cell;
+use std::io;
+use std::mem;
+use std::net::IpAddr;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+
+const NLMSG_ALIGNTO: usize = 4;
+const NLA_ALIGNTO: usize = 4;
+const NLA_HDRLEN: usize = 4;
+const NLA_TYPE_MASK: u16 = !(3 << 14);
+const RECV_BUF_SIZE: usize = 32 * 1024;
+
+#[inline]
+const fn nlmsg_align(len: usize) -> usize {
+    (len + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
+}
+#[inline]
+const fn nla_align(len: usize) -> usize {
+    (len + NLA_ALIGNTO - 1) & !(NLA_ALIGNTO - 1)
+}
+
+/// struct rtmsg, defined in include/uapi/linux/rtnetlink.h
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RtMsg {
+    pub(crate) rtm_family: u8,
+    pub(crate) rtm_dst_len: u8,
+    pub(crate) rtm_src_len: u8,
+    pub(crate) rtm_tos: u8,
+    pub(crate) rtm_table: u8,
+    pub(crate) rtm_protocol: u8,
+    pub(crate) rtm_scope: u8,
+    pub(crate) rtm_type: u8,
+    pub(crate) rtm_flags: u32,
+}
+
+/// struct ifinfomsg, defined in include/uapi/linux/rtnetlink.h
+///
+/// `libc::ifinfomsg` has private padding and cannot be built with a struct literal.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct IfInfoMsg {
+    pub(crate) ifi_family: u8,
+    pub(crate) ifi_pad: u8,
+    pub(crate) ifi_type: u16,
+    pub(crate) ifi_index: i32,
+    pub(crate) ifi_flags: u32,
+    pub(crate) ifi_change: u32,
+}
+
+/// A netlink request under construction.
+///
+/// The buffer starts with a `nlmsghdr` followed by the family header `T`,
+/// attributes are appended afterwards.
+pub(crate) struct NetlinkMessage {
+    buf: Vec<u8>,
+}
+
+impl NetlinkMessage {
+    pub(crate) fn new<T: Copy>(ty: u16, flags: u16, header: &T) -> Self {
+        let hdr_len = nlmsg_align(mem::size_of::<nlmsghdr>());
+        let mut buf = vec![0u8; hdr_len + nlmsg_align(mem::size_of::<T>())];
+        let nlh = nlmsghdr {
+            nlmsg_len: 0,
+            nlmsg_type: ty,
+            nlmsg_flags: flags,
+            nlmsg_seq: 0,
+            nlmsg_pid: 0,
+        };
+        unsafe {
+            std::ptr::write_unaligned(buf.as_mut_ptr() as *mut nlmsghdr, nlh);
+            std::ptr::write_unaligned(buf[hdr_len..].as_mut_ptr() as *mut T, *header);
+        }
+        Self { buf }
+    }
+    pub(crate) fn push_attr(&mut self, ty: u16, data: &[u8]) -> &mut Self {
+        let len = NLA_HDRLEN + data.len();
+        self.buf.extend_from_slice(&(len as u16).to_ne_bytes());
+        self.buf.extend_from_slice(&ty.to_ne_bytes());
+        self.buf.extend_from_slice(data);
+        self.buf.resize(nla_align(self.buf.len()), 0);
+        self
+    }
+    /// Appends a nested attribute whose payload is filled in by `f`.
+    pub(crate) fn push_nested<F: FnOnce(&mut Self)>(&mut self, ty: u16, f: F) -> &mut Self {
+        let start = self.buf.len();
+        self.push_attr(ty | libc::NLA_F_NESTED as u16, &[]);
+        f(self);
+        let len = (self.buf.len() - start) as u16;
+        self.buf[start..start + 2].copy_from_slice(&len.to_ne_bytes());
+        self
+    }
+    pub(crate) fn push_attr_u32(&mut self, ty: u16, value: u32) -> &mut Self {
+        self.push_attr(ty, &value.to_ne_bytes())
+    }
+    pub(crate) fn push_attr_ip(&mut self, ty: u16, value: IpAddr) -> &mut Self {
+        match value {
+            IpAddr::V4(ip) => self.push_attr(ty, &ip.octets()),
+            IpAddr::V6(ip) => self.push_attr(ty, &ip.octets()),
+        }
+    }
+    fn finish(&mut self, seq: u32) -> &[u8] {
+        let len = self.buf.len() as u32;
+        let mut nlh: nlmsghdr = unsafe { std::ptr::read_unaligned(self.buf.as_ptr() as *const _) };
+        nlh.nlmsg_len = len;
+        nlh.nlmsg_seq = seq;
+        unsafe { std::ptr::write_unaligned(self.buf.as_mut_ptr() as *mut nlmsghdr, nlh) };
+        &self.buf
+    }
+}
+
+/// A message received from the kernel: the `nlmsg_type` and the payload after `nlmsghdr`.
+pub(crate) struct NetlinkReply {
+    pub(crate) ty: u16,
+    pub(crate) payload: Vec<u8>,
+}
+
+impl NetlinkReply {
+    /// Reads the family header (e.g. `ifinfomsg`) at the start of the payload.
+    pub(crate) fn header<T: Copy>(&self) -> Option<T> {
+        if self.payload.len() < mem::size_of::<T>() {
+            return None;
+        }
+        Some(unsafe { std::ptr::read_unaligned(self.payload.as_ptr() as *const T) })
+    }
+    /// Iterates over the attributes following the family header `T`.
+    pub(crate) fn attrs<T>(&self) -> Attributes<'_> {
+        let start = nlmsg_align(mem::size_of::<T>()).min(self.payload.len());
+        Attributes::new(&self.payload[start..])
+    }
+}
+
+/// Iterator over a sequence of `rtattr`/`nlattr`, yielding `(type, payload)`.
+pub(crate) struct Attributes<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Attributes<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+}
+
+impl<'a> Iterator for Attributes<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.len() < NLA_HDRLEN {
+            return None;
+        }
+        let len = u16::from_ne_bytes([self.buf[0], self.buf[1]]) as usize;
+        let ty = u16::from_ne_bytes([self.buf[2], self.buf[3]]);
+        if len < NLA_HDRLEN || len > self.buf.len() {
+            return None;
+        }
+        let data = &self.buf[NLA_HDRLEN..len];
+        self.buf = &self.buf[nla_align(len).min(self.buf.len())..];
+        Some((ty & NLA_TYPE_MASK, data))
+    }
+}
+
+pub(crate) fn attr_u32(data: &[u8]) -> Option<u32> {
+    Some(u32::from_ne_bytes(data.get(..4)?.try_into().ok()?))
+}
+
+pub(crate) fn attr_ip(data: &[u8]) -> Option<IpAddr> {
+    match data.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(data).ok()?)),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(data).ok()?)),
+        _ => None,
+    }
+}
+
+#[cfg(any(feature = "async_tokio", feature = "async_io"))]
+pub(crate) fn attr_str(data: &[u8]) -> Option<String> {
+    let data = data.split(|&b| b == 0).next()?;
+    String::from_utf8(data.to_vec()).ok()
+}
+
+/// A `NETLINK_ROUTE` socket.
+pub(crate) struct NetlinkSocket {
+    fd: Fd,
+    seq: Cell<u32>,
+}
+
+impl NetlinkSocket {
+    pub(crate) fn new() -> io::Result<Self> {
+        unsafe {
+            let fd = Fd::new(libc::socket(
+                AF_NETLINK,
+                SOCK_RAW | SOCK_CLOEXEC,
+                NETLINK_ROUTE,
+            ))?;
+            let mut addr: sockaddr_nl = mem::zeroed();
+            addr.nl_family = AF_NETLINK as _;
+            if libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const _ as *const libc::sockaddr,
+                mem::size_of::<sockaddr_nl>() as _,
+            ) < 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self {
+                fd,
+                seq: Cell::new(1),
+            })
+        }
+    }
+    /// Creates a non-blocking socket that receives the notifications of the given `RTNLGRP_*` groups.
+    #[cfg(any(feature = "async_tokio", feature = "async_io"))]
+    pub(crate) fn subscribe(groups: &[libc::c_uint]) -> io::Result<Self> {
+        let socket = Self::new()?;
+        for group in groups {
+            let rs = unsafe {
+                libc::setsockopt(
+                    socket.fd.as_raw_fd(),
+                    libc::SOL_NETLINK,
+                    libc::NETLINK_ADD_MEMBERSHIP,
+                    group as *const libc::c_uint as *const _,
+                    mem::size_of::<libc::c_uint>() as _,
+                )
+            };
+            if rs < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        socket.fd.set_nonblocking(true)?;
+        Ok(socket)
+    }
+    fn next_seq(&self) -> u32 {
+        let seq = self.seq.get();
+        self.seq.set(seq.wrapping_add(1));
+        seq
+    }
+    fn send(&self, msg: &mut NetlinkMessage, flags: u16) -> io::Result<u32> {
+        let seq = self.next_seq();
+        let mut nlh: nlmsghdr = unsafe { std::ptr::read_unaligned(msg.buf.as_ptr() as *const _) };
+        nlh.nlmsg_flags |= flags;
+        unsafe { std::ptr::write_unaligned(msg.buf.as_mut_ptr() as *mut nlmsghdr, nlh) };
+        let buf = msg.finish(seq);
+        let mut addr: sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = AF_NETLINK as _;
+        let n = unsafe {
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                buf.as_ptr() as *const _,
+                buf.len(),
+                0,
+                &addr as *const _ as *const libc::sockaddr,
+                mem::size_of::<sockaddr_nl>() as _,
+            )
+        };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(seq)
+    }
+    /// Receives one datagram and splits it into messages.
+    fn recv_messages(&self, buf: &mut [u8]) -> io::Result<Vec<(nlmsghdr, Vec<u8>)>> {
+        let len = self.fd.read(buf)?;
+        let mut out = Vec::new();
+        let hdr_len = mem::size_of::<nlmsghdr>();
+        let mut rest = &buf[..len];
+        while rest.len() >= hdr_len {
+            let nlh: nlmsghdr = unsafe { std::ptr::read_unaligned(rest.as_ptr() as *const _) };
+            let msg_len = nlh.nlmsg_len as usize;
+            if msg_len < hdr_len || msg_len > rest.len() {
+                break;
+            }
+            out.push((nlh, rest[nlmsg_align(hdr_len)..msg_len].to_vec()));
+            rest = &rest[nlmsg_align(msg_len).min(rest.len())..];
+        }
+        Ok(out)
+    }
+    /// Receives the messages of one datagram, such as multicast notifications.
+    #[cfg(any(feature = "async_tokio", feature = "async_io"))]
+    pub(crate) fn recv(&self) -> io::Result<Vec<NetlinkReply>> {
+        let mut buf = vec![0u8; RECV_BUF_SIZE];
+        Ok(self
+            .recv_messages(&mut buf)?
+            .into_iter()
+            .map(|(nlh, payload)| NetlinkReply {
+                ty: nlh.nlmsg_type,
+                payload,
+            })
+            .collect())
+    }
+    /// Sends a request and waits for the kernel acknowledgement.
+    pub(crate) fn request(&self, mut msg: NetlinkMessage) -> io::Result<()> {
+        let seq = self.send(&mut msg, (NLM_F_REQUEST | NLM_F_ACK) as u16)?;
+        let mut buf = vec![0u8; RECV_BUF_SIZE];
+        loop {
+            for (nlh, payload) in self.recv_messages(&mut buf)? {
+                if nlh.nlmsg_seq != seq {
+                    continue;
+                }
+                if nlh.nlmsg_type == NLMSG_ERROR as u16 {
+                    return check_error(&payload);
+                }
+            }
+        }
+    }
+    /// Sends a request for a single object and returns the kernel's reply.
+    pub(crate) fn get(&self, mut msg: NetlinkMessage) -> io::Result<NetlinkReply> {
+        let seq = self.send(&mut msg, NLM_F_REQUEST as u16)?;
+        let mut buf = vec![0u8; RECV_BUF_SIZE];
+        loop {
+            for (nlh, payload) in self.recv_messages(&mut buf)? {
+                if nlh.nlmsg_seq != seq {
+                    continue;
+                }
+                if nlh.nlmsg_type == NLMSG_ERROR as u16 {
+                    check_error(&payload)?;
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "unexpected netlink acknowledgement",
+                    ));
+                }
+                return Ok(NetlinkReply {
+                    ty: nlh.nlmsg_type,
+                    payload,
+                });
+            }
+        }
+    }
+    /// Sends a dump request and collects every reply until `NLMSG_DONE`.
+    pub(crate) fn dump(&self, mut msg: NetlinkMessage) -> io::Result<Vec<NetlinkReply>> {
+        let seq = self.send(&mut msg, (NLM_F_REQUEST | NLM_F_DUMP) as u16)?;
+        let mut buf = vec![0u8; RECV_BUF_SIZE];
+        let mut replies = Vec::new();
+        loop {
+            for (nlh, payload) in self.recv_messages(&mut buf)? {
+                if nlh.nlmsg_seq != seq {
+                    continue;
+                }
+                match nlh.nlmsg_type as c_int {
+                    NLMSG_DONE => return Ok(replies),
+                    NLMSG_ERROR => {
+                        check_error(&payload)?;
+                        return Ok(replies);
+                    }
+                    _ => {
+                        replies.push(NetlinkReply {
+                            ty: nlh.nlmsg_type,
+                            payload,
+                        });
+                        if nlh.nlmsg_flags & NLM_F_MULTI as u16 == 0 {
+                            return Ok(replies);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl AsRawFd for NetlinkSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for NetlinkSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.fd.as_raw_fd()) }
+    }
+}
+
+fn check_error(payload: &[u8]) -> io::Result<()> {
+    if payload.len() < mem::size_of::<c_int>() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "truncated netlink error",
+        ));
+    }
+    let err: nlmsgerr = if payload.len() >= mem::size_of::<nlmsgerr>() {
+        unsafe { std::ptr::read_unaligned(payload.as_ptr() as *const _) }
+    } else {
+        let mut err: nlmsgerr = unsafe { mem::zeroed() };
+        err.error = c_int::from_ne_bytes(payload[..4].try_into().unwrap());
+        err
+    };
+    if err.error == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::from_raw_os_error(-err.error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attr_roundtrip() {
+        let mut msg = NetlinkMessage::new(libc::RTM_NEWROUTE, 0, &RtMsg::default());
+        msg.push_attr_u32(libc::RTA_OIF, 7)
+            .push_attr_ip(libc::RTA_DST, "10.0.0.0".parse().unwrap())
+            .push_attr(libc::RTA_PREFSRC, &[1, 2, 3]);
+        let buf = msg.finish(1).to_vec();
+        let reply = NetlinkReply {
+            ty: libc::RTM_NEWROUTE,
+            payload: buf[mem::size_of::<nlmsghdr>()..].to_vec(),
+        };
+        let attrs: Vec<_> = reply.attrs::<RtMsg>().collect();
+        assert_eq!(attrs.len(), 3);
+        assert_eq!(attr_u32(attrs[0].1), Some(7));
+        assert_eq!(attr_ip(attrs[1].1), Some("10.0.0.0".parse().unwrap()));
+        assert_eq!(attrs[2], (libc::RTA_PREFSRC, &[1u8, 2, 3][..]));
+    }
+}
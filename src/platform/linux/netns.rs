@@ -0,0 +1,118 @@
+use crate::platform::linux::netlink::{IfInfoMsg, NetlinkMessage, NetlinkSocket};
+use crate::platform::unix::Fd;
+use crate::platform::DeviceImpl;
+use libc::{AF_UNSPEC, CLONE_NEWNET, IFLA_NET_NS_FD, O_CLOEXEC, O_RDONLY, RTM_NEWLINK};
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+/// A network namespace, given either as a path (e.g. `/run/netns/tenant1`)
+/// or as an open namespace file descriptor.
+///
+/// A file descriptor is borrowed and must stay open while it is in use.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NetNs {
+    /// A namespace file such as `/run/netns/<name>` or `/proc/<pid>/ns/net`.
+    Path(PathBuf),
+    /// An open namespace file descriptor.
+    Fd(RawFd),
+}
+
+impl From<PathBuf> for NetNs {
+    fn from(value: PathBuf) -> Self {
+        NetNs::Path(value)
+    }
+}
+impl From<&Path> for NetNs {
+    fn from(value: &Path) -> Self {
+        NetNs::Path(value.to_path_buf())
+    }
+}
+impl From<&str> for NetNs {
+    fn from(value: &str) -> Self {
+        NetNs::Path(value.into())
+    }
+}
+impl From<String> for NetNs {
+    fn from(value: String) -> Self {
+        NetNs::Path(value.into())
+    }
+}
+impl From<RawFd> for NetNs {
+    fn from(value: RawFd) -> Self {
+        NetNs::Fd(value)
+    }
+}
+
+impl NetNs {
+    fn open(&self) -> io::Result<Fd> {
+        match self {
+            NetNs::Path(path) => {
+                let path = CString::new(path.as_os_str().as_bytes())?;
+                let fd = unsafe { libc::open(path.as_ptr(), O_RDONLY | O_CLOEXEC) };
+                Fd::new(fd)
+            }
+            NetNs::Fd(fd) => Ok(unsafe { Fd::new_unchecked_with_borrow(*fd, true) }),
+        }
+    }
+    /// Runs `f` on a helper thread that has joined this namespace.
+    ///
+    /// Interface configuration (addresses, MTU, routes, ...) is namespace-scoped,
+    /// so use this to configure a device that lives in another namespace.
+    /// The calling thread's namespace is left untouched.
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, NetNs};
+    /// let netns = NetNs::from("/run/netns/tenant1");
+    /// let dev = DeviceBuilder::new()
+    ///     .netns(netns.clone())
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .build_sync()
+    ///     .unwrap();
+    /// netns.run(|| dev.set_mtu(1400)).unwrap();
+    /// ```
+    pub fn run<F, R>(&self, f: F) -> io::Result<R>
+    where
+        F: FnOnce() -> io::Result<R> + Send,
+        R: Send,
+    {
+        let fd = self.open()?;
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                if unsafe { libc::setns(fd.as_raw_fd(), CLONE_NEWNET) } < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                f()
+            })
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e))
+        })
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Moves the device into another network namespace (`RTM_NEWLINK` with `IFLA_NET_NS_FD`).
+    ///
+    /// The kernel brings the interface down and drops its addresses and routes when it
+    /// changes namespace. Afterwards, configure it from within the target namespace
+    /// using [`NetNs::run`]. New queues added with `try_clone` are opened in the
+    /// target namespace.
+    pub fn move_to_netns<N: Into<NetNs>>(&self, netns: N) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        let netns = netns.into();
+        let fd = netns.open()?;
+        let header = IfInfoMsg {
+            ifi_family: AF_UNSPEC as u8,
+            ifi_index: self.if_index_impl()? as i32,
+            ..Default::default()
+        };
+        let mut msg = NetlinkMessage::new(RTM_NEWLINK, 0, &header);
+        msg.push_attr_u32(IFLA_NET_NS_FD, fd.as_raw_fd() as u32);
+        NetlinkSocket::new()?.request(msg)?;
+        *self.netns.lock().unwrap() = Some(netns);
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 /// https://github.com/WireGuard/wireguard-go/blob/master/tun/offload_linux.go
-use crate::platform::linux::checksum::{checksum, pseudo_header_checksum_no_fold};
+use crate::platform::linux::checksum::{
+    checksum, checksum_no_fold, pseudo_header_checksum_no_fold,
+};
 use byteorder::{BigEndian, ByteOrder};
 use bytes::BytesMut;
 use libc::{IPPROTO_TCP, IPPROTO_UDP};
@@ -20,6 +22,44 @@ pub const IDEAL_BATCH_SIZE: usize = 128;
 
 const TCP_FLAGS_OFFSET: usize = 13;
 
+const ETH_HLEN: usize = 14;
+const VLAN_HLEN: usize = 4;
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86DD;
+const ETH_P_8021Q: u16 = 0x8100;
+const ETH_P_8021AD: u16 = 0x88A8;
+/// The maximum length of the Ethernet header of a TAP frame,
+/// with an 802.1ad and an 802.1Q VLAN tag.
+pub const MAX_L2_HEADER_LEN: usize = ETH_HLEN + 2 * VLAN_HLEN;
+
+/// Returns the length of the Ethernet header of `frame`, including up to two
+/// 802.1Q or 802.1ad VLAN tags, and the EtherType of its payload.
+pub fn ethernet_header_len(frame: &[u8]) -> Option<(usize, u16)> {
+    let mut len = ETH_HLEN;
+    let mut ether_type = BigEndian::read_u16(frame.get(len - 2..len)?);
+    while ether_type == ETH_P_8021Q || ether_type == ETH_P_8021AD {
+        if len == MAX_L2_HEADER_LEN {
+            return None;
+        }
+        len += VLAN_HLEN;
+        ether_type = BigEndian::read_u16(frame.get(len - 2..len)?);
+    }
+    Some((len, ether_type))
+}
+
+/// Returns the offset of the IP header in `packet`: 0 on TUN devices, and the
+/// length of the Ethernet header on TAP devices (`l2`), or `None` if the frame
+/// does not carry IPv4 or IPv6.
+pub fn network_offset(packet: &[u8], l2: bool) -> Option<usize> {
+    if !l2 {
+        return Some(0);
+    }
+    match ethernet_header_len(packet)? {
+        (len, ETH_P_IP | ETH_P_IPV6) => Some(len),
+        _ => None,
+    }
+}
+
 const TCP_FLAG_FIN: u8 = 0x01;
 const TCP_FLAG_PSH: u8 = 0x08;
 const TCP_FLAG_ACK: u8 = 0x10;
@@ -78,15 +118,31 @@ impl VirtioNetHdr {
             Ok(())
         }
     }
+    /// Encodes the header into the first `vnet_hdr_len` bytes of `buf`, zeroing the bytes
+    /// past [`VIRTIO_NET_HDR_LEN`], e.g. `num_buffers` of `virtio_net_hdr_mrg_rxbuf`.
+    pub fn encode_with_len(&self, buf: &mut [u8], vnet_hdr_len: usize) -> io::Result<()> {
+        if buf.len() < vnet_hdr_len {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "too short"));
+        }
+        self.encode(buf)?;
+        buf[VIRTIO_NET_HDR_LEN..vnet_hdr_len].fill(0);
+        Ok(())
+    }
 }
 
 // virtioNetHdrLen is the length in bytes of virtioNetHdr. This matches the
 // shape of the C ABI for its kernel counterpart -- sizeof(virtio_net_hdr).
 pub const VIRTIO_NET_HDR_LEN: usize = std::mem::size_of::<VirtioNetHdr>();
 
+/// The length in bytes of `virtio_net_hdr_mrg_rxbuf`, [`VirtioNetHdr`] followed by
+/// the `num_buffers` field, which TUN leaves zeroed.
+pub const VIRTIO_NET_HDR_MRG_RXBUF_LEN: usize = VIRTIO_NET_HDR_LEN + 2;
+
 /// tcpFlowKey represents the key for a TCP flow.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct TcpFlowKey {
+    l2_header: [u8; MAX_L2_HEADER_LEN], // packets with different Ethernet headers are separate flows.
+    l2_len: u8,
     src_addr: [u8; 16],
     dst_addr: [u8; 16],
     src_port: u16,
@@ -121,8 +177,16 @@ impl TcpGROTable {
 }
 
 impl TcpFlowKey {
-    fn new(pkt: &[u8], src_addr_offset: usize, dst_addr_offset: usize, tcph_offset: usize) -> Self {
+    fn new(
+        l2: &[u8],
+        pkt: &[u8],
+        src_addr_offset: usize,
+        dst_addr_offset: usize,
+        tcph_offset: usize,
+    ) -> Self {
         let mut key = TcpFlowKey {
+            l2_header: [0; MAX_L2_HEADER_LEN],
+            l2_len: l2.len() as u8,
             src_addr: [0; 16],
             dst_addr: [0; 16],
             src_port: 0,
@@ -131,6 +195,7 @@ impl TcpFlowKey {
             is_v6: false,
         };
 
+        key.l2_header[..l2.len()].copy_from_slice(l2);
         let addr_size = dst_addr_offset - src_addr_offset;
         key.src_addr[..addr_size].copy_from_slice(&pkt[src_addr_offset..dst_addr_offset]);
         key.dst_addr[..addr_size]
@@ -147,8 +212,10 @@ impl TcpGROTable {
     /// lookupOrInsert looks up a flow for the provided packet and metadata,
     /// returning the packets found for the flow, or inserting a new one if none
     /// is found.
+    #[allow(clippy::too_many_arguments)]
     fn lookup_or_insert(
         &mut self,
+        l2: &[u8],
         pkt: &[u8],
         src_addr_offset: usize,
         dst_addr_offset: usize,
@@ -156,12 +223,13 @@ impl TcpGROTable {
         tcph_len: usize,
         bufs_index: usize,
     ) -> Option<&mut Vec<TcpGROItem>> {
-        let key = TcpFlowKey::new(pkt, src_addr_offset, dst_addr_offset, tcph_offset);
+        let key = TcpFlowKey::new(l2, pkt, src_addr_offset, dst_addr_offset, tcph_offset);
         if self.items_by_flow.contains_key(&key) {
             return self.items_by_flow.get_mut(&key);
         }
         // Insert the new item into the table
         self.insert(
+            l2,
             pkt,
             src_addr_offset,
             dst_addr_offset,
@@ -172,8 +240,10 @@ impl TcpGROTable {
         None
     }
     /// insert an item in the table for the provided packet and packet metadata.
+    #[allow(clippy::too_many_arguments)]
     fn insert(
         &mut self,
+        l2: &[u8],
         pkt: &[u8],
         src_addr_offset: usize,
         dst_addr_offset: usize,
@@ -181,7 +251,7 @@ impl TcpGROTable {
         tcph_len: usize,
         bufs_index: usize,
     ) {
-        let key = TcpFlowKey::new(pkt, src_addr_offset, dst_addr_offset, tcph_offset);
+        let key = TcpFlowKey::new(l2, pkt, src_addr_offset, dst_addr_offset, tcph_offset);
         let item = TcpGROItem {
             key,
             bufs_index: bufs_index as u16,
@@ -242,11 +312,13 @@ impl TcpGROTable {
 /// udpFlowKey represents the key for a UDP flow.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct UdpFlowKey {
-    src_addr: [u8; 16], // srcAddr
-    dst_addr: [u8; 16], // dstAddr
-    src_port: u16,      // srcPort
-    dst_port: u16,      // dstPort
-    is_v6: bool,        // isV6
+    l2_header: [u8; MAX_L2_HEADER_LEN], // l2Header
+    l2_len: u8,                         // l2Len
+    src_addr: [u8; 16],                 // srcAddr
+    dst_addr: [u8; 16],                 // dstAddr
+    src_port: u16,                      // srcPort
+    dst_port: u16,                      // dstPort
+    is_v6: bool,                        // isV6
 }
 
 ///  udpGROTable holds flow and coalescing information for the purposes of UDP GRO.
@@ -276,18 +348,22 @@ impl UdpGROTable {
 
 impl UdpFlowKey {
     pub fn new(
+        l2: &[u8],
         pkt: &[u8],
         src_addr_offset: usize,
         dst_addr_offset: usize,
         udph_offset: usize,
     ) -> UdpFlowKey {
         let mut key = UdpFlowKey {
+            l2_header: [0; MAX_L2_HEADER_LEN],
+            l2_len: l2.len() as u8,
             src_addr: [0; 16],
             dst_addr: [0; 16],
             src_port: 0,
             dst_port: 0,
             is_v6: false,
         };
+        key.l2_header[..l2.len()].copy_from_slice(l2);
         let addr_size = dst_addr_offset - src_addr_offset;
         key.src_addr[..addr_size].copy_from_slice(&pkt[src_addr_offset..dst_addr_offset]);
         key.dst_addr[..addr_size]
@@ -305,18 +381,20 @@ impl UdpGROTable {
     /// If the flow is not found, inserts a new flow and returns `None` for the items.
     fn lookup_or_insert(
         &mut self,
+        l2: &[u8],
         pkt: &[u8],
         src_addr_offset: usize,
         dst_addr_offset: usize,
         udph_offset: usize,
         bufs_index: usize,
     ) -> Option<&mut Vec<UdpGROItem>> {
-        let key = UdpFlowKey::new(pkt, src_addr_offset, dst_addr_offset, udph_offset);
+        let key = UdpFlowKey::new(l2, pkt, src_addr_offset, dst_addr_offset, udph_offset);
         if self.items_by_flow.contains_key(&key) {
             self.items_by_flow.get_mut(&key)
         } else {
             // If the flow does not exist, insert a new entry.
             self.insert(
+                l2,
                 pkt,
                 src_addr_offset,
                 dst_addr_offset,
@@ -328,8 +406,10 @@ impl UdpGROTable {
         }
     }
     /// Inserts an item in the table for the provided packet and its metadata.
+    #[allow(clippy::too_many_arguments)]
     fn insert(
         &mut self,
+        l2: &[u8],
         pkt: &[u8],
         src_addr_offset: usize,
         dst_addr_offset: usize,
@@ -337,7 +417,7 @@ impl UdpGROTable {
         bufs_index: usize,
         c_sum_known_invalid: bool,
     ) {
-        let key = UdpFlowKey::new(pkt, src_addr_offset, dst_addr_offset, udph_offset);
+        let key = UdpFlowKey::new(l2, pkt, src_addr_offset, dst_addr_offset, udph_offset);
         let item = UdpGROItem {
             key,
             bufs_index: bufs_index as u16,
@@ -577,6 +657,7 @@ fn coalesce_udp_packets<B: ExpandBuffer>(
     bufs: &mut [B],
     bufs_offset: usize,
     is_v6: bool,
+    csum_partial: bool,
 ) -> CoalesceResult {
     let buf = bufs[item.bufs_index as usize].as_ref();
     // let pkt_head = &buf[bufs_offset..]; // the packet that will end up at the front
@@ -588,14 +669,16 @@ fn coalesce_udp_packets<B: ExpandBuffer>(
         return CoalesceResult::InsufficientCap;
     }
 
-    if item.num_merged == 0
+    // Partial checksums are completed after coalescing, there is nothing to validate.
+    if !csum_partial
+        && item.num_merged == 0
         && (item.c_sum_known_invalid
             || !checksum_valid(&buf[bufs_offset..], item.iph_len, IPPROTO_UDP as _, is_v6))
     {
         return CoalesceResult::ItemInvalidCSum;
     }
 
-    if !checksum_valid(pkt, item.iph_len, IPPROTO_UDP as _, is_v6) {
+    if !csum_partial && !checksum_valid(pkt, item.iph_len, IPPROTO_UDP as _, is_v6) {
         return CoalesceResult::PktInvalidCSum;
     }
     bufs[item.bufs_index as usize].buf_extend_from_slice(&pkt[headers_len..]);
@@ -619,6 +702,7 @@ fn coalesce_tcp_packets<B: ExpandBuffer>(
     bufs: &mut [B],
     bufs_offset: usize,
     is_v6: bool,
+    csum_partial: bool,
 ) -> CoalesceResult {
     let pkt_head: &[u8]; // the packet that will end up at the front
     let headers_len = (item.iph_len + item.tcph_len) as usize;
@@ -635,7 +719,8 @@ fn coalesce_tcp_packets<B: ExpandBuffer>(
         if psh_set {
             return CoalesceResult::PSHEnding;
         }
-        if item.num_merged == 0
+        if !csum_partial
+            && item.num_merged == 0
             && !checksum_valid(
                 &bufs[item.bufs_index as usize].as_ref()[bufs_offset..],
                 item.iph_len,
@@ -645,7 +730,7 @@ fn coalesce_tcp_packets<B: ExpandBuffer>(
         {
             return CoalesceResult::ItemInvalidCSum;
         }
-        if !checksum_valid(pkt, item.iph_len, IPPROTO_TCP as _, is_v6) {
+        if !csum_partial && !checksum_valid(pkt, item.iph_len, IPPROTO_TCP as _, is_v6) {
             return CoalesceResult::PktInvalidCSum;
         }
         item.sent_seq = seq;
@@ -667,7 +752,8 @@ fn coalesce_tcp_packets<B: ExpandBuffer>(
             // too small.
             return CoalesceResult::InsufficientCap;
         }
-        if item.num_merged == 0
+        if !csum_partial
+            && item.num_merged == 0
             && !checksum_valid(
                 &bufs[item.bufs_index as usize].as_ref()[bufs_offset..],
                 item.iph_len,
@@ -677,7 +763,7 @@ fn coalesce_tcp_packets<B: ExpandBuffer>(
         {
             return CoalesceResult::ItemInvalidCSum;
         }
-        if !checksum_valid(pkt, item.iph_len, IPPROTO_TCP as _, is_v6) {
+        if !csum_partial && !checksum_valid(pkt, item.iph_len, IPPROTO_TCP as _, is_v6) {
             return CoalesceResult::PktInvalidCSum;
         }
         if psh_set {
@@ -718,14 +804,20 @@ enum GroResult {
 /// existing packets tracked in table. It returns a groResultNoop when no
 /// action was taken, groResultTableInsert when the evaluated packet was
 /// inserted into table, and groResultCoalesced when the evaluated packet was
-/// coalesced with another packet in table.
+/// coalesced with another packet in table. offset is the offset of the IP
+/// header, preceded by l2Len bytes of Ethernet header on TAP devices.
 fn tcp_gro<B: ExpandBuffer>(
     bufs: &mut [B],
     offset: usize,
+    l2_len: usize,
     pkt_i: usize,
     table: &mut TcpGROTable,
     is_v6: bool,
+    csum_partial: bool,
 ) -> GroResult {
+    let mut l2_header = [0; MAX_L2_HEADER_LEN];
+    l2_header[..l2_len].copy_from_slice(&bufs[pkt_i].as_ref()[offset - l2_len..offset]);
+    let l2 = &l2_header[..l2_len];
     let pkt = unsafe { &*(&bufs[pkt_i].as_ref()[offset..] as *const [u8]) };
     if pkt.len() > u16::MAX as usize {
         // A valid IPv4 or IPv6 packet will never exceed this.
@@ -796,6 +888,7 @@ fn tcp_gro<B: ExpandBuffer>(
     }
 
     let items = if let Some(items) = table.lookup_or_insert(
+        l2,
         pkt,
         src_addr_offset,
         src_addr_offset + addr_len,
@@ -834,7 +927,17 @@ fn tcp_gro<B: ExpandBuffer>(
             CanCoalesce::Unavailable => {}
             _ => {
                 let result = coalesce_tcp_packets(
-                    can, pkt, pkt_i, gso_size, seq, psh_set, item, bufs, offset, is_v6,
+                    can,
+                    pkt,
+                    pkt_i,
+                    gso_size,
+                    seq,
+                    psh_set,
+                    item,
+                    bufs,
+                    offset,
+                    is_v6,
+                    csum_partial,
                 );
 
                 match result {
@@ -859,6 +962,7 @@ fn tcp_gro<B: ExpandBuffer>(
 
     // failed to coalesce with any other packets; store the item in the flow
     table.insert(
+        l2,
         pkt,
         src_addr_offset,
         src_addr_offset + addr_len,
@@ -874,39 +978,41 @@ fn tcp_gro<B: ExpandBuffer>(
 pub fn apply_tcp_coalesce_accounting<B: ExpandBuffer>(
     bufs: &mut [B],
     offset: usize,
+    vnet_hdr_len: usize,
     table: &TcpGROTable,
+    csum_partial: bool,
 ) -> io::Result<()> {
     for items in table.items_by_flow.values() {
         for item in items {
+            let l2_len = item.key.l2_len as usize;
             if item.num_merged > 0 {
                 let mut hdr = VirtioNetHdr {
                     flags: VIRTIO_NET_HDR_F_NEEDS_CSUM,
-                    hdr_len: (item.iph_len + item.tcph_len) as u16,
+                    hdr_len: (l2_len + item.iph_len as usize + item.tcph_len as usize) as u16,
                     gso_size: item.gso_size,
-                    csum_start: item.iph_len as u16,
+                    csum_start: (l2_len + item.iph_len as usize) as u16,
                     csum_offset: 16,
                     gso_type: 0, // Will be set later
                 };
                 let buf = bufs[item.bufs_index as usize].as_mut();
-                let pkt = &mut buf[offset..];
+                let pkt = &mut buf[offset + l2_len..];
                 let pkt_len = pkt.len();
 
                 // Calculate the pseudo header checksum and place it at the TCP
                 // checksum offset. Downstream checksum offloading will combine
                 // this with computation of the tcp header and payload checksum.
                 let addr_len = if item.key.is_v6 { 16 } else { 4 };
-                let addr_offset = if item.key.is_v6 {
+                let src_addr_at = if item.key.is_v6 {
                     IPV6_SRC_ADDR_OFFSET
                 } else {
                     IPV4_SRC_ADDR_OFFSET
                 };
-
-                let src_addr_at = offset + addr_offset;
-                let src_addr =
-                    unsafe { &*(&pkt[src_addr_at..src_addr_at + addr_len] as *const [u8]) };
-                let dst_addr = unsafe {
-                    &*(&pkt[src_addr_at + addr_len..src_addr_at + addr_len * 2] as *const [u8])
-                };
+                let psum = pseudo_header_checksum_no_fold(
+                    IPPROTO_TCP as _,
+                    &pkt[src_addr_at..src_addr_at + addr_len],
+                    &pkt[src_addr_at + addr_len..src_addr_at + addr_len * 2],
+                    pkt_len as u16 - item.iph_len as u16,
+                );
                 // Recalculate the total len (IPv4) or payload len (IPv6).
                 // Recalculate the (IPv4) header checksum.
                 if item.key.is_v6 {
@@ -921,26 +1027,15 @@ pub fn apply_tcp_coalesce_accounting<B: ExpandBuffer>(
                     BigEndian::write_u16(&mut pkt[10..12], iph_csum);
                 }
 
-                hdr.encode(&mut buf[offset - VIRTIO_NET_HDR_LEN..])?;
-
-                let pkt = &mut buf[offset..];
-
-                let psum = pseudo_header_checksum_no_fold(
-                    IPPROTO_TCP as _,
-                    src_addr,
-                    dst_addr,
-                    pkt_len as u16 - item.iph_len as u16,
-                );
                 let tcp_csum = checksum(&[], psum);
-                BigEndian::write_u16(
-                    &mut pkt[(hdr.csum_start + hdr.csum_offset) as usize..],
-                    tcp_csum,
-                );
+                let csum_at = item.iph_len as usize + hdr.csum_offset as usize;
+                BigEndian::write_u16(&mut pkt[csum_at..], tcp_csum);
+
+                hdr.encode_with_len(&mut buf[offset - vnet_hdr_len..], vnet_hdr_len)?;
             } else {
-                let hdr = VirtioNetHdr::default();
-                hdr.encode(
-                    &mut bufs[item.bufs_index as usize].as_mut()[offset - VIRTIO_NET_HDR_LEN..],
-                )?;
+                let buf = bufs[item.bufs_index as usize].as_mut();
+                let hdr = uncoalesced_hdr(&mut buf[offset..], l2_len != 0, csum_partial);
+                hdr.encode_with_len(&mut buf[offset - vnet_hdr_len..], vnet_hdr_len)?;
             }
         }
     }
@@ -952,40 +1047,41 @@ pub fn apply_tcp_coalesce_accounting<B: ExpandBuffer>(
 pub fn apply_udp_coalesce_accounting<B: ExpandBuffer>(
     bufs: &mut [B],
     offset: usize,
+    vnet_hdr_len: usize,
     table: &UdpGROTable,
+    csum_partial: bool,
 ) -> io::Result<()> {
     for items in table.items_by_flow.values() {
         for item in items {
+            let l2_len = item.key.l2_len as usize;
             if item.num_merged > 0 {
                 let hdr = VirtioNetHdr {
                     flags: VIRTIO_NET_HDR_F_NEEDS_CSUM, // this turns into CHECKSUM_PARTIAL in the skb
-                    hdr_len: item.iph_len as u16 + UDP_H_LEN as u16,
+                    hdr_len: (l2_len + item.iph_len as usize + UDP_H_LEN) as u16,
                     gso_size: item.gso_size,
-                    csum_start: item.iph_len as u16,
+                    csum_start: (l2_len + item.iph_len as usize) as u16,
                     csum_offset: 6,
                     gso_type: VIRTIO_NET_HDR_GSO_UDP_L4,
                 };
 
                 let buf = bufs[item.bufs_index as usize].as_mut();
-                let pkt = &mut buf[offset..];
+                let pkt = &mut buf[offset + l2_len..];
                 let pkt_len = pkt.len();
 
                 // Calculate the pseudo header checksum and place it at the UDP
                 // checksum offset. Downstream checksum offloading will combine
                 // this with computation of the udp header and payload checksum.
-                let (addr_len, addr_offset) = if item.key.is_v6 {
+                let (addr_len, src_addr_at) = if item.key.is_v6 {
                     (16, IPV6_SRC_ADDR_OFFSET)
                 } else {
                     (4, IPV4_SRC_ADDR_OFFSET)
                 };
-
-                let src_addr_at = offset + addr_offset;
-                let src_addr =
-                    unsafe { &*(&pkt[src_addr_at..(src_addr_at + addr_len)] as *const [u8]) };
-                let dst_addr = unsafe {
-                    &*(&pkt[(src_addr_at + addr_len)..(src_addr_at + addr_len * 2)]
-                        as *const [u8])
-                };
+                let psum = pseudo_header_checksum_no_fold(
+                    IPPROTO_UDP as _,
+                    &pkt[src_addr_at..src_addr_at + addr_len],
+                    &pkt[src_addr_at + addr_len..src_addr_at + addr_len * 2],
+                    pkt_len as u16 - item.iph_len as u16,
+                );
 
                 // Recalculate the total len (IPv4) or payload len (IPv6).
                 // Recalculate the (IPv4) header checksum.
@@ -1000,31 +1096,21 @@ pub fn apply_udp_coalesce_accounting<B: ExpandBuffer>(
                     BigEndian::write_u16(&mut pkt[10..12], iph_csum); // set IPv4 header checksum field
                 }
 
-                hdr.encode(&mut buf[offset - VIRTIO_NET_HDR_LEN..])?;
-                let pkt = &mut buf[offset..];
                 // Recalculate the UDP len field value
                 BigEndian::write_u16(
                     &mut pkt[(item.iph_len as usize + 4)..(item.iph_len as usize + 6)],
                     pkt_len as u16 - item.iph_len as u16,
                 );
 
-                let psum = pseudo_header_checksum_no_fold(
-                    IPPROTO_UDP as _,
-                    src_addr,
-                    dst_addr,
-                    pkt_len as u16 - item.iph_len as u16,
-                );
-
                 let udp_csum = checksum(&[], psum);
-                BigEndian::write_u16(
-                    &mut pkt[(hdr.csum_start + hdr.csum_offset) as usize..],
-                    udp_csum,
-                );
+                let csum_at = item.iph_len as usize + hdr.csum_offset as usize;
+                BigEndian::write_u16(&mut pkt[csum_at..], udp_csum);
+
+                hdr.encode_with_len(&mut buf[offset - vnet_hdr_len..], vnet_hdr_len)?;
             } else {
-                let hdr = VirtioNetHdr::default();
-                hdr.encode(
-                    &mut bufs[item.bufs_index as usize].as_mut()[offset - VIRTIO_NET_HDR_LEN..],
-                )?;
+                let buf = bufs[item.bufs_index as usize].as_mut();
+                let hdr = uncoalesced_hdr(&mut buf[offset..], l2_len != 0, csum_partial);
+                hdr.encode_with_len(&mut buf[offset - vnet_hdr_len..], vnet_hdr_len)?;
             }
         }
     }
@@ -1074,10 +1160,15 @@ const UDP_H_LEN: usize = 8;
 fn udp_gro<B: ExpandBuffer>(
     bufs: &mut [B],
     offset: usize,
+    l2_len: usize,
     pkt_i: usize,
     table: &mut UdpGROTable,
     is_v6: bool,
+    csum_partial: bool,
 ) -> GroResult {
+    let mut l2_header = [0; MAX_L2_HEADER_LEN];
+    l2_header[..l2_len].copy_from_slice(&bufs[pkt_i].as_ref()[offset - l2_len..offset]);
+    let l2 = &l2_header[..l2_len];
     let pkt = unsafe { &*(&bufs[pkt_i].as_ref()[offset..] as *const [u8]) };
     if pkt.len() > u16::MAX as usize {
         // A valid IPv4 or IPv6 packet will never exceed this.
@@ -1119,6 +1210,7 @@ fn udp_gro<B: ExpandBuffer>(
     };
 
     let items = table.lookup_or_insert(
+        l2,
         pkt,
         src_addr_offset,
         src_addr_offset + addr_len,
@@ -1139,7 +1231,7 @@ fn udp_gro<B: ExpandBuffer>(
     let mut pkt_csum_known_invalid = false;
 
     if can == CanCoalesce::Append {
-        match coalesce_udp_packets(pkt, item, bufs, offset, is_v6) {
+        match coalesce_udp_packets(pkt, item, bufs, offset, is_v6, csum_partial) {
             CoalesceResult::Success => {
                 // 前面是引用，这里不需要再更新
                 // table.update_at(*item, items_len - 1);
@@ -1159,6 +1251,7 @@ fn udp_gro<B: ExpandBuffer>(
     let pkt = &bufs[pkt_i].as_ref()[offset..];
     // Failed to coalesce; store the packet in the flow.
     table.insert(
+        l2,
         pkt,
         src_addr_offset,
         src_addr_offset + addr_len,
@@ -1173,36 +1266,82 @@ fn udp_gro<B: ExpandBuffer>(
 /// packets into toWrite. toWrite, tcpTable, and udpTable should initially be
 /// empty (but non-nil), and are passed in to save allocs as the caller may reset
 /// and recycle them across vectors of packets. canUDPGRO indicates if UDP GRO is
-/// supported.
+/// supported. csumPartial indicates that TCP and UDP packets carry no transport
+/// checksum, which is left to the kernel (CHECKSUM_PARTIAL). l2 indicates that
+/// bufs hold Ethernet frames of a TAP device.
+#[allow(clippy::too_many_arguments)]
 pub fn handle_gro<B: ExpandBuffer>(
     bufs: &mut [B],
     offset: usize,
+    vnet_hdr_len: usize,
     tcp_table: &mut TcpGROTable,
     udp_table: &mut UdpGROTable,
     can_udp_gro: bool,
+    csum_partial: bool,
+    l2: bool,
     to_write: &mut Vec<usize>,
 ) -> io::Result<()> {
     let bufs_len = bufs.len();
     for i in 0..bufs_len {
-        if offset < VIRTIO_NET_HDR_LEN || offset > bufs[i].as_ref().len() - 1 {
+        if offset < vnet_hdr_len || offset > bufs[i].as_ref().len() - 1 {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "invalid offset",
             ));
         }
 
-        let result = match packet_is_gro_candidate(&bufs[i].as_ref()[offset..], can_udp_gro) {
-            GroCandidateType::Tcp4GRO => tcp_gro(bufs, offset, i, tcp_table, false),
-            GroCandidateType::Tcp6GRO => tcp_gro(bufs, offset, i, tcp_table, true),
-            GroCandidateType::Udp4GRO => udp_gro(bufs, offset, i, udp_table, false),
-            GroCandidateType::Udp6GRO => udp_gro(bufs, offset, i, udp_table, true),
-            GroCandidateType::NotGRO => GroResult::Noop,
+        let candidate = match network_offset(&bufs[i].as_ref()[offset..], l2) {
+            Some(l2_len) => (
+                l2_len,
+                packet_is_gro_candidate(&bufs[i].as_ref()[offset + l2_len..], can_udp_gro),
+            ),
+            None => (0, GroCandidateType::NotGRO),
+        };
+        let result = match candidate {
+            (l2_len, GroCandidateType::Tcp4GRO) => tcp_gro(
+                bufs,
+                offset + l2_len,
+                l2_len,
+                i,
+                tcp_table,
+                false,
+                csum_partial,
+            ),
+            (l2_len, GroCandidateType::Tcp6GRO) => tcp_gro(
+                bufs,
+                offset + l2_len,
+                l2_len,
+                i,
+                tcp_table,
+                true,
+                csum_partial,
+            ),
+            (l2_len, GroCandidateType::Udp4GRO) => udp_gro(
+                bufs,
+                offset + l2_len,
+                l2_len,
+                i,
+                udp_table,
+                false,
+                csum_partial,
+            ),
+            (l2_len, GroCandidateType::Udp6GRO) => udp_gro(
+                bufs,
+                offset + l2_len,
+                l2_len,
+                i,
+                udp_table,
+                true,
+                csum_partial,
+            ),
+            (_, GroCandidateType::NotGRO) => GroResult::Noop,
         };
 
         match result {
             GroResult::Noop => {
-                let hdr = VirtioNetHdr::default();
-                hdr.encode(&mut bufs[i].as_mut()[offset - VIRTIO_NET_HDR_LEN..offset])?;
+                let buf = bufs[i].as_mut();
+                let hdr = uncoalesced_hdr(&mut buf[offset..], l2, csum_partial);
+                hdr.encode_with_len(&mut buf[offset - vnet_hdr_len..offset], vnet_hdr_len)?;
                 // Fallthrough intended
                 to_write.push(i);
             }
@@ -1213,8 +1352,10 @@ pub fn handle_gro<B: ExpandBuffer>(
         }
     }
 
-    let err_tcp = apply_tcp_coalesce_accounting(bufs, offset, tcp_table);
-    let err_udp = apply_udp_coalesce_accounting(bufs, offset, udp_table);
+    let err_tcp =
+        apply_tcp_coalesce_accounting(bufs, offset, vnet_hdr_len, tcp_table, csum_partial);
+    let err_udp =
+        apply_udp_coalesce_accounting(bufs, offset, vnet_hdr_len, udp_table, csum_partial);
     err_tcp?;
     err_udp?;
     Ok(())
@@ -1229,32 +1370,10 @@ pub fn gso_split<B: AsRef<[u8]> + AsMut<[u8]>>(
     out_bufs: &mut [B],
     sizes: &mut [usize],
     out_offset: usize,
-    is_v6: bool,
+    l2_len: usize,
 ) -> io::Result<usize> {
-    let iph_len = hdr.csum_start as usize;
-    let (src_addr_offset, addr_len) = if is_v6 {
-        (IPV6_SRC_ADDR_OFFSET, 16)
-    } else {
-        input[10] = 0;
-        input[11] = 0; // clear IPv4 header checksum
-        (IPV4_SRC_ADDR_OFFSET, 4)
-    };
-
-    let transport_csum_at = (hdr.csum_start + hdr.csum_offset) as usize;
-    input[transport_csum_at] = 0;
-    input[transport_csum_at + 1] = 0; // clear TCP/UDP checksum
-
-    let (first_tcp_seq_num, protocol) =
-        if hdr.gso_type == VIRTIO_NET_HDR_GSO_TCPV4 || hdr.gso_type == VIRTIO_NET_HDR_GSO_TCPV6 {
-            (
-                BigEndian::read_u32(&input[hdr.csum_start as usize + 4..]),
-                IPPROTO_TCP,
-            )
-        } else {
-            (0, IPPROTO_UDP)
-        };
-
-    let mut next_segment_data_at = hdr.hdr_len as usize;
+    let hdr_len = hdr.hdr_len as usize;
+    let mut next_segment_data_at = hdr_len;
     let mut i = 0;
 
     while next_segment_data_at < input.len() {
@@ -1266,75 +1385,177 @@ pub fn gso_split<B: AsRef<[u8]> + AsMut<[u8]>>(
         if next_segment_end > input.len() {
             next_segment_end = input.len();
         }
-        let segment_data_len = next_segment_end - next_segment_data_at;
-        let total_len = hdr.hdr_len as usize + segment_data_len;
-
+        let payload = &input[next_segment_data_at..next_segment_end];
+        let total_len = hdr_len + payload.len();
+
+        let out_len = out_bufs[i].as_ref().len();
+        if out_len.saturating_sub(out_offset) < total_len {
+            return Err(io::Error::other(format!(
+                "segment len {total_len} overflows bufs element len {out_len}"
+            )));
+        }
         sizes[i] = total_len;
         let out = &mut out_bufs[i].as_mut()[out_offset..];
+        out[hdr_len..total_len].copy_from_slice(payload);
+        gso_segment_header(
+            input,
+            &hdr,
+            i,
+            payload,
+            next_segment_end == input.len(),
+            l2_len,
+            &mut out[..hdr_len],
+        );
 
-        out[..iph_len].copy_from_slice(&input[..iph_len]);
+        next_segment_data_at += hdr.gso_size as usize;
+        i += 1;
+    }
 
-        if !is_v6 {
-            // For IPv4 we are responsible for incrementing the ID field,
-            // updating the total len field, and recalculating the header
-            // checksum.
-            if i > 0 {
-                let id = BigEndian::read_u16(&out[4..]).wrapping_add(i as u16);
-                BigEndian::write_u16(&mut out[4..6], id);
-            }
-            BigEndian::write_u16(&mut out[2..4], total_len as u16);
-            let ipv4_csum = !checksum(&out[..iph_len], 0);
-            BigEndian::write_u16(&mut out[10..12], ipv4_csum);
-        } else {
-            BigEndian::write_u16(&mut out[4..6], (total_len - iph_len) as u16);
+    Ok(i)
+}
+
+/// Writes the headers of segment `i` of the GSO packet `input` into `out`,
+/// for the segment data `payload`. The IP header of `input` follows `l2_len`
+/// bytes of Ethernet header, which are copied as is.
+pub fn gso_segment_header(
+    input: &[u8],
+    hdr: &VirtioNetHdr,
+    i: usize,
+    payload: &[u8],
+    last: bool,
+    l2_len: usize,
+    out: &mut [u8],
+) {
+    // csum_start and hdr_len count from the start of the Ethernet header.
+    let iph_len = hdr.csum_start as usize;
+    let hdr_len = hdr.hdr_len as usize;
+    let total_len = hdr_len + payload.len();
+    out[..hdr_len].copy_from_slice(&input[..hdr_len]);
+
+    let is_v6 = input[l2_len] >> 4 == 6;
+    let (src_addr_offset, addr_len) = if is_v6 {
+        (l2_len + IPV6_SRC_ADDR_OFFSET, 16)
+    } else {
+        (l2_len + IPV4_SRC_ADDR_OFFSET, 4)
+    };
+    let ip = &mut out[l2_len..];
+    if !is_v6 {
+        // For IPv4 we are responsible for incrementing the ID field,
+        // updating the total len field, and recalculating the header
+        // checksum.
+        if i > 0 {
+            let id = BigEndian::read_u16(&ip[4..]).wrapping_add(i as u16);
+            BigEndian::write_u16(&mut ip[4..6], id);
         }
+        BigEndian::write_u16(&mut ip[2..4], (total_len - l2_len) as u16);
+        ip[10] = 0;
+        ip[11] = 0; // clear IPv4 header checksum
+        let ipv4_csum = !checksum(&ip[..iph_len - l2_len], 0);
+        BigEndian::write_u16(&mut ip[10..12], ipv4_csum);
+    } else {
+        BigEndian::write_u16(&mut ip[4..6], (total_len - iph_len) as u16);
+    }
 
-        out[hdr.csum_start as usize..hdr.hdr_len as usize]
-            .copy_from_slice(&input[hdr.csum_start as usize..hdr.hdr_len as usize]);
+    let transport_csum_at = (hdr.csum_start + hdr.csum_offset) as usize;
+    out[transport_csum_at] = 0;
+    out[transport_csum_at + 1] = 0; // clear TCP/UDP checksum
 
-        if protocol == IPPROTO_TCP {
+    let protocol =
+        if hdr.gso_type == VIRTIO_NET_HDR_GSO_TCPV4 || hdr.gso_type == VIRTIO_NET_HDR_GSO_TCPV6 {
+            let first_tcp_seq_num = BigEndian::read_u32(&input[iph_len + 4..]);
             let tcp_seq = first_tcp_seq_num.wrapping_add(hdr.gso_size as u32 * i as u32);
-            BigEndian::write_u32(
-                &mut out[(hdr.csum_start + 4) as usize..(hdr.csum_start + 8) as usize],
-                tcp_seq,
-            );
-            if next_segment_end != input.len() {
-                out[hdr.csum_start as usize + TCP_FLAGS_OFFSET] &= !(TCP_FLAG_FIN | TCP_FLAG_PSH);
+            BigEndian::write_u32(&mut out[iph_len + 4..iph_len + 8], tcp_seq);
+            if !last {
+                out[iph_len + TCP_FLAGS_OFFSET] &= !(TCP_FLAG_FIN | TCP_FLAG_PSH);
             }
+            IPPROTO_TCP
         } else {
-            let udp_len = (segment_data_len + (hdr.hdr_len - hdr.csum_start) as usize) as u16;
-            BigEndian::write_u16(
-                &mut out[(hdr.csum_start + 4) as usize..(hdr.csum_start + 6) as usize],
-                udp_len,
-            );
-        }
+            let udp_len = (payload.len() + hdr_len - iph_len) as u16;
+            BigEndian::write_u16(&mut out[iph_len + 4..iph_len + 6], udp_len);
+            IPPROTO_UDP
+        };
 
-        out[hdr.hdr_len as usize..total_len]
-            .as_mut()
-            .copy_from_slice(&input[next_segment_data_at..next_segment_end]);
-
-        let transport_header_len = (hdr.hdr_len - hdr.csum_start) as usize;
-        let len_for_pseudo = (transport_header_len + segment_data_len) as u16;
-        let transport_csum_no_fold = pseudo_header_checksum_no_fold(
-            protocol as u8,
-            &input[src_addr_offset..src_addr_offset + addr_len],
-            &input[src_addr_offset + addr_len..src_addr_offset + 2 * addr_len],
-            len_for_pseudo,
-        );
-        let transport_csum = !checksum(
-            &out[hdr.csum_start as usize..total_len],
-            transport_csum_no_fold,
-        );
-        BigEndian::write_u16(
-            &mut out[transport_csum_at..transport_csum_at + 2],
-            transport_csum,
-        );
+    let len_for_pseudo = (hdr_len - iph_len + payload.len()) as u16;
+    let transport_csum_no_fold = pseudo_header_checksum_no_fold(
+        protocol as u8,
+        &input[src_addr_offset..src_addr_offset + addr_len],
+        &input[src_addr_offset + addr_len..src_addr_offset + 2 * addr_len],
+        len_for_pseudo,
+    );
+    // The transport header length is even, so the header and the payload
+    // can be summed separately.
+    let transport_csum = !checksum(
+        payload,
+        checksum_no_fold(&out[iph_len..hdr_len], transport_csum_no_fold),
+    );
+    BigEndian::write_u16(
+        &mut out[transport_csum_at..transport_csum_at + 2],
+        transport_csum,
+    );
+}
 
-        next_segment_data_at += hdr.gso_size as usize;
-        i += 1;
+/// Places the pseudo-header checksum at the transport checksum offset of a TCP
+/// or UDP packet, or Ethernet frame (`l2`), and returns the checksum start and offset.
+/// Returns `None` for other packets, IPv4 fragments and IPv6 packets with extension headers.
+pub fn partial_checksum(frame: &mut [u8], l2: bool) -> Option<(u16, u16)> {
+    let l2_len = network_offset(frame, l2)?;
+    let pkt = &mut frame[l2_len..];
+    let (iph_len, protocol, src_addr_at, addr_len) = match pkt.first()? >> 4 {
+        4 if pkt.len() >= 20 => {
+            if pkt[6] & IPV4_FLAG_MORE_FRAGMENTS != 0 || pkt[6] << 3 != 0 || pkt[7] != 0 {
+                return None;
+            }
+            (
+                (pkt[0] & 0x0F) as usize * 4,
+                pkt[9],
+                IPV4_SRC_ADDR_OFFSET,
+                4,
+            )
+        }
+        6 if pkt.len() >= 40 => (40, pkt[6], IPV6_SRC_ADDR_OFFSET, 16),
+        _ => return None,
+    };
+    let (th_len, csum_offset) = match protocol as i32 {
+        IPPROTO_TCP => (20, 16),
+        IPPROTO_UDP => (UDP_H_LEN, 6),
+        _ => return None,
+    };
+    if iph_len < 20 || pkt.len() < iph_len + th_len || pkt.len() > u16::MAX as usize {
+        return None;
+    }
+    let psum = pseudo_header_checksum_no_fold(
+        protocol,
+        &pkt[src_addr_at..src_addr_at + addr_len],
+        &pkt[src_addr_at + addr_len..src_addr_at + addr_len * 2],
+        (pkt.len() - iph_len) as u16,
+    );
+    let csum_at = iph_len + csum_offset;
+    BigEndian::write_u16(&mut pkt[csum_at..csum_at + 2], checksum(&[], psum));
+    Some(((l2_len + iph_len) as u16, csum_offset as u16))
+}
+
+/// Completes the transport checksum of a TCP or UDP packet in software,
+/// for CHECKSUM_PARTIAL packets sent without offload.
+pub fn complete_partial_checksum(frame: &mut [u8], l2: bool) {
+    if let Some((csum_start, csum_offset)) = partial_checksum(frame, l2) {
+        gso_none_checksum(frame, csum_start, csum_offset);
     }
+}
 
-    Ok(i)
+/// The virtio-net header of a packet that was not coalesced.
+fn uncoalesced_hdr(frame: &mut [u8], l2: bool, csum_partial: bool) -> VirtioNetHdr {
+    if !csum_partial {
+        return VirtioNetHdr::default();
+    }
+    match partial_checksum(frame, l2) {
+        Some((csum_start, csum_offset)) => VirtioNetHdr {
+            flags: VIRTIO_NET_HDR_F_NEEDS_CSUM,
+            csum_start,
+            csum_offset,
+            ..Default::default()
+        },
+        None => VirtioNetHdr::default(),
+    }
 }
 
 pub fn gso_none_checksum(in_buf: &mut [u8], csum_start: u16, csum_offset: u16) {
@@ -1354,6 +1575,7 @@ pub struct GROTable {
     pub(crate) to_write: Vec<usize>,
     pub(crate) tcp_gro_table: TcpGROTable,
     pub(crate) udp_gro_table: UdpGROTable,
+    pub(crate) checksum_partial: bool,
 }
 
 impl GROTable {
@@ -1362,8 +1584,24 @@ impl GROTable {
             to_write: Vec::with_capacity(IDEAL_BATCH_SIZE),
             tcp_gro_table: TcpGROTable::new(),
             udp_gro_table: UdpGROTable::new(),
+            checksum_partial: false,
         }
     }
+    /// Marks the TCP and UDP packets of the following `send_multiple` calls as
+    /// CHECKSUM_PARTIAL: their transport checksum field is not read, and the kernel
+    /// completes the checksum from the pseudo-header sum filled in by the crate
+    /// (`VIRTIO_NET_HDR_F_NEEDS_CSUM`), which saves a pass over each payload.
+    ///
+    /// The IPv4 header checksum must still be valid. Other packets, IPv4 fragments
+    /// and IPv6 packets with extension headers are sent as is.
+    /// Without offload the checksums are completed in software.
+    pub fn set_checksum_partial(&mut self, partial: bool) {
+        self.checksum_partial = partial;
+    }
+    /// Returns whether packets are sent as CHECKSUM_PARTIAL.
+    pub fn checksum_partial(&self) -> bool {
+        self.checksum_partial
+    }
     pub(crate) fn reset(&mut self) {
         self.to_write.clear();
         self.tcp_gro_table.reset();
@@ -1391,18 +1629,6 @@ impl ExpandBuffer for BytesMut {
     }
 }
 
-impl ExpandBuffer for &mut BytesMut {
-    fn buf_capacity(&self) -> usize {
-        self.capacity()
-    }
-    fn buf_resize(&mut self, new_len: usize, value: u8) {
-        self.resize(new_len, value)
-    }
-
-    fn buf_extend_from_slice(&mut self, extend: &[u8]) {
-        self.extend_from_slice(extend)
-    }
-}
 impl ExpandBuffer for Vec<u8> {
     fn buf_capacity(&self) -> usize {
         self.capacity()
@@ -1416,16 +1642,16 @@ impl ExpandBuffer for Vec<u8> {
         self.extend_from_slice(extend)
     }
 }
-impl ExpandBuffer for &mut Vec<u8> {
+impl<B: ExpandBuffer + ?Sized> ExpandBuffer for &mut B {
     fn buf_capacity(&self) -> usize {
-        self.capacity()
+        (**self).buf_capacity()
     }
 
     fn buf_resize(&mut self, new_len: usize, value: u8) {
-        self.resize(new_len, value)
+        (**self).buf_resize(new_len, value)
     }
 
     fn buf_extend_from_slice(&mut self, extend: &[u8]) {
-        self.extend_from_slice(extend)
+        (**self).buf_extend_from_slice(extend)
     }
 }
@@ -0,0 +1,211 @@
+use crate::platform::linux::netlink::{attr_ip, attr_u32, NetlinkMessage, NetlinkSocket, RtMsg};
+use crate::platform::DeviceImpl;
+use ipnet::IpNet;
+use libc::{
+    AF_INET, AF_INET6, AF_UNSPEC, NLM_F_CREATE, NLM_F_EXCL, RTA_DST, RTA_OIF, RTA_PREFSRC,
+    RTA_PRIORITY, RTA_TABLE, RTM_DELROUTE, RTM_GETROUTE, RTM_NEWROUTE, RTN_UNICAST, RTPROT_BOOT,
+    RT_SCOPE_LINK, RT_SCOPE_NOWHERE, RT_SCOPE_UNIVERSE, RT_TABLE_MAIN, RT_TABLE_UNSPEC,
+};
+use std::io;
+use std::net::IpAddr;
+
+/// A route that sends traffic for `destination` through the device.
+///
+/// Routes are always scoped to the device's interface index.
+/// `table` defaults to the main routing table and `metric` to the kernel default.
+///
+/// # Example
+/// ```no_run
+/// use tun_rs::{DeviceBuilder, Route};
+/// let dev = DeviceBuilder::new()
+///     .ipv4("10.0.0.1", 24, None)
+///     .route("192.168.10.0/24".parse::<ipnet::IpNet>().unwrap())
+///     .build_sync()
+///     .unwrap();
+/// dev.add_route(
+///     &Route::new("192.168.20.0/24".parse().unwrap())
+///         .metric(10)
+///         .table(100),
+/// )
+/// .unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Route {
+    /// The destination prefix.
+    pub destination: IpNet,
+    /// The route priority (`RTA_PRIORITY`); lower values are preferred.
+    pub metric: Option<u32>,
+    /// The routing table ID. `None` means the main table.
+    pub table: Option<u32>,
+    /// The preferred source address (`RTA_PREFSRC`).
+    pub source: Option<IpAddr>,
+}
+
+impl Route {
+    /// Creates a route to `destination` in the main table.
+    pub fn new(destination: IpNet) -> Self {
+        Self {
+            destination,
+            metric: None,
+            table: None,
+            source: None,
+        }
+    }
+    /// Sets the route metric.
+    pub fn metric(mut self, metric: u32) -> Self {
+        self.metric = Some(metric);
+        self
+    }
+    /// Sets the routing table ID.
+    pub fn table(mut self, table: u32) -> Self {
+        self.table = Some(table);
+        self
+    }
+    /// Sets the preferred source address.
+    pub fn source(mut self, source: IpAddr) -> Self {
+        self.source = Some(source);
+        self
+    }
+}
+
+impl From<IpNet> for Route {
+    fn from(destination: IpNet) -> Self {
+        Route::new(destination)
+    }
+}
+
+fn route_message(ty: u16, flags: u16, route: &Route, if_index: u32) -> NetlinkMessage {
+    let destination = route.destination.trunc();
+    let (family, scope) = match destination {
+        IpNet::V4(_) => (AF_INET, RT_SCOPE_LINK),
+        IpNet::V6(_) => (AF_INET6, RT_SCOPE_UNIVERSE),
+    };
+    let table = route.table.unwrap_or(RT_TABLE_MAIN as u32);
+    let header = RtMsg {
+        rtm_family: family as u8,
+        rtm_dst_len: destination.prefix_len(),
+        rtm_table: if table < 256 {
+            table as u8
+        } else {
+            RT_TABLE_UNSPEC
+        },
+        rtm_protocol: RTPROT_BOOT,
+        rtm_scope: if ty == RTM_DELROUTE {
+            RT_SCOPE_NOWHERE
+        } else {
+            scope
+        },
+        rtm_type: RTN_UNICAST,
+        ..Default::default()
+    };
+    let mut msg = NetlinkMessage::new(ty, flags, &header);
+    if destination.prefix_len() > 0 {
+        msg.push_attr_ip(RTA_DST, destination.addr());
+    }
+    msg.push_attr_u32(RTA_OIF, if_index)
+        .push_attr_u32(RTA_TABLE, table);
+    if let Some(metric) = route.metric {
+        msg.push_attr_u32(RTA_PRIORITY, metric);
+    }
+    if let Some(source) = route.source {
+        msg.push_attr_ip(RTA_PREFSRC, source);
+    }
+    msg
+}
+
+impl DeviceImpl {
+    pub(crate) fn add_route_impl(&self, route: &Route) -> io::Result<()> {
+        let if_index = self.if_index_impl()?;
+        let msg = route_message(
+            RTM_NEWROUTE,
+            (NLM_F_CREATE | NLM_F_EXCL) as u16,
+            route,
+            if_index,
+        );
+        NetlinkSocket::new()?.request(msg)
+    }
+    pub(crate) fn remove_route_impl(&self, route: &Route) -> io::Result<()> {
+        let if_index = self.if_index_impl()?;
+        let msg = route_message(RTM_DELROUTE, 0, route, if_index);
+        NetlinkSocket::new()?.request(msg)
+    }
+    pub(crate) fn routes_impl(&self) -> io::Result<Vec<Route>> {
+        let if_index = self.if_index_impl()?;
+        let header = RtMsg {
+            rtm_family: AF_UNSPEC as u8,
+            ..Default::default()
+        };
+        let replies = NetlinkSocket::new()?.dump(NetlinkMessage::new(RTM_GETROUTE, 0, &header))?;
+        let mut routes = Vec::new();
+        for reply in replies {
+            if reply.ty != RTM_NEWROUTE {
+                continue;
+            }
+            let Some(rtm) = reply.header::<RtMsg>() else {
+                continue;
+            };
+            if rtm.rtm_type != RTN_UNICAST {
+                continue;
+            }
+            let mut oif = None;
+            let mut dst = None;
+            let mut table = rtm.rtm_table as u32;
+            let mut metric = None;
+            let mut source = None;
+            for (ty, data) in reply.attrs::<RtMsg>() {
+                match ty {
+                    RTA_OIF => oif = attr_u32(data),
+                    RTA_DST => dst = attr_ip(data),
+                    RTA_TABLE => table = attr_u32(data).unwrap_or(table),
+                    RTA_PRIORITY => metric = attr_u32(data),
+                    RTA_PREFSRC => source = attr_ip(data),
+                    _ => {}
+                }
+            }
+            if oif != Some(if_index) {
+                continue;
+            }
+            let dst = match (dst, rtm.rtm_family as i32) {
+                (Some(dst), _) => dst,
+                (None, AF_INET) => IpAddr::from([0u8; 4]),
+                (None, AF_INET6) => IpAddr::from([0u8; 16]),
+                _ => continue,
+            };
+            let Ok(destination) = IpNet::new(dst, rtm.rtm_dst_len) else {
+                continue;
+            };
+            routes.push(Route {
+                destination,
+                metric,
+                table: (table != RT_TABLE_MAIN as u32).then_some(table),
+                source,
+            });
+        }
+        Ok(routes)
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Adds a route through this device using rtnetlink.
+    ///
+    /// The device must be up, otherwise the kernel rejects the route.
+    pub fn add_route(&self, route: &Route) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.add_route_impl(route)
+    }
+    /// Removes a route previously added through this device.
+    ///
+    /// `destination`, `table` and `metric` must match the installed route.
+    pub fn remove_route(&self, route: &Route) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.remove_route_impl(route)
+    }
+    /// Lists the unicast routes, in all tables, whose output interface is this device.
+    ///
+    /// Routes in the main table are reported with `table: None`.
+    pub fn routes(&self) -> io::Result<Vec<Route>> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.routes_impl()
+    }
+}
@@ -0,0 +1,279 @@
+use crate::platform::linux::netlink::{NetlinkMessage, NetlinkSocket};
+use crate::platform::DeviceImpl;
+use ipnet::IpNet;
+use libc::{AF_INET, AF_INET6, NLM_F_CREATE, NLM_F_EXCL, RTM_DELRULE, RTM_NEWRULE};
+use std::io;
+
+/// https://github.com/torvalds/linux/blob/master/include/uapi/linux/fib_rules.h
+const FRA_DST: u16 = 1;
+const FRA_SRC: u16 = 2;
+const FRA_PRIORITY: u16 = 6;
+const FRA_FWMARK: u16 = 10;
+const FRA_SUPPRESS_PREFIXLEN: u16 = 14;
+const FRA_TABLE: u16 = 15;
+const FRA_FWMASK: u16 = 16;
+const FRA_UID_RANGE: u16 = 20;
+const FR_ACT_TO_TBL: u8 = 1;
+const FIB_RULE_INVERT: u32 = 0x2;
+
+/// struct fib_rule_hdr
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct FibRuleHdr {
+    family: u8,
+    dst_len: u8,
+    src_len: u8,
+    tos: u8,
+    table: u8,
+    res1: u8,
+    res2: u8,
+    action: u8,
+    flags: u32,
+}
+
+/// A policy routing rule (`ip rule`) that directs matching traffic to a routing table.
+///
+/// # Example
+/// A split tunnel where everything not marked with `0xca6c` uses table `51820`,
+/// while more specific routes in the main table keep working:
+/// ```no_run
+/// use tun_rs::{DeviceBuilder, Route, RoutingRule};
+/// let dev = DeviceBuilder::new()
+///     .ipv4("10.0.0.1", 24, None)
+///     .route(Route::new("0.0.0.0/0".parse().unwrap()).table(51820))
+///     .build_sync()
+///     .unwrap();
+/// dev.add_rules([
+///     RoutingRule::ipv4(51820).fwmark(0xca6c).invert(true),
+///     RoutingRule::ipv4(libc::RT_TABLE_MAIN as u32).suppress_prefixlength(0),
+/// ])
+/// .unwrap();
+/// // the rules are removed when `dev` is dropped
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RoutingRule {
+    /// Whether the rule belongs to the IPv6 rule set.
+    pub ipv6: bool,
+    /// The routing table to look up.
+    pub table: u32,
+    /// The rule priority; `None` lets the kernel pick one.
+    pub priority: Option<u32>,
+    /// Matches packets carrying this firewall mark.
+    pub fwmark: Option<u32>,
+    /// The mask applied to the firewall mark before matching.
+    pub fwmask: Option<u32>,
+    /// Matches packets from this source prefix.
+    pub source: Option<IpNet>,
+    /// Matches packets to this destination prefix.
+    pub destination: Option<IpNet>,
+    /// Matches sockets owned by a UID in this inclusive range.
+    pub uid_range: Option<(u32, u32)>,
+    /// Rejects routing decisions whose prefix length is less than or equal to this value.
+    pub suppress_prefixlength: Option<u32>,
+    /// Inverts the selector (`ip rule add not ...`).
+    pub invert: bool,
+}
+
+impl RoutingRule {
+    /// Creates an IPv4 rule that looks up `table`.
+    pub fn ipv4(table: u32) -> Self {
+        Self::new(false, table)
+    }
+    /// Creates an IPv6 rule that looks up `table`.
+    pub fn ipv6(table: u32) -> Self {
+        Self::new(true, table)
+    }
+    fn new(ipv6: bool, table: u32) -> Self {
+        Self {
+            ipv6,
+            table,
+            priority: None,
+            fwmark: None,
+            fwmask: None,
+            source: None,
+            destination: None,
+            uid_range: None,
+            suppress_prefixlength: None,
+            invert: false,
+        }
+    }
+    /// Sets the rule priority.
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+    /// Matches on the firewall mark.
+    pub fn fwmark(mut self, fwmark: u32) -> Self {
+        self.fwmark = Some(fwmark);
+        self
+    }
+    /// Sets the mask applied to the firewall mark.
+    pub fn fwmask(mut self, fwmask: u32) -> Self {
+        self.fwmask = Some(fwmask);
+        self
+    }
+    /// Matches on the source prefix.
+    pub fn source(mut self, source: IpNet) -> Self {
+        self.source = Some(source);
+        self
+    }
+    /// Matches on the destination prefix.
+    pub fn destination(mut self, destination: IpNet) -> Self {
+        self.destination = Some(destination);
+        self
+    }
+    /// Matches on the UID range `start..=end`.
+    pub fn uid_range(mut self, start: u32, end: u32) -> Self {
+        self.uid_range = Some((start, end));
+        self
+    }
+    /// Sets `suppress_prefixlength`.
+    pub fn suppress_prefixlength(mut self, len: u32) -> Self {
+        self.suppress_prefixlength = Some(len);
+        self
+    }
+    /// Inverts the selector.
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+
+    fn message(&self, ty: u16, flags: u16) -> io::Result<NetlinkMessage> {
+        for net in [self.source, self.destination].into_iter().flatten() {
+            if net.addr().is_ipv6() != self.ipv6 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "rule prefix does not match the rule family",
+                ));
+            }
+        }
+        let header = FibRuleHdr {
+            family: if self.ipv6 { AF_INET6 } else { AF_INET } as u8,
+            dst_len: self.destination.map_or(0, |v| v.prefix_len()),
+            src_len: self.source.map_or(0, |v| v.prefix_len()),
+            table: if self.table < 256 {
+                self.table as u8
+            } else {
+                0
+            },
+            action: FR_ACT_TO_TBL,
+            flags: if self.invert { FIB_RULE_INVERT } else { 0 },
+            ..Default::default()
+        };
+        let mut msg = NetlinkMessage::new(ty, flags, &header);
+        msg.push_attr_u32(FRA_TABLE, self.table);
+        if let Some(priority) = self.priority {
+            msg.push_attr_u32(FRA_PRIORITY, priority);
+        }
+        if let Some(fwmark) = self.fwmark {
+            msg.push_attr_u32(FRA_FWMARK, fwmark);
+        }
+        if let Some(fwmask) = self.fwmask {
+            msg.push_attr_u32(FRA_FWMASK, fwmask);
+        }
+        if let Some(source) = self.source {
+            msg.push_attr_ip(FRA_SRC, source.trunc().addr());
+        }
+        if let Some(destination) = self.destination {
+            msg.push_attr_ip(FRA_DST, destination.trunc().addr());
+        }
+        if let Some((start, end)) = self.uid_range {
+            let mut range = [0u8; 8];
+            range[..4].copy_from_slice(&start.to_ne_bytes());
+            range[4..].copy_from_slice(&end.to_ne_bytes());
+            msg.push_attr(FRA_UID_RANGE, &range);
+        }
+        if let Some(len) = self.suppress_prefixlength {
+            msg.push_attr_u32(FRA_SUPPRESS_PREFIXLEN, len);
+        }
+        Ok(msg)
+    }
+    fn add(&self, socket: &NetlinkSocket) -> io::Result<()> {
+        let msg = self.message(RTM_NEWRULE, (NLM_F_CREATE | NLM_F_EXCL) as u16)?;
+        socket.request(msg)
+    }
+    fn remove(&self, socket: &NetlinkSocket) -> io::Result<()> {
+        let msg = self.message(RTM_DELRULE, 0)?;
+        socket.request(msg)
+    }
+}
+
+/// The rules added through a device, removed when its last queue is dropped.
+#[derive(Default)]
+pub(crate) struct DeviceRules {
+    /// Each rule with a socket in the namespace it was added in.
+    rules: Vec<(RoutingRule, NetlinkSocket)>,
+}
+
+impl Drop for DeviceRules {
+    fn drop(&mut self) {
+        for (rule, socket) in self.rules.iter().rev() {
+            if let Err(e) = rule.remove(socket) {
+                log::warn!("remove rule {rule:?},{e}");
+            }
+        }
+    }
+}
+
+impl DeviceImpl {
+    pub(crate) fn add_rules_impl<I: IntoIterator<Item = RoutingRule>>(
+        &self,
+        rules: I,
+    ) -> io::Result<()> {
+        let mut added = DeviceRules::default();
+        for rule in rules {
+            let socket = NetlinkSocket::new()?;
+            rule.add(&socket)?;
+            added.rules.push((rule, socket));
+        }
+        let mut owned = self.rules.lock().unwrap();
+        owned.rules.append(&mut added.rules);
+        Ok(())
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Adds a policy routing rule in the namespace of the calling thread.
+    ///
+    /// The rule is owned by the device and removed when its last queue is dropped,
+    /// like routes installed with `associate_route` on macOS and BSD.
+    pub fn add_rule(&self, rule: &RoutingRule) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.add_rules_impl([rule.clone()])
+    }
+    /// Adds the given rules, see [`DeviceImpl::add_rule`].
+    ///
+    /// If any rule cannot be added, the rules added so far are removed again.
+    pub fn add_rules<I: IntoIterator<Item = RoutingRule>>(&self, rules: I) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.add_rules_impl(rules)
+    }
+    /// Removes a policy routing rule. All selectors must match the installed rule.
+    ///
+    /// A rule owned by the device is removed in the namespace it was added in.
+    pub fn remove_rule(&self, rule: &RoutingRule) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        let mut owned = self.rules.lock().unwrap();
+        match owned.rules.iter().position(|(v, _)| v == rule) {
+            Some(index) => {
+                owned.rules[index].0.remove(&owned.rules[index].1)?;
+                owned.rules.remove(index);
+                Ok(())
+            }
+            None => rule.remove(&NetlinkSocket::new()?),
+        }
+    }
+    /// Returns the rules owned by the device.
+    pub fn rules(&self) -> Vec<RoutingRule> {
+        let _guard = self.op_lock.lock().unwrap();
+        let owned = self.rules.lock().unwrap();
+        owned.rules.iter().map(|(rule, _)| rule.clone()).collect()
+    }
+    /// Keeps the rules owned by the device installed after it is dropped and returns them.
+    pub fn release_rules(&self) -> Vec<RoutingRule> {
+        let _guard = self.op_lock.lock().unwrap();
+        let mut owned = self.rules.lock().unwrap();
+        owned.rules.drain(..).map(|(rule, _)| rule).collect()
+    }
+}
@@ -0,0 +1,191 @@
+use crate::platform::linux::netlink::{IfInfoMsg, NetlinkMessage, NetlinkSocket};
+use crate::platform::DeviceImpl;
+use libc::{IFLA_STATS, IFLA_STATS64, RTM_GETLINK};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Kernel counters of a network interface.
+///
+/// These count everything that crossed the interface, including packets
+/// that were dropped before reaching userspace.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct InterfaceStats {
+    /// Packets received by the kernel, i.e. written to the device by the application.
+    pub rx_packets: u64,
+    /// Packets transmitted by the kernel, i.e. queued for the application to read.
+    pub tx_packets: u64,
+    /// Bytes of `rx_packets`, excluding the virtio-net header.
+    pub rx_bytes: u64,
+    /// Bytes of `tx_packets`, excluding the virtio-net header.
+    pub tx_bytes: u64,
+    /// Receive errors reported by the driver.
+    pub rx_errors: u64,
+    /// Transmit errors reported by the driver.
+    pub tx_errors: u64,
+    /// Packets written by the application that the kernel dropped.
+    pub rx_dropped: u64,
+    /// Packets the kernel dropped instead of queueing them for the application,
+    /// e.g. because the queue was full or no queue was attached.
+    pub tx_dropped: u64,
+    /// Multicast packets received. There is no transmit counterpart.
+    pub multicast: u64,
+}
+
+impl InterfaceStats {
+    /// Parses the leading fields of `struct rtnl_link_stats64`,
+    /// which share the order of [`InterfaceStats`].
+    fn from_stats64(data: &[u8]) -> Option<Self> {
+        Self::from_fields(
+            data.chunks_exact(8)
+                .map(|v| u64::from_ne_bytes(v.try_into().unwrap())),
+        )
+    }
+    /// Parses the leading fields of the 32-bit `struct rtnl_link_stats`.
+    fn from_stats(data: &[u8]) -> Option<Self> {
+        Self::from_fields(
+            data.chunks_exact(4)
+                .map(|v| u32::from_ne_bytes(v.try_into().unwrap()) as u64),
+        )
+    }
+    fn from_fields(mut fields: impl Iterator<Item = u64>) -> Option<Self> {
+        let mut next = || fields.next();
+        Some(Self {
+            rx_packets: next()?,
+            tx_packets: next()?,
+            rx_bytes: next()?,
+            tx_bytes: next()?,
+            rx_errors: next()?,
+            tx_errors: next()?,
+            rx_dropped: next()?,
+            tx_dropped: next()?,
+            multicast: next()?,
+        })
+    }
+}
+
+/// Packets and bytes read and written by this process, see [`DeviceImpl::io_counters`].
+///
+/// Each successful read or write counts as one packet, so a GSO packet
+/// received with offload enabled is counted once, including its virtio-net header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IoCounters {
+    /// Successful reads from the device.
+    pub rx_packets: u64,
+    /// Bytes read from the device, including the virtio-net header.
+    pub rx_bytes: u64,
+    /// Successful writes to the device.
+    pub tx_packets: u64,
+    /// Bytes written to the device, including the virtio-net header.
+    pub tx_bytes: u64,
+}
+
+/// Counters shared by all queues of a device.
+#[derive(Default, Debug)]
+pub(crate) struct AtomicIoCounters {
+    rx_packets: AtomicU64,
+    rx_bytes: AtomicU64,
+    tx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+}
+
+impl AtomicIoCounters {
+    fn snapshot(&self) -> IoCounters {
+        IoCounters {
+            rx_packets: self.rx_packets.load(Ordering::Relaxed),
+            rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+            tx_packets: self.tx_packets.load(Ordering::Relaxed),
+            tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl DeviceImpl {
+    #[inline]
+    pub(crate) fn count_rx(&self, len: usize) {
+        if let Some(counters) = &self.io_counters {
+            counters.rx_packets.fetch_add(1, Ordering::Relaxed);
+            counters.rx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+        }
+    }
+    #[inline]
+    pub(crate) fn count_tx(&self, len: usize) {
+        if let Some(counters) = &self.io_counters {
+            counters.tx_packets.fetch_add(1, Ordering::Relaxed);
+            counters.tx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+        }
+    }
+    /// Reads `IFLA_STATS64`, or the 32-bit `IFLA_STATS` if it is missing.
+    fn statistics_netlink(&self) -> io::Result<InterfaceStats> {
+        let hdr = IfInfoMsg {
+            ifi_index: self.if_index_impl()? as i32,
+            ..Default::default()
+        };
+        let msg = NetlinkMessage::new(RTM_GETLINK, 0, &hdr);
+        let reply = NetlinkSocket::new()?.get(msg)?;
+        let mut stats = None;
+        for (ty, data) in reply.attrs::<IfInfoMsg>() {
+            match ty {
+                IFLA_STATS64 => {
+                    return InterfaceStats::from_stats64(data).ok_or_else(missing_stats)
+                }
+                IFLA_STATS => stats = InterfaceStats::from_stats(data),
+                _ => {}
+            }
+        }
+        stats.ok_or_else(missing_stats)
+    }
+    fn statistics_sysfs(&self) -> io::Result<InterfaceStats> {
+        let dir = format!("/sys/class/net/{}/statistics", self.name_impl()?);
+        let read = |field: &str| -> io::Result<u64> {
+            std::fs::read_to_string(format!("{dir}/{field}"))?
+                .trim()
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        };
+        Ok(InterfaceStats {
+            rx_packets: read("rx_packets")?,
+            tx_packets: read("tx_packets")?,
+            rx_bytes: read("rx_bytes")?,
+            tx_bytes: read("tx_bytes")?,
+            rx_errors: read("rx_errors")?,
+            tx_errors: read("tx_errors")?,
+            rx_dropped: read("rx_dropped")?,
+            tx_dropped: read("tx_dropped")?,
+            multicast: read("multicast")?,
+        })
+    }
+}
+
+fn missing_stats() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "missing IFLA_STATS64 and IFLA_STATS",
+    )
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Returns the kernel counters of the interface.
+    ///
+    /// They are read from `IFLA_STATS64` over rtnetlink, falling back to the 32-bit
+    /// `IFLA_STATS`, so they follow the network namespace of the calling thread.
+    /// If the rtnetlink request fails, they are read from `/sys/class/net/<name>/statistics`,
+    /// which reflects the namespace sysfs was mounted in instead.
+    ///
+    /// From the kernel's point of view, `tx` is what the kernel wrote to this device
+    /// (what the application receives) and `rx` is what the application sent.
+    pub fn statistics(&self) -> io::Result<InterfaceStats> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.statistics_netlink()
+            .or_else(|_| self.statistics_sysfs())
+    }
+    /// Returns the packets and bytes this process has read from and written to the device,
+    /// summed over all queues created with `try_clone`.
+    ///
+    /// Returns `None` unless counting was enabled with `DeviceBuilder::io_counters`.
+    pub fn io_counters(&self) -> Option<IoCounters> {
+        self.io_counters
+            .as_ref()
+            .map(|counters| counters.snapshot())
+    }
+}
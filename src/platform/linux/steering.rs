@@ -0,0 +1,178 @@
+use crate::platform::linux::sys::{tunsetfilterebpf, tunsetsteeringebpf};
+use crate::platform::unix::Fd;
+use crate::platform::DeviceImpl;
+use libc::{c_int, c_short, IFF_TAP};
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+/// https://github.com/torvalds/linux/blob/master/include/uapi/linux/bpf.h
+const BPF_PROG_LOAD: c_int = 5;
+const BPF_PROG_TYPE_SOCKET_FILTER: u32 = 1;
+
+const BPF_LD_ABS_B: u8 = 0x30;
+const BPF_LD_ABS_H: u8 = 0x28;
+const BPF_LD_ABS_W: u8 = 0x20;
+const BPF_MOV64_X: u8 = 0xbf;
+const BPF_MOV64_K: u8 = 0xb7;
+const BPF_XOR64_X: u8 = 0xaf;
+const BPF_RSH64_K: u8 = 0x77;
+const BPF_JEQ_K: u8 = 0x15;
+const BPF_JA: u8 = 0x05;
+const BPF_EXIT: u8 = 0x95;
+
+/// struct bpf_insn
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BpfInsn {
+    code: u8,
+    regs: u8,
+    off: i16,
+    imm: i32,
+}
+
+const fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> BpfInsn {
+    let regs = if cfg!(target_endian = "little") {
+        dst | (src << 4)
+    } else {
+        (dst << 4) | src
+    };
+    BpfInsn {
+        code,
+        regs,
+        off,
+        imm,
+    }
+}
+
+/// The `BPF_PROG_LOAD` part of union bpf_attr
+#[repr(C)]
+#[derive(Default)]
+struct BpfProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+}
+
+fn load_socket_filter(insns: &[BpfInsn]) -> io::Result<Fd> {
+    let attr = BpfProgLoadAttr {
+        prog_type: BPF_PROG_TYPE_SOCKET_FILTER,
+        insn_cnt: insns.len() as u32,
+        insns: insns.as_ptr() as u64,
+        license: c"Apache-2.0".as_ptr() as u64,
+        ..Default::default()
+    };
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            BPF_PROG_LOAD,
+            &attr as *const BpfProgLoadAttr,
+            std::mem::size_of::<BpfProgLoadAttr>(),
+        )
+    };
+    Fd::new(fd as RawFd)
+}
+
+/// Returns a steering program that hashes the destination address.
+///
+/// Only legacy packet loads (`LD_ABS`) are used, as in classic BPF.
+/// The kernel picks queue `hash % queues`; packets it cannot parse go to queue 0.
+fn destination_hash_program(tap: bool) -> Vec<BpfInsn> {
+    let l3 = if tap { 14 } else { 0 };
+    // r6 = skb, required by LD_ABS
+    let mut prog = vec![insn(BPF_MOV64_X, 6, 1, 0, 0)];
+    if tap {
+        prog.extend([
+            insn(BPF_LD_ABS_H, 0, 0, 0, 12),
+            insn(BPF_JEQ_K, 0, 0, 3, libc::ETH_P_IP),
+            insn(BPF_JEQ_K, 0, 0, 2, libc::ETH_P_IPV6),
+            insn(BPF_MOV64_K, 0, 0, 0, 0),
+            insn(BPF_EXIT, 0, 0, 0, 0),
+        ]);
+    }
+    prog.extend([
+        // r0 = ip version
+        insn(BPF_LD_ABS_B, 0, 0, 0, l3),
+        insn(BPF_RSH64_K, 0, 0, 0, 4),
+        insn(BPF_JEQ_K, 0, 0, 2, 6),
+        // IPv4: r0 = daddr
+        insn(BPF_LD_ABS_W, 0, 0, 0, l3 + 16),
+        insn(BPF_JA, 0, 0, 8, 0),
+        // IPv6: r0 = daddr[0] ^ daddr[1] ^ daddr[2] ^ daddr[3]
+        insn(BPF_LD_ABS_W, 0, 0, 0, l3 + 24),
+        insn(BPF_MOV64_X, 7, 0, 0, 0),
+        insn(BPF_LD_ABS_W, 0, 0, 0, l3 + 28),
+        insn(BPF_XOR64_X, 7, 0, 0, 0),
+        insn(BPF_LD_ABS_W, 0, 0, 0, l3 + 32),
+        insn(BPF_XOR64_X, 7, 0, 0, 0),
+        insn(BPF_LD_ABS_W, 0, 0, 0, l3 + 36),
+        insn(BPF_XOR64_X, 0, 7, 0, 0),
+        // r0 ^= r0 >> 16; r0 ^= r0 >> 8
+        insn(BPF_MOV64_X, 1, 0, 0, 0),
+        insn(BPF_RSH64_K, 1, 0, 0, 16),
+        insn(BPF_XOR64_X, 0, 1, 0, 0),
+        insn(BPF_MOV64_X, 1, 0, 0, 0),
+        insn(BPF_RSH64_K, 1, 0, 0, 8),
+        insn(BPF_XOR64_X, 0, 1, 0, 0),
+        insn(BPF_EXIT, 0, 0, 0, 0),
+    ]);
+    prog
+}
+
+impl DeviceImpl {
+    fn set_ebpf(
+        &self,
+        prog: Option<RawFd>,
+        f: unsafe fn(c_int, *mut c_int) -> nix::Result<c_int>,
+    ) -> io::Result<()> {
+        let mut fd = prog.unwrap_or(-1);
+        unsafe {
+            if let Err(err) = f(self.as_raw_fd(), &mut fd) {
+                return Err(io::Error::from(err));
+            }
+        }
+        Ok(())
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Sets the eBPF program (`TUNSETSTEERINGEBPF`) that selects the queue of each packet
+    /// sent to userspace, replacing the kernel's default flow hash.
+    ///
+    /// `prog` is the fd of a loaded `BPF_PROG_TYPE_SOCKET_FILTER` program whose return value,
+    /// modulo the number of queues, is the queue index. `None` restores the default.
+    /// The program applies to all queues of the device; the fd can be closed afterwards.
+    pub fn set_steering_ebpf(&self, prog: Option<RawFd>) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.set_ebpf(prog, tunsetsteeringebpf)
+    }
+    /// Sets the eBPF program (`TUNSETFILTEREBPF`) that filters packets sent to userspace.
+    ///
+    /// `prog` is the fd of a loaded `BPF_PROG_TYPE_SOCKET_FILTER` program whose return value
+    /// is the number of bytes to keep: packets for which it returns 0 are dropped, and a
+    /// smaller value than the packet length truncates the packet to that many bytes.
+    /// Return the packet length (`skb->len`) or `u32::MAX` to pass a packet unchanged.
+    /// `None` removes the program.
+    /// Unlike [`DeviceImpl::attach_filter`], this also works on TUN devices.
+    pub fn set_filter_ebpf(&self, prog: Option<RawFd>) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        self.set_ebpf(prog, tunsetfilterebpf)
+    }
+    /// Steers packets to queues by a hash of their destination address.
+    ///
+    /// This is a built-in alternative to [`DeviceImpl::set_steering_ebpf`] for per-peer workers:
+    /// all packets to the same destination arrive on the same queue.
+    /// It loads a small generated steering program and requires `CAP_BPF` or `CAP_SYS_ADMIN`.
+    pub fn steer_by_destination(&self) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        let tap = self.flags & IFF_TAP as c_short == IFF_TAP as c_short;
+        let prog = load_socket_filter(&destination_hash_program(tap))?;
+        self.set_ebpf(Some(prog.as_raw_fd()), tunsetsteeringebpf)
+    }
+}
@@ -1,5 +1,8 @@
-use libc::{c_int, ifreq, in6_ifreq};
-use nix::{ioctl_read, ioctl_read_bad, ioctl_write_ptr, ioctl_write_ptr_bad};
+use libc::{c_int, c_uint, ifreq, in6_ifreq, sock_fprog};
+use nix::{
+    ioctl_read, ioctl_read_bad, ioctl_readwrite_bad, ioctl_write_int, ioctl_write_ptr,
+    ioctl_write_ptr_bad,
+};
 
 ioctl_read_bad!(siocgifflags, 0x8913, ifreq);
 ioctl_write_ptr_bad!(siocsifflags, 0x8914, ifreq);
@@ -22,12 +25,23 @@ ioctl_write_ptr_bad!(siocsifname, 0x8923, ifreq);
 ioctl_write_ptr_bad!(siocsifhwaddr, 0x8924, ifreq);
 ioctl_read_bad!(tx_queue_len, 0x8942, ifreq);
 ioctl_write_ptr_bad!(change_tx_queue_len, 0x8943, ifreq);
+ioctl_readwrite_bad!(siocethtool, 0x8946, ifreq);
 
+ioctl_read!(tungetfeatures, b'T', 207, c_uint);
 ioctl_read!(tungetiff, b'T', 210, c_int);
+ioctl_read!(tungetvnethdrsz, b'T', 215, c_int);
+ioctl_read!(tungetsndbuf, b'T', 211, c_int);
 
 ioctl_write_ptr!(tunsetiff, b'T', 202, c_int);
-ioctl_write_ptr!(tunsetpersist, b'T', 203, c_int);
-ioctl_write_ptr!(tunsetowner, b'T', 204, c_int);
-ioctl_write_ptr!(tunsetgroup, b'T', 206, c_int);
+// The kernel reads these arguments by value rather than through a pointer.
+ioctl_write_int!(tunsetpersist, b'T', 203);
+ioctl_write_int!(tunsetowner, b'T', 204);
+ioctl_write_int!(tunsetgroup, b'T', 206);
 ioctl_write_ptr!(tunsetoffload, b'T', 208, c_int);
 ioctl_write_ptr!(tunsetvnethdrsz, b'T', 216, c_int);
+ioctl_write_ptr!(tunsetsndbuf, b'T', 212, c_int);
+ioctl_write_ptr!(tunsetqueue, b'T', 217, c_int);
+ioctl_write_ptr!(tunattachfilter, b'T', 213, sock_fprog);
+ioctl_write_ptr!(tundetachfilter, b'T', 214, sock_fprog);
+ioctl_read!(tunsetsteeringebpf, b'T', 224, c_int);
+ioctl_read!(tunsetfilterebpf, b'T', 225, c_int);
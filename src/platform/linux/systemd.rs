@@ -0,0 +1,123 @@
+//! systemd socket activation and file descriptor store.
+//!
+//! https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html
+//! https://www.freedesktop.org/software/systemd/man/latest/sd_pid_notify_with_fds.html
+use crate::platform::linux::fd_passing::send_with_fd;
+use crate::platform::unix::{Fd, Tun};
+use crate::platform::{DeviceImpl, FdState};
+use std::env;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::Mutex;
+
+/// The first fd passed by systemd.
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Passed fds already taken by this process, each can only be owned once.
+static TAKEN: Mutex<Vec<RawFd>> = Mutex::new(Vec::new());
+
+/// Same rules as systemd: at most 255 printable ASCII characters without `:`.
+fn check_fd_name(name: &str) -> io::Result<()> {
+    if name.is_empty()
+        || name.len() > 255
+        || !name.bytes().all(|b| b.is_ascii_graphic() && b != b':')
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid file descriptor name {name:?}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Looks up the fd named `name` in `LISTEN_FDS`/`LISTEN_FDNAMES`.
+fn listen_fd(name: &str) -> io::Result<RawFd> {
+    // Like sd_listen_fds, the fds are only ours if LISTEN_PID names this process.
+    let pid = env::var("LISTEN_PID")
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "LISTEN_PID is not set"))?;
+    if pid.parse::<u32>().ok() != Some(std::process::id()) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "LISTEN_PID does not match this process",
+        ));
+    }
+    let count: usize = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "LISTEN_FDS is not set"))?;
+    let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+    let index = names
+        .split(':')
+        .take(count)
+        .position(|v| v == name)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no file descriptor named {name} was passed"),
+            )
+        })?;
+    Ok(SD_LISTEN_FDS_START + index as RawFd)
+}
+
+/// Sends `state` to the service manager with `fd` attached.
+fn notify_with_fd(state: &str, fd: RawFd) -> io::Result<()> {
+    let path = env::var_os("NOTIFY_SOCKET")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "NOTIFY_SOCKET is not set"))?;
+    let socket = UnixDatagram::unbound()?;
+    match path.as_bytes() {
+        [b'@', name @ ..] => socket.connect_addr(&SocketAddr::from_abstract_name(name)?)?,
+        [b'/', ..] => socket.connect(&path)?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported NOTIFY_SOCKET {path:?}"),
+            ))
+        }
+    }
+    send_with_fd(socket.as_fd(), state.as_bytes(), fd)
+}
+
+impl DeviceImpl {
+    pub(crate) fn from_listen_fds(name: &str) -> io::Result<Self> {
+        check_fd_name(name)?;
+        let fd = listen_fd(name)?;
+        let mut taken = TAKEN.lock().unwrap();
+        if taken.contains(&fd) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("file descriptor {name} was already taken"),
+            ));
+        }
+        // Checked before taking ownership, the fd may not be ours to close.
+        let state = FdState::detect(unsafe { BorrowedFd::borrow_raw(fd) })?;
+        let fd = Fd::new(fd)?;
+        taken.push(fd.as_raw_fd());
+        // Passed fds are inherited without FD_CLOEXEC.
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+            log::warn!("failed to set FD_CLOEXEC: {:?}", io::Error::last_os_error());
+        }
+        Ok(DeviceImpl::from_state(Tun::new(fd), state))
+    }
+}
+
+//Public User Interface
+impl DeviceImpl {
+    /// Stores a duplicate of the device fd in the systemd file descriptor store
+    /// (`FDSTORE=1`) under `name`.
+    ///
+    /// After a restart of the service, systemd passes the fd back, and the queue is
+    /// re-attached with [`SyncDevice::from_listen_fds`](crate::SyncDevice::from_listen_fds).
+    /// Only the queue fd is stored: the interface's addresses and routes are still there
+    /// because the stored fd kept the interface alive, and are not restored if it was removed.
+    /// The unit needs `FileDescriptorStoreMax=` to be set.
+    ///
+    /// The name is at most 255 printable ASCII characters and must not contain `:`.
+    pub fn store_in_fdstore(&self, name: &str) -> io::Result<()> {
+        let _guard = self.op_lock.lock().unwrap();
+        check_fd_name(name)?;
+        notify_with_fd(&format!("FDSTORE=1\nFDNAME={name}\n"), self.as_raw_fd())
+    }
+}
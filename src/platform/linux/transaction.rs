@@ -0,0 +1,345 @@
+use crate::platform::linux::address::{AddressFlags, AddressOptions, InterfaceAddress};
+use crate::platform::linux::route::Route;
+use crate::platform::{DeviceImpl, ETHER_ADDR_LEN};
+use crate::{ToIpv4Address, ToIpv4Netmask, ToIpv6Address, ToIpv6Netmask};
+use ipnet::IpNet;
+use libc::IFF_UP;
+use std::io;
+use std::net::IpAddr;
+
+#[derive(Clone, Debug)]
+struct NewAddress {
+    net: IpNet,
+    peer: Option<IpAddr>,
+    options: AddressOptions,
+}
+
+impl From<&InterfaceAddress> for NewAddress {
+    fn from(address: &InterfaceAddress) -> Self {
+        NewAddress {
+            net: address.net,
+            peer: address.peer,
+            options: AddressOptions {
+                nodad: address.flags.contains(AddressFlags::NODAD),
+                noprefixroute: address.flags.contains(AddressFlags::NOPREFIXROUTE),
+                preferred_lifetime: address.preferred_lifetime,
+                valid_lifetime: address.valid_lifetime,
+            },
+        }
+    }
+}
+
+impl NewAddress {
+    fn matches(&self, address: &InterfaceAddress) -> bool {
+        self.net == address.net && self.peer == address.peer
+    }
+}
+
+/// Whether the installed route `installed` satisfies `route`, whose unset fields match any value.
+fn route_matches(route: &Route, installed: &Route) -> bool {
+    route.destination == installed.destination
+        && route.table == installed.table
+        && route.metric.is_none_or(|v| installed.metric == Some(v))
+        && route.source.is_none_or(|v| installed.source == Some(v))
+}
+
+/// A single applied change, kept to undo it on rollback.
+#[derive(Debug)]
+enum Change {
+    Mtu { new: u16, old: u16 },
+    MacAddress { new: [u8; 6], old: [u8; 6] },
+    AddAddress(NewAddress),
+    RemoveAddress(NewAddress),
+    Enabled(bool),
+    AddRoute(Route),
+    RemoveRoute(Route),
+}
+
+impl Change {
+    fn apply(&self, device: &DeviceImpl) -> io::Result<()> {
+        match self {
+            Change::Mtu { new, .. } => device.set_mtu_impl(*new),
+            Change::MacAddress { new, .. } => device.set_mac_address_impl(*new),
+            Change::AddAddress(v) => device.add_address_impl(v.net, v.peer, &v.options),
+            Change::RemoveAddress(v) => device.remove_address_impl(v.net),
+            Change::Enabled(v) => device.enabled_impl(*v),
+            Change::AddRoute(route) => device.add_route_impl(route),
+            Change::RemoveRoute(route) => device.remove_route_impl(route),
+        }
+    }
+    fn undo(&self, device: &DeviceImpl) -> io::Result<()> {
+        match self {
+            Change::Mtu { old, .. } => device.set_mtu_impl(*old),
+            Change::MacAddress { old, .. } => device.set_mac_address_impl(*old),
+            Change::AddAddress(v) => device.remove_address_impl(v.net),
+            Change::RemoveAddress(v) => device.add_address_impl(v.net, v.peer, &v.options),
+            Change::Enabled(v) => device.enabled_impl(!*v),
+            Change::AddRoute(route) => device.remove_route_impl(route),
+            Change::RemoveRoute(route) => device.add_route_impl(route),
+        }
+    }
+}
+
+/// A set of address, MTU, route, MAC and up/down changes applied to a device at once.
+///
+/// [`commit`](Self::commit) compares the changes with the current state of the device,
+/// applies only what differs, and on the first error undoes the applied changes
+/// in reverse order, so the device is left as it was.
+/// [`DeviceBuilder`](crate::DeviceBuilder) configures new devices the same way.
+///
+/// # Example
+/// ```no_run
+/// use tun_rs::{DeviceBuilder, DeviceConfigTransaction};
+/// let dev = DeviceBuilder::new()
+///     .ipv4("10.0.0.1", 24, None)
+///     .build_sync()
+///     .unwrap();
+/// // Readdress and shrink the MTU without a window in which the device has no address.
+/// DeviceConfigTransaction::new()
+///     .mtu(1400)
+///     .ipv4("10.0.1.1", 24, None)
+///     .route("10.1.0.0/16".parse::<ipnet::IpNet>().unwrap())
+///     .commit(&dev)
+///     .unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct DeviceConfigTransaction {
+    mtu: Option<u16>,
+    mac_addr: Option<[u8; ETHER_ADDR_LEN as usize]>,
+    ipv4: Option<io::Result<NewAddress>>,
+    add_addresses: Vec<io::Result<NewAddress>>,
+    remove_addresses: Vec<IpAddr>,
+    enabled: Option<bool>,
+    add_routes: Vec<Route>,
+    remove_routes: Vec<Route>,
+}
+
+impl DeviceConfigTransaction {
+    /// Creates an empty transaction.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the MTU.
+    pub fn mtu(mut self, mtu: u16) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+    /// Sets the MAC address, only supported by TAP devices.
+    pub fn mac_addr(mut self, mac_addr: [u8; ETHER_ADDR_LEN as usize]) -> Self {
+        self.mac_addr = Some(mac_addr);
+        self
+    }
+    /// Replaces all IPv4 addresses with `address`, like [`DeviceImpl::set_network_address`].
+    ///
+    /// The new address is added before the old ones are removed.
+    pub fn ipv4<IPv4: ToIpv4Address, Netmask: ToIpv4Netmask>(
+        mut self,
+        address: IPv4,
+        mask: Netmask,
+        destination: Option<IPv4>,
+    ) -> Self {
+        let address = || -> io::Result<NewAddress> {
+            Ok(NewAddress {
+                net: IpNet::new(address.ipv4()?.into(), mask.prefix()?)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+                peer: destination.map(|v| v.ipv4()).transpose()?.map(IpAddr::V4),
+                options: AddressOptions::new(),
+            })
+        };
+        self.ipv4 = Some(address());
+        self
+    }
+    /// Adds an IPv6 address unless it is already assigned.
+    pub fn ipv6<IPv6: ToIpv6Address, Netmask: ToIpv6Netmask>(
+        self,
+        address: IPv6,
+        mask: Netmask,
+    ) -> Self {
+        self.ipv6_with(address, mask, AddressOptions::new())
+    }
+    /// Adds an IPv6 address with flags and lifetimes unless it is already assigned,
+    /// see [`DeviceImpl::add_address_v6_with`].
+    pub fn ipv6_with<IPv6: ToIpv6Address, Netmask: ToIpv6Netmask>(
+        mut self,
+        address: IPv6,
+        mask: Netmask,
+        options: AddressOptions,
+    ) -> Self {
+        let address = || -> io::Result<NewAddress> {
+            Ok(NewAddress {
+                net: IpNet::new(address.ipv6()?.into(), mask.prefix()?)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+                peer: None,
+                options,
+            })
+        };
+        self.add_addresses.push(address());
+        self
+    }
+    /// Removes an address, with any prefix length, if it is assigned.
+    pub fn remove_address(mut self, address: IpAddr) -> Self {
+        self.remove_addresses.push(address);
+        self
+    }
+    /// Brings the device up or down.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = Some(enabled);
+        self
+    }
+    /// Adds a route unless it is already installed.
+    ///
+    /// Routes are changed after the device has been enabled.
+    pub fn route<R: Into<Route>>(mut self, route: R) -> Self {
+        self.add_routes.push(route.into());
+        self
+    }
+    /// Removes a route if it is installed.
+    pub fn remove_route<R: Into<Route>>(mut self, route: R) -> Self {
+        self.remove_routes.push(route.into());
+        self
+    }
+    /// Applies the transaction to `device`, rolling back to the previous state on error.
+    pub fn commit(self, device: &DeviceImpl) -> io::Result<()> {
+        let _guard = device.op_lock.lock().unwrap();
+        self.commit_impl(device)
+    }
+    pub(crate) fn commit_impl(self, device: &DeviceImpl) -> io::Result<()> {
+        let mut transaction = Transaction {
+            device,
+            applied: Vec::new(),
+            routes: None,
+        };
+        if let Err(e) = transaction.run(self) {
+            transaction.rollback();
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+struct Transaction<'a> {
+    device: &'a DeviceImpl,
+    applied: Vec<Change>,
+    /// The routes before any change, restored on rollback.
+    routes: Option<Vec<Route>>,
+}
+
+impl Transaction<'_> {
+    fn apply(&mut self, change: Change) -> io::Result<()> {
+        change.apply(self.device)?;
+        self.applied.push(change);
+        Ok(())
+    }
+    fn run(&mut self, config: DeviceConfigTransaction) -> io::Result<()> {
+        let device = self.device;
+        let ipv4 = config.ipv4.transpose()?;
+        let add_addresses = config
+            .add_addresses
+            .into_iter()
+            .collect::<io::Result<Vec<_>>>()?;
+        if let Some(new) = config.mtu {
+            let old = device.mtu_impl()?;
+            if new != old {
+                self.apply(Change::Mtu { new, old })?;
+            }
+        }
+        if let Some(new) = config.mac_addr {
+            let old = device.mac_address_impl()?;
+            if new != old {
+                self.apply(Change::MacAddress { new, old })?;
+            }
+        }
+
+        if ipv4.is_some() || !add_addresses.is_empty() || !config.remove_addresses.is_empty() {
+            // Removing an address also removes the routes through it.
+            self.routes = Some(device.routes_impl()?);
+            self.change_addresses(ipv4, add_addresses, &config.remove_addresses)?;
+        }
+
+        if let Some(enabled) = config.enabled {
+            let up = device.ifru_flags()? & IFF_UP as i16 != 0;
+            if enabled != up {
+                self.apply(Change::Enabled(enabled))?;
+            }
+        }
+
+        if !config.add_routes.is_empty() || !config.remove_routes.is_empty() {
+            let current = device.routes_impl()?;
+            for route in config.remove_routes {
+                for installed in current.iter().filter(|v| route_matches(&route, v)) {
+                    self.apply(Change::RemoveRoute(installed.clone()))?;
+                }
+            }
+            for route in config.add_routes {
+                if !current.iter().any(|v| route_matches(&route, v)) {
+                    self.apply(Change::AddRoute(route))?;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn change_addresses(
+        &mut self,
+        ipv4: Option<NewAddress>,
+        mut add_addresses: Vec<NewAddress>,
+        remove_addresses: &[IpAddr],
+    ) -> io::Result<()> {
+        let device = self.device;
+        let current = device.interface_addresses_impl()?;
+        let replaced_ipv4 = ipv4.is_some();
+        add_addresses.extend(ipv4);
+        let (conflicting, removed): (Vec<&InterfaceAddress>, Vec<_>) = current
+            .iter()
+            .filter(|v| !add_addresses.iter().any(|a| a.matches(v)))
+            .filter(|v| {
+                (replaced_ipv4 && v.net.addr().is_ipv4())
+                    || remove_addresses.contains(&v.net.addr())
+            })
+            .partition(|v| add_addresses.iter().any(|a| a.net == v.net));
+        // Only an address whose peer changes is removed before it is added again,
+        // all others are added first, so the device keeps an address while it is readdressed.
+        for address in conflicting {
+            self.apply(Change::RemoveAddress(address.into()))?;
+        }
+        for address in &add_addresses {
+            if !current.iter().any(|v| address.matches(v)) {
+                self.apply(Change::AddAddress(address.clone()))?;
+            }
+        }
+        for address in removed {
+            self.apply(Change::RemoveAddress(address.into()))?;
+        }
+        // With promote_secondaries off, removing a primary IPv4 address also removes
+        // the secondary addresses of its subnet, which may include the new one.
+        let current = device.interface_addresses_impl()?;
+        for address in &add_addresses {
+            if !current.iter().any(|v| address.matches(v)) {
+                self.apply(Change::AddAddress(address.clone()))?;
+            }
+        }
+        Ok(())
+    }
+    fn rollback(self) {
+        let device = self.device;
+        for change in self.applied.iter().rev() {
+            if let Err(e) = change.undo(device) {
+                log::warn!("failed to roll back {change:?}: {e:?}");
+            }
+        }
+        let Some(routes) = self.routes else {
+            return;
+        };
+        let current = match device.routes_impl() {
+            Ok(current) => current,
+            Err(e) => {
+                log::warn!("failed to list routes for rollback: {e:?}");
+                return;
+            }
+        };
+        for route in routes.iter().filter(|v| !current.contains(v)) {
+            if let Err(e) = device.add_route_impl(route) {
+                log::warn!("failed to restore route {route:?}: {e:?}");
+            }
+        }
+    }
+}
@@ -107,10 +107,24 @@ impl SyncDevice {
     /// - It must be valid and open.
     ///
     /// This function is only available on Unix platforms.
+    ///
+    /// On Linux, the layer, offload and multi-queue state are detected from the fd,
+    /// see [`FdState::detect`].
     #[cfg(unix)]
     pub unsafe fn from_fd(fd: RawFd) -> std::io::Result<Self> {
         Ok(SyncDevice(DeviceImpl::from_fd(fd)?))
     }
+    /// Creates a new SyncDevice from a raw file descriptor with the given state
+    /// instead of detecting it.
+    ///
+    /// # Safety
+    /// - The file descriptor (`fd`) must be an owned file descriptor.
+    /// - It must be valid and open.
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    pub unsafe fn from_fd_with_state(fd: RawFd, state: FdState) -> std::io::Result<Self> {
+        let tun = crate::platform::unix::Tun::new(crate::platform::unix::Fd::new_unchecked(fd));
+        Ok(SyncDevice(DeviceImpl::from_state(tun, state)))
+    }
     /// # Safety
     /// The fd passed in must be a valid, open file descriptor.
     /// Unlike [`from_fd`], this function does **not** take ownership of `fd`,
@@ -301,6 +315,24 @@ impl SyncDevice {
 }
 #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
 impl SyncDevice {
+    /// Receives a device fd sent with [`DeviceImpl::send_fd`] over a Unix socket (`SCM_RIGHTS`).
+    ///
+    /// The device flags are recovered with `TUNGETIFF`, so the layer, offload (vnet_hdr)
+    /// and multi-queue state match the sending device, and `try_clone` keeps working
+    /// if the receiver is allowed to open the device.
+    pub fn from_received_fd<S: std::os::fd::AsFd>(socket: &S) -> std::io::Result<SyncDevice> {
+        Ok(SyncDevice(DeviceImpl::recv_fd(socket)?))
+    }
+    /// Takes the device fd named `name` passed by systemd (`LISTEN_FDS`/`LISTEN_FDNAMES`),
+    /// e.g. one stored with [`DeviceImpl::store_in_fdstore`] before a restart.
+    ///
+    /// The state of the device is recovered as with [`SyncDevice::from_fd`].
+    /// Returns a `NotFound` error if no such fd was passed to this process,
+    /// and an `AlreadyExists` error if it was already taken.
+    #[cfg(feature = "systemd")]
+    pub fn from_listen_fds(name: &str) -> std::io::Result<SyncDevice> {
+        Ok(SyncDevice(DeviceImpl::from_listen_fds(name)?))
+    }
     #[cfg(feature = "interruptible")]
     pub fn send_multiple_intr<B: ExpandBuffer>(
         &self,
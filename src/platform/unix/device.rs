@@ -59,18 +59,30 @@ impl DeviceImpl {
 
     /// Recv a packet from tun device
     pub(crate) fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        self.tun.recv(buf)
+        let len = self.tun.recv(buf)?;
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        self.count_rx(len);
+        Ok(len)
     }
     pub(crate) fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
-        self.tun.recv_vectored(bufs)
+        let len = self.tun.recv_vectored(bufs)?;
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        self.count_rx(len);
+        Ok(len)
     }
 
     /// Send a packet to tun device
     pub(crate) fn send(&self, buf: &[u8]) -> io::Result<usize> {
-        self.tun.send(buf)
+        let len = self.tun.send(buf)?;
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        self.count_tx(len);
+        Ok(len)
     }
     pub(crate) fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
-        self.tun.send_vectored(bufs)
+        let len = self.tun.send_vectored(bufs)?;
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        self.count_tx(len);
+        Ok(len)
     }
     #[cfg(feature = "interruptible")]
     pub(crate) fn read_interruptible(
@@ -78,7 +90,10 @@ impl DeviceImpl {
         buf: &mut [u8],
         event: &crate::InterruptEvent,
     ) -> io::Result<usize> {
-        self.tun.read_interruptible(buf, event)
+        let len = self.tun.read_interruptible(buf, event)?;
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        self.count_rx(len);
+        Ok(len)
     }
     #[cfg(feature = "interruptible")]
     pub(crate) fn readv_interruptible(
@@ -86,7 +101,10 @@ impl DeviceImpl {
         bufs: &mut [IoSliceMut<'_>],
         event: &crate::InterruptEvent,
     ) -> io::Result<usize> {
-        self.tun.readv_interruptible(bufs, event)
+        let len = self.tun.readv_interruptible(bufs, event)?;
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        self.count_rx(len);
+        Ok(len)
     }
     #[cfg(feature = "interruptible")]
     #[inline]
@@ -102,7 +120,10 @@ impl DeviceImpl {
         buf: &[u8],
         event: &crate::InterruptEvent,
     ) -> io::Result<usize> {
-        self.tun.write_interruptible(buf, event)
+        let len = self.tun.write_interruptible(buf, event)?;
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        self.count_tx(len);
+        Ok(len)
     }
     #[cfg(feature = "interruptible")]
     #[inline]
@@ -111,7 +132,10 @@ impl DeviceImpl {
         bufs: &[IoSlice<'_>],
         event: &crate::InterruptEvent,
     ) -> io::Result<usize> {
-        self.tun.writev_interruptible(bufs, event)
+        let len = self.tun.writev_interruptible(bufs, event)?;
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        self.count_tx(len);
+        Ok(len)
     }
     #[cfg(feature = "interruptible")]
     #[inline]
@@ -141,7 +165,11 @@ impl DeviceImpl {
     }
     pub(crate) fn if_index_impl(&self) -> io::Result<u32> {
         let if_name = std::ffi::CString::new(self.name_impl()?)?;
-        unsafe { Ok(libc::if_nametoindex(if_name.as_ptr())) }
+        let index = unsafe { libc::if_nametoindex(if_name.as_ptr()) };
+        if index == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(index)
     }
     /// Retrieves all IP addresses associated with the network interface.
     ///
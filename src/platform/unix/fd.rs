@@ -1,6 +1,6 @@
 use std::io;
 use std::io::{IoSlice, IoSliceMut};
-use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, RawFd};
 
 use libc::{self, fcntl, F_GETFL, O_NONBLOCK};
 
@@ -147,6 +147,12 @@ impl AsRawFd for Fd {
     }
 }
 
+impl AsFd for Fd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.inner) }
+    }
+}
+
 impl IntoRawFd for Fd {
     fn into_raw_fd(mut self) -> RawFd {
         let fd = self.inner;
@@ -439,3 +439,1461 @@ fn create_tap() {
         }
     }
 }
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_route() {
+    use tun_rs::Route;
+    let device = DeviceBuilder::new()
+        .ipv4("10.26.4.100", 24, None)
+        .ipv6("fd12:3456:789a:6666::1", 64)
+        .route("10.26.5.0/24".parse::<ipnet::IpNet>().unwrap())
+        .build_sync()
+        .unwrap();
+    let routes = device.routes().unwrap();
+    assert!(routes
+        .iter()
+        .any(|r| r.destination == "10.26.5.0/24".parse().unwrap() && r.table.is_none()));
+
+    let v4 = Route::new("10.26.6.0/24".parse().unwrap())
+        .metric(42)
+        .table(1234)
+        .source("10.26.4.100".parse().unwrap());
+    device.add_route(&v4).unwrap();
+    let v6 = Route::new("fd12:3456:789a:7777::/64".parse().unwrap()).metric(7);
+    device.add_route(&v6).unwrap();
+    let routes = device.routes().unwrap();
+    assert!(routes.contains(&v4), "{routes:?}");
+    assert!(routes.contains(&v6), "{routes:?}");
+
+    device.remove_route(&v4).unwrap();
+    device.remove_route(&v6).unwrap();
+    let routes = device.routes().unwrap();
+    assert!(!routes.contains(&v4));
+    assert!(!routes.contains(&v6));
+    assert!(device.remove_route(&v4).is_err());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_rule() {
+    use tun_rs::RoutingRule;
+    let device = DeviceBuilder::new()
+        .ipv4("10.26.7.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let rules = [
+        RoutingRule::ipv4(4321)
+            .priority(4321)
+            .fwmark(0x4321)
+            .invert(true),
+        RoutingRule::ipv4(4322)
+            .priority(4322)
+            .source("10.26.7.0/24".parse().unwrap())
+            .uid_range(1000, 2000)
+            .suppress_prefixlength(0),
+        RoutingRule::ipv6(4323).priority(4323),
+    ];
+    device.add_rules(rules.clone()).unwrap();
+    assert_eq!(device.rules(), rules);
+    for rule in &rules {
+        let err = device.add_rule(rule).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EEXIST));
+    }
+    assert_eq!(device.rules(), rules);
+    device.remove_rule(&rules[2]).unwrap();
+    assert_eq!(device.rules(), &rules[..2]);
+    assert!(device.remove_rule(&rules[2]).is_err());
+    drop(device);
+    let device = DeviceBuilder::new()
+        .ipv4("10.26.7.100", 24, None)
+        .rule(rules[2].clone())
+        .build_sync()
+        .unwrap();
+    // The rules of the dropped device are gone.
+    for rule in &rules[..2] {
+        assert!(device.remove_rule(rule).is_err());
+    }
+    assert_eq!(device.rules(), &rules[2..]);
+    assert_eq!(device.release_rules(), &rules[2..]);
+    drop(device);
+    let device = DeviceBuilder::new().build_sync().unwrap();
+    device.remove_rule(&rules[2]).unwrap();
+    assert!(device
+        .add_rule(&RoutingRule::ipv6(1).source("10.0.0.0/8".parse().unwrap()))
+        .is_err());
+    // A failed batch rolls back the rules it added.
+    assert!(device
+        .add_rules([rules[0].clone(), rules[0].clone()])
+        .is_err());
+    assert!(device.rules().is_empty());
+    assert!(device.remove_rule(&rules[0]).is_err());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_netns() {
+    use std::os::fd::AsRawFd;
+    use tun_rs::NetNs;
+    let ns_file = std::thread::spawn(|| {
+        assert_eq!(unsafe { libc::unshare(libc::CLONE_NEWNET) }, 0);
+        std::fs::File::open("/proc/thread-self/ns/net").unwrap()
+    })
+    .join()
+    .unwrap();
+    let netns = NetNs::from(ns_file.as_raw_fd());
+
+    let device = DeviceBuilder::new()
+        .name("tunns0")
+        .netns(netns.clone())
+        .ipv4("10.26.8.100", 24, None)
+        .mtu(1400)
+        .build_sync()
+        .unwrap();
+    assert!(device.mtu().is_err());
+    let (mtu, addresses) = netns
+        .run(|| Ok((device.mtu()?, device.addresses()?)))
+        .unwrap();
+    assert_eq!(mtu, 1400);
+    assert!(addresses.contains(&"10.26.8.100".parse().unwrap()));
+
+    // Additional queues are opened in the device's namespace.
+    let device = DeviceBuilder::new()
+        .name("tunns3")
+        .netns(netns.clone())
+        .multi_queue(true)
+        .build_sync()
+        .unwrap();
+    let queue = device.try_clone().unwrap();
+    assert_eq!(netns.run(|| device.queue_count()).unwrap(), 2);
+    drop(queue);
+
+    let device = DeviceBuilder::new().name("tunns1").build_sync().unwrap();
+    assert!(device.if_index().is_ok());
+    device.move_to_netns(netns.clone()).unwrap();
+    assert!(device.if_index().is_err());
+    netns
+        .run(|| device.set_network_address("10.26.9.100", 24, None))
+        .unwrap();
+    let addresses = netns.run(|| device.addresses()).unwrap();
+    assert!(addresses.contains(&"10.26.9.100".parse().unwrap()));
+    // Link queries follow the namespace of the calling thread, not the sysfs mount.
+    assert_eq!(netns.run(|| device.queue_count()).unwrap(), 1);
+    assert!(netns.run(|| device.statistics()).is_ok());
+
+    let device = DeviceBuilder::new()
+        .name("tunns2")
+        .netns(netns.clone())
+        .persist(true)
+        .build_sync()
+        .unwrap();
+    drop(device);
+    let device = DeviceBuilder::new()
+        .attach_existing("tunns2")
+        .netns(netns.clone())
+        .build_sync()
+        .unwrap();
+    netns.run(|| device.destroy()).unwrap();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_filter() {
+    use std::time::Duration;
+    use tun_rs::{Layer, PacketFilter};
+    let tun = DeviceBuilder::new().build_sync().unwrap();
+    let filter = PacketFilter::new().ipv4_only().build().unwrap();
+    assert!(tun.attach_filter(&filter).is_err());
+
+    let device = DeviceBuilder::new()
+        .layer(Layer::L2)
+        .multi_queue(true)
+        .ipv4("10.26.10.1", 24, None)
+        .route("239.26.10.0/24".parse::<ipnet::IpNet>().unwrap())
+        .build_sync()
+        .unwrap();
+    device.set_nonblocking(true).unwrap();
+    let socket = std::net::UdpSocket::bind("10.26.10.1:0").unwrap();
+    let send = || {
+        socket.send_to(b"filter", "239.26.10.1:7777").unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    let drain = |dev: &tun_rs::SyncDevice| {
+        let mut buf = [0u8; 2048];
+        let mut found = false;
+        while let Ok(len) = dev.recv(&mut buf) {
+            found |= len > 34 && buf[12..14] == [0x08, 0x00] && buf[30..34] == [239, 26, 10, 1];
+        }
+        found
+    };
+    let filter = PacketFilter::new()
+        .ipv4_only()
+        .drop_multicast()
+        .destination("10.26.10.0/24".parse().unwrap())
+        .destination("fd00::/8".parse().unwrap())
+        .build()
+        .unwrap();
+    device.attach_filter(&filter).unwrap();
+    let queue = device.try_clone().unwrap();
+    queue.set_nonblocking(true).unwrap();
+    drain(&device);
+    drain(&queue);
+    send();
+    assert!(!drain(&device) && !drain(&queue));
+
+    let filter = PacketFilter::new()
+        .destination("239.26.0.0/16".parse().unwrap())
+        .build()
+        .unwrap();
+    device.attach_filter(&filter).unwrap();
+    send();
+    assert!(drain(&device) || drain(&queue));
+
+    device.detach_filter().unwrap();
+    send();
+    assert!(drain(&device) || drain(&queue));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_steering() {
+    use std::time::Duration;
+    let device = DeviceBuilder::new()
+        .multi_queue(true)
+        .ipv4("10.26.11.1", 24, None)
+        .build_sync()
+        .unwrap();
+    let queue = device.try_clone().unwrap();
+    assert!(device.set_filter_ebpf(Some(i32::MAX)).is_err());
+    device.set_filter_ebpf(None).unwrap();
+    device.steer_by_destination().unwrap();
+    device.set_nonblocking(true).unwrap();
+    queue.set_nonblocking(true).unwrap();
+
+    let socket = std::net::UdpSocket::bind("10.26.11.1:0").unwrap();
+    for _ in 0..4 {
+        socket.send_to(b"steer", "10.26.11.2:7777").unwrap();
+        socket.send_to(b"steer", "10.26.11.3:7777").unwrap();
+    }
+    std::thread::sleep(Duration::from_millis(100));
+    let count = |dev: &tun_rs::SyncDevice| {
+        let mut buf = [0u8; 2048];
+        let mut count = [0; 2];
+        while let Ok(len) = dev.recv(&mut buf) {
+            if len > 20 && buf[0] >> 4 == 4 && buf[16..19] == [10, 26, 11] {
+                count[buf[19] as usize - 2] += 1;
+            }
+        }
+        count
+    };
+    let (a, b) = (count(&device), count(&queue));
+    assert_eq!([a[0] + b[0], a[1] + b[1]], [4, 4]);
+    // each destination is pinned to one queue, and the two hash to different queues
+    assert!(a == [4, 0] && b == [0, 4] || a == [0, 4] && b == [4, 0]);
+    device.set_steering_ebpf(None).unwrap();
+
+    let tap = DeviceBuilder::new()
+        .layer(tun_rs::Layer::L2)
+        .multi_queue(true)
+        .build_sync()
+        .unwrap();
+    tap.steer_by_destination().unwrap();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_queue_attach_detach() {
+    let device = DeviceBuilder::new()
+        .multi_queue(true)
+        .ipv4("10.26.12.1", 24, None)
+        .build_sync()
+        .unwrap();
+    assert_eq!(device.queue_count().unwrap(), 1);
+    let queues: Vec<_> = (0..3).map(|_| device.try_clone().unwrap()).collect();
+    assert_eq!(device.queue_count().unwrap(), 4);
+    queues[0].detach_queue().unwrap();
+    queues[1].detach_queue().unwrap();
+    assert_eq!(device.queue_count().unwrap(), 2);
+    assert!(queues[0].detach_queue().is_err());
+    queues[0].attach_queue().unwrap();
+    assert_eq!(device.queue_count().unwrap(), 3);
+    drop(queues);
+    assert_eq!(device.queue_count().unwrap(), 1);
+
+    let single = DeviceBuilder::new().build_sync().unwrap();
+    assert_eq!(single.queue_count().unwrap(), 1);
+    assert!(single.detach_queue().is_err());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(all(feature = "async_tokio", feature = "async_framed"))]
+#[tokio::test]
+async fn test_multi_queue_device() {
+    use futures::StreamExt;
+    let device = DeviceBuilder::new()
+        .ipv4("10.26.13.1", 24, None)
+        .build_multi_queue(3)
+        .unwrap();
+    assert_eq!(device.len(), 3);
+    assert_eq!(device.queues().count(), 3);
+    assert_eq!(device.queues().next().unwrap().queue_count().unwrap(), 3);
+
+    let mut stream = device.recv_stream();
+    let socket = tokio::net::UdpSocket::bind("10.26.13.1:0").await.unwrap();
+    for port in 0..8u16 {
+        socket
+            .send_to(b"multi queue", ("10.26.13.2", 7000 + port))
+            .await
+            .unwrap();
+    }
+    let mut received = 0;
+    let mut replies = Vec::new();
+    while received < 8 {
+        let packet = tokio::time::timeout(Duration::from_secs(2), stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let Some(ipv4) = pnet_packet::ipv4::Ipv4Packet::new(&packet) else {
+            continue;
+        };
+        if ipv4.get_next_level_protocol() != IpNextHeaderProtocols::Udp {
+            continue;
+        }
+        let udp = pnet_packet::udp::UdpPacket::new(ipv4.payload()).unwrap();
+        if udp.payload() != b"multi queue" {
+            continue;
+        }
+        received += 1;
+        // reply through the flow-hash dispatcher, in one batch
+        let mut reply = packet.to_vec();
+        reply[12..16].copy_from_slice(&ipv4.get_destination().octets());
+        reply[16..20].copy_from_slice(&ipv4.get_source().octets());
+        reply[20..22].copy_from_slice(&udp.get_destination().to_be_bytes());
+        reply[22..24].copy_from_slice(&udp.get_source().to_be_bytes());
+        reply[26..28].copy_from_slice(&[0, 0]);
+        replies.push(reply);
+    }
+    let sent = replies.clone();
+    device
+        .send_multiple(&mut tun_rs::GROTable::new(), &mut replies, 0)
+        .await
+        .unwrap();
+    assert_eq!(replies, sent);
+    let mut buf = [0u8; 64];
+    for _ in 0..8 {
+        let (len, from) = tokio::time::timeout(Duration::from_secs(2), socket.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..len], b"multi queue");
+        assert_eq!(from.ip(), "10.26.13.2".parse::<std::net::IpAddr>().unwrap());
+    }
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_capabilities() {
+    let probed = DeviceBuilder::probe().unwrap();
+    assert_ne!(probed.features & libc::IFF_TUN as u32, 0);
+    assert!(probed.multi_queue && probed.vnet_hdr && probed.ebpf);
+
+    let device = DeviceBuilder::new().build_sync().unwrap();
+    let capabilities = device.capabilities().unwrap();
+    assert_eq!(capabilities.features, probed.features);
+    assert!(!capabilities.multi_queue && !capabilities.vnet_hdr && !capabilities.tcp_offload);
+
+    let device = DeviceBuilder::new()
+        .offload(probed.tcp_offload)
+        .multi_queue(true)
+        .strict(true)
+        .build_sync()
+        .unwrap();
+    let capabilities = device.capabilities().unwrap();
+    assert!(capabilities.multi_queue);
+    assert_eq!(capabilities.tcp_offload, probed.tcp_offload);
+    assert_eq!(capabilities.udp_offload, probed.udp_offload);
+    assert_eq!(capabilities.tcp_offload, device.tcp_gso());
+    assert_eq!(capabilities.udp_offload, device.udp_gso());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_statistics() {
+    let device = DeviceBuilder::new()
+        .name("tunstat0")
+        .ipv4("10.26.14.1", 24, None)
+        .io_counters(true)
+        .build_sync()
+        .unwrap();
+    let plain = DeviceBuilder::new().name("tunstat1").build_sync().unwrap();
+    assert!(plain.io_counters().is_none());
+    let before = device.statistics().unwrap();
+
+    let socket = std::net::UdpSocket::bind("10.26.14.1:0").unwrap();
+    socket.send_to(b"statistics", "10.26.14.2:8080").unwrap();
+    let mut buf = [0; 1500];
+    let mut reads = 0;
+    let len = loop {
+        let len = device.recv(&mut buf).unwrap();
+        reads += 1;
+        if buf[..len].ends_with(b"statistics") {
+            break len;
+        }
+    };
+    device.send(&buf[..len]).unwrap();
+
+    let counters = device.io_counters().unwrap();
+    assert_eq!(counters.rx_packets, reads);
+    assert_eq!(counters.tx_packets, 1);
+    assert_eq!(counters.tx_bytes, len as u64);
+
+    let after = device.statistics().unwrap();
+    assert!(after.tx_packets >= before.tx_packets + reads);
+    assert!(after.rx_packets > before.rx_packets);
+    assert!(after.rx_bytes >= before.rx_bytes + len as u64);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "async_tokio")]
+#[tokio::test]
+async fn test_watch_events() {
+    use futures::StreamExt;
+    use tun_rs::LinkEvent;
+    let device = DeviceBuilder::new()
+        .name("tunev0")
+        .ipv4("10.26.15.1", 24, None)
+        .build_async()
+        .unwrap();
+    let mut events = device.watch_events().unwrap();
+    device.set_mtu(1400).unwrap();
+    device.add_address_v4("10.26.15.2", 24).unwrap();
+    // Changing an existing address is not reported as another AddressAdded.
+    assert!(std::process::Command::new("ip")
+        .args(["addr", "change", "10.26.15.2/24", "dev", "tunev0"])
+        .args(["valid_lft", "1000", "preferred_lft", "1000"])
+        .status()
+        .unwrap()
+        .success());
+    device.enabled(false).unwrap();
+    device.set_name("tunev1").unwrap();
+    device
+        .remove_address("10.26.15.2".parse().unwrap())
+        .unwrap();
+    drop(device);
+
+    let mut received = Vec::new();
+    while let Some(event) = tokio::time::timeout(Duration::from_secs(2), events.next())
+        .await
+        .unwrap()
+    {
+        received.push(event.unwrap());
+    }
+    let expected = [
+        LinkEvent::MtuChanged(1400),
+        LinkEvent::AddressAdded("10.26.15.2/24".parse().unwrap()),
+        LinkEvent::Down,
+        LinkEvent::Renamed("tunev1".into()),
+        LinkEvent::AddressRemoved("10.26.15.2/24".parse().unwrap()),
+        LinkEvent::Deleted,
+    ];
+    let mut position = 0;
+    for event in &expected {
+        position += received[position..]
+            .iter()
+            .position(|v| v == event)
+            .unwrap_or_else(|| panic!("{event:?} not in {received:?}"))
+            + 1;
+    }
+    assert_eq!(received.last(), Some(&LinkEvent::Deleted));
+    let added = LinkEvent::AddressAdded("10.26.15.2/24".parse().unwrap());
+    assert_eq!(received.iter().filter(|v| **v == added).count(), 1);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_master() {
+    let master = |name: &str| {
+        std::fs::read_link(format!("/sys/class/net/{name}/master"))
+            .ok()
+            .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned()))
+    };
+    let device = DeviceBuilder::new()
+        .name("tapmaster0")
+        .layer(tun_rs::Layer::L2)
+        .master("brmaster0")
+        .create_bridge(true)
+        .build_sync()
+        .unwrap();
+    assert_eq!(master("tapmaster0").as_deref(), Some("brmaster0"));
+    device.clear_master().unwrap();
+    assert_eq!(master("tapmaster0"), None);
+    device.set_master("brmaster0").unwrap();
+    assert_eq!(master("tapmaster0").as_deref(), Some("brmaster0"));
+    let err = device.set_master("brmaster-none").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    drop(device);
+    _ = std::process::Command::new("ip")
+        .args(["link", "del", "brmaster0"])
+        .status();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_persist() {
+    use std::io::ErrorKind;
+    use tun_rs::Layer;
+    let sysfs =
+        |field: &str| std::fs::read_to_string(format!("/sys/class/net/tappersist0/{field}"));
+    let device = DeviceBuilder::new()
+        .name("tappersist0")
+        .layer(Layer::L2)
+        .owner(1000)
+        .group(1001)
+        .persist(true)
+        .build_sync()
+        .unwrap();
+    drop(device);
+    assert_eq!(sysfs("owner").unwrap().trim(), "1000");
+    assert_eq!(sysfs("group").unwrap().trim(), "1001");
+
+    let err = DeviceBuilder::new()
+        .attach_existing("tappersist0")
+        .build_sync()
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    let err = DeviceBuilder::new()
+        .attach_existing("tappersist0")
+        .layer(Layer::L2)
+        .multi_queue(true)
+        .build_sync()
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    let err = DeviceBuilder::new()
+        .attach_existing("lo")
+        .build_sync()
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    let err = DeviceBuilder::new()
+        .attach_existing("tapmissing0")
+        .build_sync()
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let device = DeviceBuilder::new()
+        .attach_existing("tappersist0")
+        .layer(Layer::L2)
+        .build_sync()
+        .unwrap();
+    assert_eq!(device.name().unwrap(), "tappersist0");
+    device.destroy().unwrap();
+    assert_eq!(sysfs("owner").unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_fd_passing() {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use tun_rs::SyncDevice;
+    let (helper, daemon) = UnixStream::pair().unwrap();
+    let device = DeviceBuilder::new()
+        .name("tunpass0")
+        .ipv4("10.26.16.1", 24, None)
+        .offload(true)
+        .multi_queue(true)
+        .build_sync()
+        .unwrap();
+    let (tcp_gso, udp_gso) = (device.tcp_gso(), device.udp_gso());
+    device.send_fd(&helper).unwrap();
+    drop(device);
+
+    let device = SyncDevice::from_received_fd(&daemon).unwrap();
+    assert_eq!(device.name().unwrap(), "tunpass0");
+    assert_eq!(device.tcp_gso(), tcp_gso);
+    assert_eq!(device.udp_gso(), udp_gso);
+    assert!(device.capabilities().unwrap().multi_queue);
+    let queue = device.try_clone().unwrap();
+    assert_eq!(queue.tcp_gso(), tcp_gso);
+    assert_eq!(device.queue_count().unwrap(), 2);
+
+    // More than one fd in a message is rejected, and all of them are closed.
+    let file = std::fs::File::open("/dev/null").unwrap();
+    let fds = [file.as_raw_fd(); 2];
+    unsafe {
+        let mut byte = 0u8;
+        let mut iov = libc::iovec {
+            iov_base: &mut byte as *mut u8 as *mut _,
+            iov_len: 1,
+        };
+        let mut cmsg_buf = [0u64; 4];
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+        msg.msg_controllen = libc::CMSG_SPACE(size_of_val(&fds) as _) as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(size_of_val(&fds) as _) as _;
+        std::ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut _, 2);
+        assert_eq!(libc::sendmsg(helper.as_raw_fd(), &msg, 0), 1);
+    }
+    let err = SyncDevice::from_received_fd(&daemon).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    drop(helper);
+    let err = SyncDevice::from_received_fd(&daemon).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_from_fd_state() {
+    use std::os::fd::{AsFd, AsRawFd};
+    use tun_rs::{FdState, Layer, SyncDevice};
+    let device = DeviceBuilder::new()
+        .name("tapfd0")
+        .layer(Layer::L2)
+        .offload(true)
+        .multi_queue(true)
+        .build_sync()
+        .unwrap();
+    let state = FdState::detect(device.as_fd()).unwrap();
+    assert_eq!(
+        state,
+        FdState {
+            layer: Layer::L2,
+            packet_information: false,
+            multi_queue: true,
+            vnet_hdr: device.tcp_gso(),
+            vnet_hdr_len: device.vnet_hdr_len(),
+            udp_gso: device.udp_gso(),
+        }
+    );
+
+    let fd = unsafe { libc::dup(device.as_raw_fd()) };
+    let inherited = unsafe { SyncDevice::from_fd(fd).unwrap() };
+    assert_eq!(inherited.tcp_gso(), device.tcp_gso());
+    assert_eq!(inherited.udp_gso(), device.udp_gso());
+    let queue = inherited.try_clone().unwrap();
+    assert_eq!(queue.name().unwrap(), "tapfd0");
+
+    let fd = unsafe { libc::dup(device.as_raw_fd()) };
+    let overridden = unsafe {
+        SyncDevice::from_fd_with_state(
+            fd,
+            FdState {
+                udp_gso: false,
+                ..state
+            },
+        )
+        .unwrap()
+    };
+    assert!(!overridden.udp_gso());
+    assert_eq!(overridden.tcp_gso(), device.tcp_gso());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "systemd"))]
+#[test]
+fn test_systemd_fdstore() {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixDatagram;
+    use tun_rs::SyncDevice;
+    let device = DeviceBuilder::new()
+        .name("tunsd0")
+        .ipv4("10.26.17.1", 24, None)
+        .build_sync()
+        .unwrap();
+    let path = std::env::temp_dir().join(format!("tun-rs-notify-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let notify = UnixDatagram::bind(&path).unwrap();
+    std::env::set_var("NOTIFY_SOCKET", &path);
+    device.store_in_fdstore("tun").unwrap();
+    assert!(device.store_in_fdstore("tun:0").is_err());
+    std::env::remove_var("NOTIFY_SOCKET");
+
+    let mut payload = [0u8; 64];
+    let mut iov = libc::iovec {
+        iov_base: payload.as_mut_ptr() as *mut _,
+        iov_len: payload.len(),
+    };
+    let mut cmsg_buf = [0u64; 4];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+    msg.msg_controllen = std::mem::size_of_val(&cmsg_buf) as _;
+    let n = unsafe { libc::recvmsg(notify.as_raw_fd(), &mut msg, 0) };
+    assert!(n > 0);
+    assert_eq!(&payload[..n as usize], b"FDSTORE=1\nFDNAME=tun\n");
+    let fd = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        assert_eq!((*cmsg).cmsg_type, libc::SCM_RIGHTS);
+        std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int)
+    };
+    drop(notify);
+    std::fs::remove_file(&path).unwrap();
+
+    // Pretend the stored fd was passed by systemd after a restart.
+    let index = fd - 3;
+    let mut names = vec!["other"; index as usize];
+    names.push("tun");
+    std::env::set_var("LISTEN_PID", std::process::id().to_string());
+    std::env::set_var("LISTEN_FDS", (index + 1).to_string());
+    std::env::set_var("LISTEN_FDNAMES", names.join(":"));
+    let restored = SyncDevice::from_listen_fds("tun").unwrap();
+    assert_eq!(restored.as_raw_fd(), fd);
+    assert_eq!(restored.name().unwrap(), "tunsd0");
+    let err = SyncDevice::from_listen_fds("tun").err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+    let err = SyncDevice::from_listen_fds("missing").err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    std::env::remove_var("LISTEN_PID");
+    let err = SyncDevice::from_listen_fds("tun").err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_ethtool_features() {
+    use tun_rs::EthtoolFeature;
+    let device = DeviceBuilder::new()
+        .name("tunfeat0")
+        .offload(true)
+        .build_sync()
+        .unwrap();
+    let features = device.features().unwrap();
+    assert!(features.iter().any(|(name, _)| name == "rx-gro"));
+    assert!(features.is_active(EthtoolFeature::TcpSegmentation));
+    assert_eq!(
+        features.is_active(EthtoolFeature::UdpSegmentation),
+        device.udp_gso()
+    );
+
+    for feature in [
+        EthtoolFeature::GenericReceive,
+        EthtoolFeature::TcpSegmentation,
+    ] {
+        device.set_features(&[(feature, false)]).unwrap();
+        assert!(!device.features().unwrap().is_active(feature));
+        device.set_features(&[(feature, true)]).unwrap();
+        assert!(device.features().unwrap().is_active(feature));
+    }
+
+    // Without TUNSETOFFLOAD, the kernel refuses to turn TSO on.
+    let plain = DeviceBuilder::new().name("tunfeat1").build_sync().unwrap();
+    assert!(!plain
+        .features()
+        .unwrap()
+        .is_active(EthtoolFeature::TcpSegmentation));
+    assert!(plain
+        .set_features(&[(EthtoolFeature::TcpSegmentation, true)])
+        .is_err());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_vnet_hdr_len_sndbuf() {
+    use std::os::fd::AsFd;
+    use tun_rs::{FdState, GROTable, VIRTIO_NET_HDR_MRG_RXBUF_LEN};
+    let device = DeviceBuilder::new()
+        .name("tunhdr0")
+        .ipv4("10.26.18.1", 24, None)
+        .offload(true)
+        .vnet_hdr_len(VIRTIO_NET_HDR_MRG_RXBUF_LEN)
+        .sndbuf(1 << 20)
+        .build_sync()
+        .unwrap();
+    assert_eq!(device.vnet_hdr_len(), VIRTIO_NET_HDR_MRG_RXBUF_LEN);
+    assert_eq!(
+        FdState::detect(device.as_fd()).unwrap().vnet_hdr_len,
+        VIRTIO_NET_HDR_MRG_RXBUF_LEN
+    );
+    assert_eq!(device.sndbuf().unwrap(), 1 << 20);
+    device.set_sndbuf(i32::MAX).unwrap();
+    assert_eq!(device.sndbuf().unwrap(), i32::MAX);
+
+    // Kernel to device: the packet follows the 12-byte header.
+    let socket = std::net::UdpSocket::bind("10.26.18.1:0").unwrap();
+    socket.send_to(b"mrg_rxbuf", "10.26.18.2:8080").unwrap();
+    device.set_nonblocking(true).unwrap();
+    let mut original_buffer = vec![0; VIRTIO_NET_HDR_MRG_RXBUF_LEN + 65535];
+    let mut bufs = vec![vec![0u8; 1500]; 4];
+    let mut sizes = vec![0; 4];
+    let deadline = std::time::Instant::now() + Duration::from_secs(2);
+    let mut received = false;
+    while !received && std::time::Instant::now() < deadline {
+        let num = match device.recv_multiple(&mut original_buffer, &mut bufs, &mut sizes, 0) {
+            Ok(num) => num,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            Err(e) => panic!("{e:?}"),
+        };
+        for (buf, size) in bufs.iter().zip(&sizes).take(num) {
+            let Some(ipv4) = pnet_packet::ipv4::Ipv4Packet::new(&buf[..*size]) else {
+                continue;
+            };
+            if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Udp {
+                let udp = pnet_packet::udp::UdpPacket::new(ipv4.payload()).unwrap();
+                received |= udp.payload() == b"mrg_rxbuf";
+            }
+        }
+    }
+    assert!(received);
+
+    // Device to kernel: send_multiple leaves room for the 12-byte header.
+    let port = socket.local_addr().unwrap().port();
+    let payload = b"from tun";
+    let mut packet = vec![0u8; 28 + payload.len()];
+    packet[0] = 0x45;
+    let total_len = packet.len() as u16;
+    packet[2..4].copy_from_slice(&total_len.to_be_bytes());
+    packet[8] = 64;
+    packet[9] = 17;
+    packet[12..16].copy_from_slice(&[10, 26, 18, 2]);
+    packet[16..20].copy_from_slice(&[10, 26, 18, 1]);
+    let mut sum = packet[..20]
+        .chunks(2)
+        .map(|v| u16::from_be_bytes([v[0], v[1]]) as u32)
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    packet[10..12].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+    packet[20..22].copy_from_slice(&9u16.to_be_bytes());
+    packet[22..24].copy_from_slice(&port.to_be_bytes());
+    packet[24..26].copy_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+    packet[28..].copy_from_slice(payload);
+    let mut buf = vec![0xffu8; VIRTIO_NET_HDR_MRG_RXBUF_LEN];
+    buf.extend_from_slice(&packet);
+    let mut gro_table = GROTable::default();
+    device
+        .send_multiple(&mut gro_table, &mut [buf], VIRTIO_NET_HDR_MRG_RXBUF_LEN)
+        .unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let mut recv_buf = [0u8; 64];
+    let (len, _) = socket.recv_from(&mut recv_buf).unwrap();
+    assert_eq!(&recv_buf[..len], payload);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_interface_addresses() {
+    use tun_rs::{AddressFlags, AddressOptions, AddressScope};
+    let device = DeviceBuilder::new()
+        .name("tunaddr0")
+        .ipv4("10.26.19.1", 24, None)
+        .ipv6_with("fd00:26:19::1", 64, AddressOptions::new().nodad(true))
+        .build_sync()
+        .unwrap();
+    device
+        .add_address_v6_with(
+            "fd00:26:20::1",
+            64,
+            &AddressOptions::new()
+                .nodad(true)
+                .noprefixroute(true)
+                .lifetimes(Duration::from_secs(600), Duration::from_secs(3600)),
+        )
+        .unwrap();
+    let invalid = AddressOptions::new().lifetimes(Duration::from_secs(20), Duration::from_secs(10));
+    assert_eq!(
+        device
+            .add_address_v6_with("fd00:26:21::1", 64, &invalid)
+            .unwrap_err()
+            .kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    let addresses = device.interface_addresses().unwrap();
+    let v4 = addresses
+        .iter()
+        .find(|v| v.net == "10.26.19.1/24".parse().unwrap())
+        .unwrap();
+    assert_eq!(v4.scope, AddressScope::Global);
+    assert_eq!(v4.valid_lifetime, None);
+
+    let first = addresses
+        .iter()
+        .find(|v| v.net == "fd00:26:19::1/64".parse().unwrap())
+        .unwrap();
+    assert!(first
+        .flags
+        .contains(AddressFlags::NODAD | AddressFlags::PERMANENT));
+    assert!(!first.flags.contains(AddressFlags::TENTATIVE));
+    assert_eq!(first.preferred_lifetime, None);
+
+    let second = addresses
+        .iter()
+        .find(|v| v.net == "fd00:26:20::1/64".parse().unwrap())
+        .unwrap();
+    assert_eq!(second.scope, AddressScope::Global);
+    assert!(second
+        .flags
+        .contains(AddressFlags::NODAD | AddressFlags::NOPREFIXROUTE));
+    assert!(!second.flags.contains(AddressFlags::TENTATIVE));
+    let preferred = second.preferred_lifetime.unwrap();
+    let valid = second.valid_lifetime.unwrap();
+    assert!(preferred <= Duration::from_secs(600) && preferred > Duration::from_secs(590));
+    assert!(valid <= Duration::from_secs(3600) && valid > Duration::from_secs(3590));
+
+    let routes = device.routes().unwrap();
+    assert!(routes
+        .iter()
+        .any(|v| v.destination == "fd00:26:19::/64".parse().unwrap()));
+    assert!(!routes
+        .iter()
+        .any(|v| v.destination == "fd00:26:20::/64".parse().unwrap()));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_ipv6_conf() {
+    use tun_rs::{AddressScope, Ipv6AddrGenMode};
+    let device = DeviceBuilder::new()
+        .name("tunra0")
+        .ipv4("10.26.20.1", 24, None)
+        .ipv6_addr_gen_mode(Ipv6AddrGenMode::None)
+        .accept_ra(false)
+        .build_sync()
+        .unwrap();
+    assert_eq!(device.ipv6_addr_gen_mode().unwrap(), Ipv6AddrGenMode::None);
+    assert!(!device.accept_ra().unwrap());
+    assert!(!device
+        .interface_addresses()
+        .unwrap()
+        .iter()
+        .any(|v| v.scope == AddressScope::Link));
+
+    // Without the option the kernel assigns a link-local address.
+    let plain = DeviceBuilder::new()
+        .name("tunra1")
+        .ipv4("10.26.21.1", 24, None)
+        .build_sync()
+        .unwrap();
+    assert_ne!(plain.ipv6_addr_gen_mode().unwrap(), Ipv6AddrGenMode::None);
+    assert!(plain
+        .interface_addresses()
+        .unwrap()
+        .iter()
+        .any(|v| v.scope == AddressScope::Link));
+
+    device.set_ipv6_disabled(true).unwrap();
+    assert!(device.ipv6_disabled().unwrap());
+    assert!(device.add_address_v6("fd00:26:20::1", 64).is_err());
+    device.set_ipv6_disabled(false).unwrap();
+    device.add_address_v6("fd00:26:20::1", 64).unwrap();
+
+    device.set_ipv6_conf("router_solicitations", "0").unwrap();
+    assert_eq!(device.ipv6_conf("router_solicitations").unwrap(), "0");
+    assert_eq!(
+        device.ipv6_conf("../all/forwarding").unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_config_transaction() {
+    use std::net::{IpAddr, Ipv4Addr};
+    use tun_rs::{DeviceConfigTransaction, Route};
+    let device = DeviceBuilder::new()
+        .name("tuntx0")
+        .ipv4("10.26.22.1", 24, None)
+        .mtu(1500)
+        .route("10.26.24.0/24".parse::<ipnet::IpNet>().unwrap())
+        .build_sync()
+        .unwrap();
+    let ipv4 = |device: &SyncDevice| -> Vec<ipnet::IpNet> {
+        device
+            .interface_addresses()
+            .unwrap()
+            .into_iter()
+            .map(|v| v.net)
+            .filter(|v| v.addr().is_ipv4())
+            .collect()
+    };
+    let has_route = |device: &SyncDevice, destination: &str| -> bool {
+        let destination = destination.parse().unwrap();
+        device
+            .routes()
+            .unwrap()
+            .iter()
+            .any(|v| v.destination == destination)
+    };
+
+    // A failing route rolls back the MTU and the new address.
+    let err = DeviceConfigTransaction::new()
+        .mtu(1400)
+        .ipv4("10.26.23.1", 24, None)
+        .route(
+            Route::new("10.26.25.0/24".parse().unwrap())
+                .source(IpAddr::V4(Ipv4Addr::new(10, 99, 99, 99))),
+        )
+        .commit(&device);
+    assert!(err.is_err());
+    assert_eq!(device.mtu().unwrap(), 1500);
+    assert_eq!(ipv4(&device), vec!["10.26.22.1/24".parse().unwrap()]);
+    assert!(has_route(&device, "10.26.22.0/24"));
+    assert!(has_route(&device, "10.26.24.0/24"));
+    assert!(!has_route(&device, "10.26.23.0/24"));
+
+    DeviceConfigTransaction::new()
+        .mtu(1400)
+        .ipv4("10.26.23.1", 24, None)
+        .ipv6("fd00:26:23::1", 64)
+        .remove_route("10.26.24.0/24".parse::<ipnet::IpNet>().unwrap())
+        .route("10.26.25.0/24".parse::<ipnet::IpNet>().unwrap())
+        .commit(&device)
+        .unwrap();
+    assert_eq!(device.mtu().unwrap(), 1400);
+    assert_eq!(ipv4(&device), vec!["10.26.23.1/24".parse().unwrap()]);
+    assert!(!has_route(&device, "10.26.24.0/24"));
+    assert!(has_route(&device, "10.26.25.0/24"));
+    assert!(device
+        .interface_addresses()
+        .unwrap()
+        .iter()
+        .any(|v| v.net == "fd00:26:23::1/64".parse().unwrap()));
+
+    // Unchanged settings are not applied again.
+    DeviceConfigTransaction::new()
+        .mtu(1400)
+        .ipv4("10.26.23.1", 24, None)
+        .route("10.26.25.0/24".parse::<ipnet::IpNet>().unwrap())
+        .enabled(true)
+        .commit(&device)
+        .unwrap();
+
+    // Readdressing within the same subnet keeps the new address.
+    device.set_network_address("10.26.23.2", 24, None).unwrap();
+    assert_eq!(ipv4(&device), vec!["10.26.23.2/24".parse().unwrap()]);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_recv_gso() {
+    use std::os::fd::AsRawFd;
+    use tun_rs::{ChecksumState, GsoType};
+    let device = DeviceBuilder::new()
+        .name("tungso0")
+        .ipv4("10.26.26.1", 24, None)
+        .offload(true)
+        .build_sync()
+        .unwrap();
+    if !device.udp_gso() {
+        // USO needs Linux 6.2+.
+        return;
+    }
+    // One 3000-byte send with UDP_SEGMENT reaches the device as a single super-packet.
+    let socket = std::net::UdpSocket::bind("10.26.26.1:0").unwrap();
+    let gso_size: libc::c_int = 1000;
+    let rs = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_UDP,
+            libc::UDP_SEGMENT,
+            &gso_size as *const _ as *const libc::c_void,
+            size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    assert_eq!(rs, 0);
+    let payload = vec![7u8; 3000];
+    socket.send_to(&payload, "10.26.26.2:8080").unwrap();
+
+    device.set_nonblocking(true).unwrap();
+    let mut buf = vec![0; device.vnet_hdr_len() + 65535];
+    let deadline = std::time::Instant::now() + Duration::from_secs(2);
+    let (info, packet) = loop {
+        assert!(std::time::Instant::now() < deadline);
+        match device.recv_gso(&mut buf) {
+            Ok((info, packet)) if info.gso_type == GsoType::UdpL4 => break (info, packet),
+            Ok(_) => continue,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) => panic!("{e:?}"),
+        }
+    };
+    assert_eq!(packet.len(), 28 + 3000);
+    assert_eq!(info.gso_size, 1000);
+    assert_eq!(info.hdr_len, 28);
+    assert_eq!(
+        info.checksum,
+        ChecksumState::Partial {
+            start: 20,
+            offset: 6
+        }
+    );
+    assert_eq!(info.segments(packet), 3);
+    let segments = info.iter_segments(packet).unwrap();
+    assert_eq!(segments.len(), 3);
+    let mut iter_bufs = vec![[0u8; 1500]; 3];
+    for (segment, buf) in segments.zip(&mut iter_bufs) {
+        assert_eq!(segment.header().len(), 28);
+        assert_eq!(segment.copy_to(buf).unwrap(), segment.len());
+    }
+
+    let mut bufs = vec![vec![0u8; 1500]; 4];
+    let mut sizes = vec![0; 4];
+    let num = info.split(packet, &mut bufs, &mut sizes, 0).unwrap();
+    assert_eq!(num, 3);
+    // The lazy iterator yields the same packets.
+    for (buf, iter_buf) in bufs.iter().zip(&iter_bufs) {
+        assert_eq!(buf[..1028], iter_buf[..1028]);
+    }
+    for (buf, size) in bufs.iter().zip(&sizes).take(num) {
+        assert_eq!(*size, 1028);
+        let ipv4 = pnet_packet::ipv4::Ipv4Packet::new(&buf[..*size]).unwrap();
+        assert_eq!(ipv4.get_total_length(), 1028);
+        let udp = pnet_packet::udp::UdpPacket::new(ipv4.payload()).unwrap();
+        assert_eq!(udp.get_length(), 1008);
+        assert_eq!(
+            udp.get_checksum(),
+            pnet_packet::udp::ipv4_checksum(&udp, &ipv4.get_source(), &ipv4.get_destination())
+        );
+        assert_eq!(udp.payload(), &payload[..1000]);
+    }
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_send_gso() {
+    use tun_rs::{GsoInfo, GsoType};
+    // Offloaded to the kernel, and split in software without offload.
+    for (name, subnet, offload) in [("tungso1", 27, true), ("tungso2", 28, false)] {
+        let device = DeviceBuilder::new()
+            .name(name)
+            .ipv4(format!("10.26.{subnet}.1"), 24, None)
+            .offload(offload)
+            .build_sync()
+            .unwrap();
+        let socket = std::net::UdpSocket::bind(format!("10.26.{subnet}.1:0")).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let port = socket.local_addr().unwrap().port();
+
+        // An IPv4/UDP super-packet from 10.26.x.2:9000 with 3000 bytes of payload,
+        // lengths and checksums are filled in by send_gso.
+        let mut packet = vec![0u8; 28 + 3000];
+        packet[0] = 0x45;
+        packet[8] = 64;
+        packet[9] = 17;
+        packet[12..16].copy_from_slice(&[10, 26, subnet, 2]);
+        packet[16..20].copy_from_slice(&[10, 26, subnet, 1]);
+        packet[20..22].copy_from_slice(&9000u16.to_be_bytes());
+        packet[22..24].copy_from_slice(&port.to_be_bytes());
+        for (i, v) in packet[28..].iter_mut().enumerate() {
+            *v = (i / 1000) as u8;
+        }
+        let info = GsoInfo::new(GsoType::UdpL4, 1000);
+        assert_eq!(device.send_gso(&mut packet, info).unwrap(), 3028);
+
+        let mut buf = [0u8; 2000];
+        for i in 0..3u8 {
+            let (len, from) = socket.recv_from(&mut buf).unwrap();
+            assert_eq!(from, format!("10.26.{subnet}.2:9000").parse().unwrap());
+            assert_eq!(len, 1000);
+            assert!(buf[..len].iter().all(|v| *v == i));
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_gso_info_split() {
+    use tun_rs::{GsoInfo, GsoType};
+    // An IPv4/UDP packet with 3000 bytes of payload, whose lengths and checksums
+    // are not filled in: split parses the headers and recomputes them.
+    let mut packet = vec![0u8; 28 + 3000];
+    packet[0] = 0x45;
+    packet[8] = 64;
+    packet[9] = 17;
+    packet[12..16].copy_from_slice(&[10, 26, 33, 2]);
+    packet[16..20].copy_from_slice(&[10, 26, 33, 1]);
+    packet[20..22].copy_from_slice(&9000u16.to_be_bytes());
+    packet[22..24].copy_from_slice(&8080u16.to_be_bytes());
+    for (i, v) in packet[28..].iter_mut().enumerate() {
+        *v = (i / 1000) as u8;
+    }
+
+    let info = GsoInfo::new(GsoType::UdpL4, 1000);
+    assert_eq!(info.segments(&packet), 3);
+    assert_eq!(info.iter_segments(&packet).unwrap().len(), 3);
+    let mut bufs = vec![vec![0u8; 1500]; 4];
+    let mut sizes = vec![0; 4];
+    let num = info.split(&mut packet, &mut bufs, &mut sizes, 0).unwrap();
+    assert_eq!(num, 3);
+    for (i, (buf, size)) in bufs.iter().zip(&sizes).take(num).enumerate() {
+        assert_eq!(*size, 1028);
+        let ipv4 = pnet_packet::ipv4::Ipv4Packet::new(&buf[..*size]).unwrap();
+        assert_eq!(ipv4.get_total_length(), 1028);
+        assert_eq!(ipv4_header_checksum(&buf[..20]), 0);
+        let udp = pnet_packet::udp::UdpPacket::new(ipv4.payload()).unwrap();
+        assert_eq!(udp.get_length(), 1008);
+        assert_eq!(
+            udp.get_checksum(),
+            pnet_packet::udp::ipv4_checksum(&udp, &ipv4.get_source(), &ipv4.get_destination())
+        );
+        assert!(udp.payload().iter().all(|v| *v == i as u8));
+    }
+
+    // Invalid descriptions and undersized buffers are errors, not panics.
+    let zero = GsoInfo::new(GsoType::UdpL4, 0);
+    assert_eq!(zero.segments(&packet), 0);
+    assert!(zero.iter_segments(&packet).is_err());
+    assert!(zero.split(&mut packet, &mut bufs, &mut sizes, 0).is_err());
+    let tcp = GsoInfo::new(GsoType::TcpV4, 1000);
+    assert_eq!(tcp.segments(&packet), 0);
+    assert!(tcp.split(&mut packet, &mut bufs, &mut sizes, 0).is_err());
+    let mut small = vec![vec![0u8; 500]; 4];
+    assert!(info.split(&mut packet, &mut small, &mut sizes, 0).is_err());
+    assert!(info
+        .split(&mut packet[..10], &mut bufs, &mut sizes, 0)
+        .is_err());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_send_checksum_partial() {
+    use tun_rs::GROTable;
+    // Completed by the kernel with offload, and in software without it.
+    for (name, subnet, offload) in [("tuncsum0", 29, true), ("tuncsum1", 30, false)] {
+        let device = DeviceBuilder::new()
+            .name(name)
+            .ipv4(format!("10.26.{subnet}.1"), 24, None)
+            .offload(offload)
+            .build_sync()
+            .unwrap();
+        let socket = std::net::UdpSocket::bind(format!("10.26.{subnet}.1:0")).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let port = socket.local_addr().unwrap().port();
+
+        // IPv4/UDP packets from 10.26.x.2:9000 with a bogus UDP checksum,
+        // which is overwritten.
+        let offset = device.vnet_hdr_len();
+        let mut bufs: Vec<Vec<u8>> = (0..3u8)
+            .map(|i| {
+                let mut buf = Vec::with_capacity(offset + 65536);
+                buf.resize(offset + 28 + 100, 0);
+                let packet = &mut buf[offset..];
+                packet[0] = 0x45;
+                packet[2..4].copy_from_slice(&128u16.to_be_bytes());
+                packet[8] = 64;
+                packet[9] = 17;
+                packet[12..16].copy_from_slice(&[10, 26, subnet, 2]);
+                packet[16..20].copy_from_slice(&[10, 26, subnet, 1]);
+                let csum = ipv4_header_checksum(&packet[..20]);
+                packet[10..12].copy_from_slice(&csum.to_be_bytes());
+                packet[20..22].copy_from_slice(&9000u16.to_be_bytes());
+                packet[22..24].copy_from_slice(&port.to_be_bytes());
+                packet[24..26].copy_from_slice(&108u16.to_be_bytes());
+                packet[26..28].copy_from_slice(&0xdeadu16.to_be_bytes());
+                packet[28..].fill(i);
+                buf
+            })
+            .collect();
+        let mut gro_table = GROTable::new();
+        gro_table.set_checksum_partial(true);
+        device
+            .send_multiple(&mut gro_table, &mut bufs, offset)
+            .unwrap();
+
+        let mut buf = [0u8; 2000];
+        for i in 0..3u8 {
+            let (len, from) = socket.recv_from(&mut buf).unwrap();
+            assert_eq!(from, format!("10.26.{subnet}.2:9000").parse().unwrap());
+            assert_eq!(len, 100);
+            assert!(buf[..len].iter().all(|v| *v == i));
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+fn ipv4_header_checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|v| u16::from_be_bytes([v[0], v[1]]) as u32)
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_tap_offload() {
+    use std::os::fd::AsRawFd;
+    use std::process::Command;
+    use tun_rs::{ChecksumState, GROTable, GsoType, Layer};
+    let device = DeviceBuilder::new()
+        .name("tapgso0")
+        .layer(Layer::L2)
+        .ipv4("10.26.31.1", 24, None)
+        .offload(true)
+        .io_counters(true)
+        .build_sync()
+        .unwrap();
+    if !device.udp_gso() {
+        // USO needs Linux 6.2+.
+        return;
+    }
+    let ip = |args: &str| {
+        Command::new("ip")
+            .args(args.split(' '))
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap()
+            .success()
+    };
+    // Untagged frames on tapgso0, and frames tagged with VLAN 5 on tapgso0.5
+    // when the kernel supports 802.1Q.
+    _ = ip("link del tapgso0.5");
+    let mut cases = vec![(None, 31u8)];
+    if ip("link add link tapgso0 name tapgso0.5 type vlan id 5") {
+        assert!(ip("addr add 10.26.32.1/24 dev tapgso0.5"));
+        assert!(ip("link set tapgso0.5 up"));
+        assert!(ip(
+            "neigh replace 10.26.32.2 lladdr 02:00:00:00:1f:02 dev tapgso0.5"
+        ));
+        cases.push((Some(5u16), 32));
+    }
+    let peer_mac = [2, 0, 0, 0, 0x1f, 2];
+    assert!(ip(
+        "neigh replace 10.26.31.2 lladdr 02:00:00:00:1f:02 dev tapgso0"
+    ));
+    let mac = device.mac_address().unwrap();
+    device.set_nonblocking(true).unwrap();
+
+    for &(vlan, subnet) in &cases {
+        let l2_len = if vlan.is_some() { 18 } else { 14 };
+        let socket = std::net::UdpSocket::bind(format!("10.26.{subnet}.1:0")).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let port = socket.local_addr().unwrap().port();
+
+        // GSO split on receive.
+        let gso_size: libc::c_int = 1000;
+        let rs = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_UDP,
+                libc::UDP_SEGMENT,
+                &gso_size as *const _ as *const libc::c_void,
+                size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        assert_eq!(rs, 0);
+        socket
+            .send_to(&[7u8; 3000], format!("10.26.{subnet}.2:8080"))
+            .unwrap();
+        let mut buf = vec![0; device.vnet_hdr_len() + 65535];
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        let (info, packet) = loop {
+            assert!(std::time::Instant::now() < deadline);
+            match device.recv_gso(&mut buf) {
+                Ok((info, packet)) if info.gso_type == GsoType::UdpL4 => break (info, packet),
+                Ok(_) => continue,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => panic!("{e:?}"),
+            }
+        };
+        assert_eq!(info.l2_len, l2_len);
+        assert_eq!(info.hdr_len, l2_len + 28);
+        assert_eq!(
+            info.checksum,
+            ChecksumState::Partial {
+                start: l2_len + 20,
+                offset: 6
+            }
+        );
+        let segments = info.iter_segments(packet).unwrap();
+        assert_eq!(segments.len(), 3);
+        for segment in segments {
+            let mut frame = [0u8; 1600];
+            let len = segment.copy_to(&mut frame).unwrap();
+            assert_eq!(len, l2_len as usize + 1028);
+            assert_eq!(frame[..6], peer_mac);
+            let ipv4 = pnet_packet::ipv4::Ipv4Packet::new(&frame[l2_len as usize..len]).unwrap();
+            assert_eq!(ipv4.get_total_length(), 1028);
+            let udp = pnet_packet::udp::UdpPacket::new(ipv4.payload()).unwrap();
+            assert_eq!(udp.get_length(), 1008);
+            assert_eq!(
+                udp.get_checksum(),
+                pnet_packet::udp::ipv4_checksum(&udp, &ipv4.get_source(), &ipv4.get_destination())
+            );
+        }
+
+        // GRO on send: three datagrams of one flow are written as one frame.
+        let offset = device.vnet_hdr_len();
+        let mut bufs: Vec<Vec<u8>> = (0..3u8)
+            .map(|i| {
+                let mut frame = Vec::with_capacity(offset + 65536);
+                frame.resize(offset, 0);
+                frame.extend_from_slice(&mac);
+                frame.extend_from_slice(&peer_mac);
+                if let Some(vlan) = vlan {
+                    frame.extend_from_slice(&[0x81, 0x00]);
+                    frame.extend_from_slice(&vlan.to_be_bytes());
+                }
+                frame.extend_from_slice(&[0x08, 0x00]);
+                let mut packet = vec![0u8; 28 + 100];
+                packet[0] = 0x45;
+                packet[2..4].copy_from_slice(&128u16.to_be_bytes());
+                packet[8] = 64;
+                packet[9] = 17;
+                packet[12..16].copy_from_slice(&[10, 26, subnet, 2]);
+                packet[16..20].copy_from_slice(&[10, 26, subnet, 1]);
+                let csum = ipv4_header_checksum(&packet[..20]);
+                packet[10..12].copy_from_slice(&csum.to_be_bytes());
+                packet[20..22].copy_from_slice(&9000u16.to_be_bytes());
+                packet[22..24].copy_from_slice(&port.to_be_bytes());
+                packet[24..26].copy_from_slice(&108u16.to_be_bytes());
+                packet[28..].fill(i);
+                let udp = pnet_packet::udp::UdpPacket::new(&packet[20..]).unwrap();
+                let csum = pnet_packet::udp::ipv4_checksum(
+                    &udp,
+                    &[10, 26, subnet, 2].into(),
+                    &[10, 26, subnet, 1].into(),
+                );
+                packet[26..28].copy_from_slice(&csum.to_be_bytes());
+                frame.extend_from_slice(&packet);
+                frame
+            })
+            .collect();
+        let tx_packets = device.io_counters().unwrap().tx_packets;
+        device
+            .send_multiple(&mut GROTable::new(), &mut bufs, offset)
+            .unwrap();
+        assert_eq!(device.io_counters().unwrap().tx_packets, tx_packets + 1);
+
+        let mut buf = [0u8; 2000];
+        for i in 0..3u8 {
+            let (len, from) = socket.recv_from(&mut buf).unwrap();
+            assert_eq!(from, format!("10.26.{subnet}.2:9000").parse().unwrap());
+            assert_eq!(len, 100);
+            assert!(buf[..len].iter().all(|v| *v == i));
+        }
+    }
+    if cases.len() > 1 {
+        assert!(ip("link del tapgso0.5"));
+    }
+}